@@ -1,6 +1,11 @@
-use crate::{DB_INSERT_CHUNK_SIZE, Error, Result, retain_with_keys, user};
-use futures::{StreamExt, TryStreamExt, stream};
-use sqlx::{PgPool, Postgres};
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Result, UpsertStats, execute_upsert, guard::DeleteGuard,
+    retain_with_keys, user,
+};
+use futures::TryFutureExt;
+use itertools::Itertools;
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
+use std::collections::HashMap;
 
 #[derive(Debug, sqlx::FromRow, serde::Serialize)]
 pub struct Address {
@@ -16,6 +21,8 @@ pub const FETCH_ADDRESS_QUERY: &str = r#"
         country
     FROM
         addresses
+    WHERE
+        deleted_at IS NULL
 "#;
 
 fn fetch_address_query<'builder>() -> sqlx::QueryBuilder<'builder, Postgres> {
@@ -24,7 +31,7 @@ fn fetch_address_query<'builder>() -> sqlx::QueryBuilder<'builder, Postgres> {
 
 pub async fn by_email(pool: &PgPool, email: &str) -> Result<Vec<Address>> {
     let brns = fetch_address_query()
-        .push("WHERE user_id = ")
+        .push(" AND user_id = ")
         .push_bind(user::id_for_email(email))
         .build_query_as::<Address>()
         .fetch_all(pool)
@@ -33,21 +40,24 @@ pub async fn by_email(pool: &PgPool, email: &str) -> Result<Vec<Address>> {
     Ok(brns)
 }
 
-pub async fn upsert_many(pool: &PgPool, addresses: &[Address]) -> Result<u64> {
+pub async fn upsert_many<'c, A>(conn: A, addresses: &[Address]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if addresses.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
-    let affected: Vec<u64> = stream::iter(addresses)
-        .chunks(DB_INSERT_CHUNK_SIZE)
-        .map(Ok)
-        .and_then(|chunk| async move {
-            let result = sqlx::QueryBuilder::new(
-                r#"INSERT INTO addresses (
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in addresses.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = sqlx::QueryBuilder::new(
+            r#"INSERT INTO addresses (
                     user_id,
                     state,
                     country
                 ) "#,
-            )
+        );
+        query
             .push_values(chunk, |mut b, address| {
                 b.push_bind(&address.user_id)
                     .push_bind(&address.state)
@@ -56,22 +66,240 @@ pub async fn upsert_many(pool: &PgPool, addresses: &[Address]) -> Result<u64> {
             .push(
                 r#"ON CONFLICT(user_id) DO UPDATE SET
                 state = excluded.state,
-                country = excluded.country
+                country = excluded.country,
+                deleted_at = NULL
+            WHERE (addresses.state, addresses.country)
+                IS DISTINCT FROM (excluded.state, excluded.country)
+                OR addresses.deleted_at IS NOT NULL
             "#,
-            )
-            .build()
-            .execute(pool)
-            .await?;
-            Ok::<u64, Error>(result.rows_affected())
-        })
-        .try_collect()
-        .await?;
-    Ok(affected.iter().sum())
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
 }
 
-pub async fn retain(pool: &PgPool, addresses: &[Address]) -> Result<u64> {
-    retain_with_keys(pool, "addresses", "user_id", addresses, |address| {
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, addresses: &[Address]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(conn, guard, "addresses", "user_id", addresses, |address| {
         address.user_id.as_str()
     })
     .await
 }
+
+// ========== User Addresses ==========
+
+/// One of a user's addresses, kept in the private schema for the service role
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize)]
+pub struct UserAddress {
+    pub paragraph_id: i64,
+    pub user_uid: i64,
+    /// Position among the user's addresses
+    pub delta: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub street_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub street_address_2: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zip_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    pub is_primary: bool,
+    pub is_mailing_address: bool,
+}
+
+const FETCH_USER_ADDRESS_QUERY: &str = r#"
+    SELECT
+        paragraph_id,
+        user_uid,
+        delta,
+        street_address,
+        street_address_2,
+        city,
+        state,
+        zip_code,
+        country,
+        is_primary,
+        is_mailing_address
+    FROM
+        private.user_addresses
+    WHERE
+        deleted_at IS NULL
+"#;
+
+/// Prefer the address flagged as primary, then the first
+const PRIMARY_ORDER: &str = " ORDER BY user_uid, is_primary DESC, delta, paragraph_id";
+
+/// Prefer the address flagged for mailing, then the primary, then the first
+const MAILING_ORDER: &str =
+    " ORDER BY user_uid, is_mailing_address DESC, is_primary DESC, delta, paragraph_id";
+
+fn fetch_user_address_query<'builder>() -> QueryBuilder<'builder, Postgres> {
+    QueryBuilder::new(FETCH_USER_ADDRESS_QUERY)
+}
+
+/// All addresses of a user, in their Drupal order
+pub async fn all_for_user(pool: &PgPool, user_uid: i64) -> Result<Vec<UserAddress>> {
+    fetch_user_address_query()
+        .push(" AND user_uid = ")
+        .push_bind(user_uid)
+        .push(" ORDER BY delta, paragraph_id")
+        .build_query_as::<UserAddress>()
+        .fetch_all(pool)
+        .map_err(Into::into)
+        .await
+}
+
+/// The primary address of a user, or their first address if none is
+/// flagged as primary
+pub async fn primary(pool: &PgPool, user_uid: i64) -> Result<Option<UserAddress>> {
+    first_for_user(pool, user_uid, PRIMARY_ORDER).await
+}
+
+/// The address to mail a user at: the one flagged for mailing, or else their
+/// primary or first address
+pub async fn mailing(pool: &PgPool, user_uid: i64) -> Result<Option<UserAddress>> {
+    first_for_user(pool, user_uid, MAILING_ORDER).await
+}
+
+/// The mailing addresses of many users, as [`mailing`] picks them, by user uid
+pub async fn mailing_many(pool: &PgPool, user_uids: &[i64]) -> Result<HashMap<i64, UserAddress>> {
+    let mut query = QueryBuilder::new("SELECT DISTINCT ON (user_uid) * FROM (");
+    query
+        .push(FETCH_USER_ADDRESS_QUERY)
+        .push(" AND user_uid = ANY(")
+        .push_bind(user_uids)
+        .push(")) addresses")
+        .push(MAILING_ORDER);
+    let addresses = query
+        .build_query_as::<UserAddress>()
+        .fetch_all(pool)
+        .await?;
+    Ok(addresses
+        .into_iter()
+        .map(|address| (address.user_uid, address))
+        .collect())
+}
+
+async fn first_for_user(pool: &PgPool, user_uid: i64, order: &str) -> Result<Option<UserAddress>> {
+    fetch_user_address_query()
+        .push(" AND user_uid = ")
+        .push_bind(user_uid)
+        .push(order)
+        .push(" LIMIT 1")
+        .build_query_as::<UserAddress>()
+        .fetch_optional(pool)
+        .map_err(Into::into)
+        .await
+}
+
+pub async fn upsert_user_addresses<'c, A>(conn: A, addresses: &[UserAddress]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    if addresses.is_empty() {
+        return Ok(UpsertStats::default());
+    }
+    let unique = addresses
+        .iter()
+        .unique_by(|address| address.paragraph_id)
+        .collect_vec();
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in unique.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            r#"INSERT INTO private.user_addresses AS user_addresses (
+                    paragraph_id,
+                    user_uid,
+                    delta,
+                    street_address,
+                    street_address_2,
+                    city,
+                    state,
+                    zip_code,
+                    country,
+                    is_primary,
+                    is_mailing_address
+                ) "#,
+        );
+        query
+            .push_values(chunk, |mut b, address| {
+                b.push_bind(address.paragraph_id)
+                    .push_bind(address.user_uid)
+                    .push_bind(address.delta)
+                    .push_bind(&address.street_address)
+                    .push_bind(&address.street_address_2)
+                    .push_bind(&address.city)
+                    .push_bind(&address.state)
+                    .push_bind(&address.zip_code)
+                    .push_bind(&address.country)
+                    .push_bind(address.is_primary)
+                    .push_bind(address.is_mailing_address);
+            })
+            .push(
+                r#"ON CONFLICT(paragraph_id) DO UPDATE SET
+                user_uid = excluded.user_uid,
+                delta = excluded.delta,
+                street_address = excluded.street_address,
+                street_address_2 = excluded.street_address_2,
+                city = excluded.city,
+                state = excluded.state,
+                zip_code = excluded.zip_code,
+                country = excluded.country,
+                is_primary = excluded.is_primary,
+                is_mailing_address = excluded.is_mailing_address,
+                deleted_at = NULL
+            WHERE (
+                    user_addresses.user_uid,
+                    user_addresses.delta,
+                    user_addresses.street_address,
+                    user_addresses.street_address_2,
+                    user_addresses.city,
+                    user_addresses.state,
+                    user_addresses.zip_code,
+                    user_addresses.country,
+                    user_addresses.is_primary,
+                    user_addresses.is_mailing_address
+                ) IS DISTINCT FROM (
+                    excluded.user_uid,
+                    excluded.delta,
+                    excluded.street_address,
+                    excluded.street_address_2,
+                    excluded.city,
+                    excluded.state,
+                    excluded.zip_code,
+                    excluded.country,
+                    excluded.is_primary,
+                    excluded.is_mailing_address
+                )
+                OR user_addresses.deleted_at IS NOT NULL
+            "#,
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
+}
+
+pub async fn retain_user_addresses<'c, A>(
+    conn: A,
+    guard: &DeleteGuard,
+    addresses: &[UserAddress],
+) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(
+        conn,
+        guard,
+        "private.user_addresses",
+        "paragraph_id",
+        addresses,
+        |address| address.paragraph_id,
+    )
+    .await
+}
@@ -0,0 +1,242 @@
+//! Airstreams and their ownership periods, one per Drupal ownership
+//! paragraph, current and past.
+
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Error, Result, UpsertStats, execute_upsert, guard::DeleteGuard,
+    retain_with_keys,
+};
+use chrono::NaiveDate;
+use futures::TryFutureExt;
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub struct Airstream {
+    pub airstream_id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// `Trailer`, `Class A` or `Class B`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub airstream_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year: Option<i32>,
+    /// Feet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<f64>,
+}
+
+/// A user's ownership of an airstream, current while there is no leave date
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize)]
+pub struct Ownership {
+    pub paragraph_id: i64,
+    #[sqlx(flatten)]
+    pub airstream: Airstream,
+    pub user_uid: i64,
+    /// The partner of the owner owns it as well
+    pub include_partner: bool,
+    pub join_date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leave_date: Option<NaiveDate>,
+}
+
+const FETCH_OWNERSHIP_QUERY: &str = r#"
+    SELECT
+        o.paragraph_id,
+        o.user_uid,
+        o.include_partner,
+        o.join_date,
+        o.leave_date,
+
+        a.airstream_id,
+        a.vin,
+        a.model,
+        a.airstream_type,
+        a.year,
+        a.length
+    FROM
+        airstream_ownership o
+        JOIN airstreams a ON a.airstream_id = o.airstream_id
+    WHERE
+        o.deleted_at IS NULL
+        AND a.deleted_at IS NULL
+"#;
+
+fn fetch_ownership_query<'builder>() -> QueryBuilder<'builder, Postgres> {
+    QueryBuilder::new(FETCH_OWNERSHIP_QUERY)
+}
+
+/// All airstreams a user owns or owned, oldest first
+pub async fn by_user_uid(pool: &PgPool, uid: i64) -> Result<Vec<Ownership>> {
+    fetch_ownership_query()
+        .push(" AND o.user_uid = ")
+        .push_bind(uid)
+        .push(" ORDER BY o.join_date, o.paragraph_id")
+        .build_query_as::<Ownership>()
+        .fetch_all(pool)
+        .map_err(Error::from)
+        .await
+}
+
+/// The airstreams currently owned by the members of a club
+pub async fn current_by_club(pool: &PgPool, club_uid: i64) -> Result<Vec<Ownership>> {
+    fetch_ownership_query()
+        .push(
+            r#" AND o.leave_date IS NULL
+            AND o.user_uid IN (
+                SELECT u.uid
+                FROM members m
+                JOIN users u ON u.id = m.primary_user
+                JOIN clubs c ON c.number = m.local_club
+                WHERE m.deleted_at IS NULL AND c.uid = "#,
+        )
+        .push_bind(club_uid)
+        .push(") ORDER BY a.year, a.model, o.paragraph_id")
+        .build_query_as::<Ownership>()
+        .fetch_all(pool)
+        .map_err(Error::from)
+        .await
+}
+
+pub async fn upsert_many<'c, A>(conn: A, airstreams: &[Airstream]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    if airstreams.is_empty() {
+        return Ok(UpsertStats::default());
+    }
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in airstreams.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            "INSERT INTO airstreams(airstream_id, vin, model, airstream_type, year, length) ",
+        );
+        query
+            .push_values(chunk, |mut b, airstream| {
+                b.push_bind(airstream.airstream_id)
+                    .push_bind(&airstream.vin)
+                    .push_bind(&airstream.model)
+                    .push_bind(&airstream.airstream_type)
+                    .push_bind(airstream.year)
+                    .push_bind(airstream.length);
+            })
+            .push(
+                r#"ON CONFLICT(airstream_id) DO UPDATE SET
+                    vin = excluded.vin,
+                    model = excluded.model,
+                    airstream_type = excluded.airstream_type,
+                    year = excluded.year,
+                    length = excluded.length,
+                    deleted_at = NULL
+                WHERE (
+                        airstreams.vin,
+                        airstreams.model,
+                        airstreams.airstream_type,
+                        airstreams.year,
+                        airstreams.length
+                    ) IS DISTINCT FROM (
+                        excluded.vin,
+                        excluded.model,
+                        excluded.airstream_type,
+                        excluded.year,
+                        excluded.length
+                    )
+                    OR airstreams.deleted_at IS NOT NULL
+                "#,
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
+}
+
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, airstreams: &[Airstream]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(
+        conn,
+        guard,
+        "airstreams",
+        "airstream_id",
+        airstreams,
+        |airstream| airstream.airstream_id,
+    )
+    .await
+}
+
+pub async fn upsert_ownership<'c, A>(conn: A, ownership: &[Ownership]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    if ownership.is_empty() {
+        return Ok(UpsertStats::default());
+    }
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in ownership.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            r#"INSERT INTO airstream_ownership (
+                    paragraph_id,
+                    airstream_id,
+                    user_uid,
+                    include_partner,
+                    join_date,
+                    leave_date
+                ) "#,
+        );
+        query
+            .push_values(chunk, |mut b, owned| {
+                b.push_bind(owned.paragraph_id)
+                    .push_bind(owned.airstream.airstream_id)
+                    .push_bind(owned.user_uid)
+                    .push_bind(owned.include_partner)
+                    .push_bind(owned.join_date)
+                    .push_bind(owned.leave_date);
+            })
+            .push(
+                r#"ON CONFLICT(paragraph_id) DO UPDATE SET
+                    airstream_id = excluded.airstream_id,
+                    user_uid = excluded.user_uid,
+                    include_partner = excluded.include_partner,
+                    join_date = excluded.join_date,
+                    leave_date = excluded.leave_date,
+                    deleted_at = NULL
+                WHERE (
+                        airstream_ownership.airstream_id,
+                        airstream_ownership.user_uid,
+                        airstream_ownership.include_partner,
+                        airstream_ownership.join_date,
+                        airstream_ownership.leave_date
+                    ) IS DISTINCT FROM (
+                        excluded.airstream_id,
+                        excluded.user_uid,
+                        excluded.include_partner,
+                        excluded.join_date,
+                        excluded.leave_date
+                    )
+                    OR airstream_ownership.deleted_at IS NOT NULL
+                "#,
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
+}
+
+pub async fn retain_ownership<'c, A>(
+    conn: A,
+    guard: &DeleteGuard,
+    ownership: &[Ownership],
+) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(
+        conn,
+        guard,
+        "airstream_ownership",
+        "paragraph_id",
+        ownership,
+        |owned| owned.paragraph_id,
+    )
+    .await
+}
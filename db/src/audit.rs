@@ -0,0 +1,84 @@
+//! Field level change log of members, users and leadership.
+//!
+//! Triggers record every change the sync writes to the audited columns in
+//! `member_changes`, `user_changes` and `leadership_changes`, with the sync
+//! run that made it. Rows that appear are recorded as changes from null, rows
+//! that disappear as changes to null. Only transactions that [`start`]
+//! auditing are recorded.
+
+use crate::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::TryFutureExt;
+use sqlx::{PgConnection, PgPool};
+
+/// Record the changes the rest of the transaction makes under `run_id`
+pub async fn start(conn: &mut PgConnection, run_id: Option<i64>) -> Result<()> {
+    sqlx::query(
+        r#"SELECT
+            set_config('aci.audit', 'on', true),
+            set_config('aci.audit_run_id', coalesce($1::text, ''), true)"#,
+    )
+    .bind(run_id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Stop recording changes, returning how many the transaction recorded.
+/// They all share its start time.
+pub async fn finish(conn: &mut PgConnection) -> Result<u64> {
+    let (recorded, _): (i64, String) = sqlx::query_as(
+        r#"SELECT
+            (SELECT count(*) FROM member_changes WHERE changed_at = now())
+            + (SELECT count(*) FROM user_changes WHERE changed_at = now())
+            + (SELECT count(*) FROM leadership_changes WHERE changed_at = now()),
+            set_config('aci.audit', 'off', true)"#,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(recorded as u64)
+}
+
+/// A recorded change of a member, user or one of their leadership records
+#[derive(Debug, sqlx::FromRow, serde::Serialize)]
+pub struct Entry {
+    pub changed_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<i64>,
+    /// `member`, `user` or `leadership`
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<NaiveDate>,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+/// All recorded changes of the users, oldest first
+pub async fn timeline(pool: &PgPool, user_ids: &[String]) -> Result<Vec<Entry>> {
+    sqlx::query_as::<_, Entry>(
+        r#"
+        SELECT changed_at, run_id, 'member' AS source, NULL AS kind, NULL::bigint AS entity,
+            NULL::bigint AS role, NULL::date AS start_date, field, old_value, new_value
+        FROM member_changes WHERE user_id = ANY($1)
+        UNION ALL
+        SELECT changed_at, run_id, 'user', NULL, NULL, NULL, NULL, field, old_value, new_value
+        FROM user_changes WHERE user_id = ANY($1)
+        UNION ALL
+        SELECT changed_at, run_id, 'leadership', kind, entity, role, start_date, field, old_value,
+            new_value
+        FROM leadership_changes WHERE user_id = ANY($1)
+        ORDER BY changed_at, source, field
+        "#,
+    )
+    .bind(user_ids)
+    .fetch_all(pool)
+    .map_err(Into::into)
+    .await
+}
@@ -1,6 +1,8 @@
-use crate::{DB_INSERT_CHUNK_SIZE, Error, Result, retain_with_keys, user};
-use futures::{StreamExt, TryStreamExt, stream};
-use sqlx::{PgPool, Postgres};
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Result, UpsertStats, execute_upsert, guard::DeleteGuard,
+    retain_with_keys, user,
+};
+use sqlx::{Acquire, PgPool, Postgres};
 
 #[derive(Debug, sqlx::FromRow, serde::Serialize, Clone)]
 pub struct Brn {
@@ -14,6 +16,8 @@ pub const FETCH_BRN_QUERY: &str = r#"
         number
     FROM
         brns
+    WHERE
+        deleted_at IS NULL
 "#;
 
 fn fetch_brn_query<'builder>() -> sqlx::QueryBuilder<'builder, Postgres> {
@@ -22,7 +26,7 @@ fn fetch_brn_query<'builder>() -> sqlx::QueryBuilder<'builder, Postgres> {
 
 pub async fn by_number(pool: &PgPool, number: &str) -> Result<Option<Brn>> {
     let user = fetch_brn_query()
-        .push("WHERE number = ")
+        .push(" AND number = ")
         .push_bind(number)
         .build_query_as::<Brn>()
         .fetch_optional(pool)
@@ -33,7 +37,7 @@ pub async fn by_number(pool: &PgPool, number: &str) -> Result<Option<Brn>> {
 
 pub async fn by_email(pool: &PgPool, email: &str) -> Result<Vec<Brn>> {
     let brns = fetch_brn_query()
-        .push("WHERE user_id = ")
+        .push(" AND user_id = ")
         .push_bind(user::id_for_email(email))
         .build_query_as::<Brn>()
         .fetch_all(pool)
@@ -42,38 +46,45 @@ pub async fn by_email(pool: &PgPool, email: &str) -> Result<Vec<Brn>> {
     Ok(brns)
 }
 
-pub async fn upsert_many(pool: &PgPool, brns: &[Brn]) -> Result<u64> {
+pub async fn upsert_many<'c, A>(conn: A, brns: &[Brn]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if brns.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
-    let affected: Vec<u64> = stream::iter(brns)
-        .chunks(DB_INSERT_CHUNK_SIZE)
-        .map(Ok)
-        .and_then(|chunk| async move {
-            let result = sqlx::QueryBuilder::new(
-                r#"INSERT INTO brns (
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in brns.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = sqlx::QueryBuilder::new(
+            r#"INSERT INTO brns (
                     user_id,
                     number
                 ) "#,
-            )
+        );
+        query
             .push_values(chunk, |mut b, brn| {
                 b.push_bind(&brn.user_id).push_bind(&brn.number);
             })
             .push(
                 r#"ON CONFLICT(number) DO UPDATE SET
-                user_id = excluded.user_id
+                user_id = excluded.user_id,
+                deleted_at = NULL
+            WHERE brns.user_id IS DISTINCT FROM excluded.user_id
+                OR brns.deleted_at IS NOT NULL
             "#,
-            )
-            .build()
-            .execute(pool)
-            .await?;
-            Ok::<u64, Error>(result.rows_affected())
-        })
-        .try_collect()
-        .await?;
-    Ok(affected.iter().sum())
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
 }
 
-pub async fn retain(pool: &PgPool, users: &[Brn]) -> Result<u64> {
-    retain_with_keys(pool, "brns", "number", users, |brn| brn.number.as_str()).await
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, users: &[Brn]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(conn, guard, "brns", "number", users, |brn| {
+        brn.number.as_str()
+    })
+    .await
 }
@@ -1,7 +1,10 @@
-use crate::{DB_INSERT_CHUNK_SIZE, Error, Result, leadership, retain_with_keys, user};
-use futures::{StreamExt, TryFutureExt, TryStreamExt, stream};
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Error, Result, UpsertStats, execute_upsert, guard::DeleteGuard,
+    leadership, retain_with_keys, user,
+};
+use futures::TryFutureExt;
 use itertools::Itertools;
-use sqlx::{PgPool, Postgres, QueryBuilder};
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
 
 pub async fn all(pool: &PgPool) -> Result<Vec<Club>> {
     sqlx::query_as::<_, Club>(FETCH_CLUBS_QUERY)
@@ -12,7 +15,7 @@ pub async fn all(pool: &PgPool) -> Result<Vec<Club>> {
 
 pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<Club>> {
     let club = fetch_clubs_query()
-        .push("where uid = ")
+        .push(" and uid = ")
         .push_bind(uid)
         .build_query_as::<Club>()
         .fetch_optional(pool)
@@ -23,7 +26,7 @@ pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<Club>> {
 
 pub async fn by_number(pool: &PgPool, number: i32) -> Result<Option<Club>> {
     let club = fetch_clubs_query()
-        .push("where number = ")
+        .push(" and number = ")
         .push_bind(number)
         .build_query_as::<Club>()
         .fetch_optional(pool)
@@ -40,6 +43,8 @@ const FETCH_CLUBS_QUERY: &str = r#"
         region
     FROM
         clubs
+    WHERE
+        deleted_at IS NULL
 "#;
 
 fn fetch_clubs_query<'builder>() -> sqlx::QueryBuilder<'builder, Postgres> {
@@ -57,11 +62,15 @@ pub struct Club {
     pub region: Option<i64>,
 }
 
-pub async fn upsert_many(pool: &PgPool, clubs: &[Club]) -> Result<u64> {
+pub async fn upsert_many<'c, A>(conn: A, clubs: &[Club]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if clubs.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
-    let result = sqlx::QueryBuilder::new("INSERT INTO clubs(uid, number, name, region) ")
+    let mut query = sqlx::QueryBuilder::new("INSERT INTO clubs(uid, number, name, region) ");
+    query
         .push_values(clubs, |mut b, club| {
             b.push_bind(club.uid)
                 .push_bind(club.number)
@@ -72,17 +81,21 @@ pub async fn upsert_many(pool: &PgPool, clubs: &[Club]) -> Result<u64> {
             r#"ON CONFLICT(uid) DO UPDATE SET
                 name = excluded.name,
                 number = excluded.number,
-                region = excluded.region
+                region = excluded.region,
+                deleted_at = NULL
+            WHERE (clubs.name, clubs.number, clubs.region)
+                IS DISTINCT FROM (excluded.name, excluded.number, excluded.region)
+                OR clubs.deleted_at IS NOT NULL
             "#,
-        )
-        .build()
-        .execute(pool)
-        .await?;
-    Ok(result.rows_affected())
+        );
+    execute_upsert(&mut *conn.acquire().await?, query, clubs.len()).await
 }
 
-pub async fn retain(pool: &PgPool, clubs: &[Club]) -> Result<u64> {
-    retain_with_keys(pool, "clubs", "uid", clubs, |club| club.uid).await
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, clubs: &[Club]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(conn, guard, "clubs", "uid", clubs, |club| club.uid).await
 }
 
 // ========== Club Leadership ==========
@@ -161,6 +174,8 @@ const FETCH_LEADERSHIP_QUERY: &str = r#"
         JOIN clubs c ON c.uid = lc.club
         JOIN users u ON u.id = lc.user_id
         JOIN leadership_role r ON r.uid = lc.role
+    WHERE
+        lc.deleted_at IS NULL
 "#;
 
 fn fetch_leadership_query<'builder>() -> QueryBuilder<'builder, Postgres> {
@@ -172,7 +187,7 @@ pub async fn all_leadership(
     filter: leadership::DateFilter,
 ) -> Result<Vec<Leadership>> {
     let mut query = fetch_leadership_query();
-    leadership::apply_date_filter(&mut query, &filter, false);
+    leadership::apply_date_filter(&mut query, &filter, true);
     query
         .build_query_as::<Leadership>()
         .fetch_all(pool)
@@ -186,7 +201,7 @@ pub async fn leadership_by_uid(
     filter: leadership::DateFilter,
 ) -> Result<Vec<Leadership>> {
     let mut query = fetch_leadership_query();
-    query.push("WHERE lc.club = ").push_bind(club_uid);
+    query.push(" AND lc.club = ").push_bind(club_uid);
     leadership::apply_date_filter(&mut query, &filter, true);
     query
         .build_query_as::<Leadership>()
@@ -201,9 +216,7 @@ pub async fn leadership_by_number(
     filter: leadership::DateFilter,
 ) -> Result<Vec<Leadership>> {
     let mut query = fetch_leadership_query();
-    query
-        .push("WHERE c.number = ")
-        .push_bind(club_number as i64);
+    query.push(" AND c.number = ").push_bind(club_number as i64);
     leadership::apply_date_filter(&mut query, &filter, true);
     query
         .build_query_as::<Leadership>()
@@ -212,20 +225,22 @@ pub async fn leadership_by_number(
         .map_err(Error::from)
 }
 
-pub async fn upsert_leadership(pool: &PgPool, leadership: &[Leadership]) -> Result<u64> {
+pub async fn upsert_leadership<'c, A>(conn: A, leadership: &[Leadership]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if leadership.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
 
-    let affected: Vec<u64> = stream::iter(
-        leadership
-            .iter()
-            .unique_by(|l| (l.club.uid, &l.user.id, l.role.uid, l.start_date)),
-    )
-    .chunks(DB_INSERT_CHUNK_SIZE)
-    .map(Ok::<_, Error>)
-    .and_then(|chunk| async move {
-        let result = QueryBuilder::new(
+    let unique = leadership
+        .iter()
+        .unique_by(|l| (l.club.uid, &l.user.id, l.role.uid, l.start_date))
+        .collect_vec();
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in unique.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
             r#"INSERT INTO leadership_club(
                     club,
                     user_id,
@@ -233,35 +248,41 @@ pub async fn upsert_leadership(pool: &PgPool, leadership: &[Leadership]) -> Resu
                     start_date,
                     end_date
                 ) "#,
-        )
-        .push_values(&chunk, |mut b, lead| {
-            b.push_bind(lead.club.uid)
-                .push_bind(&lead.user.id)
-                .push_bind(lead.role.uid)
-                .push_bind(lead.start_date)
-                .push_bind(lead.end_date);
-        })
-        .push(
-            r#"ON CONFLICT(club, user_id, role, start_date) DO UPDATE SET
-                    end_date = excluded.end_date
+        );
+        query
+            .push_values(chunk, |mut b, lead| {
+                b.push_bind(lead.club.uid)
+                    .push_bind(&lead.user.id)
+                    .push_bind(lead.role.uid)
+                    .push_bind(lead.start_date)
+                    .push_bind(lead.end_date);
+            })
+            .push(
+                r#"ON CONFLICT(club, user_id, role, start_date) DO UPDATE SET
+                    end_date = excluded.end_date,
+                    deleted_at = NULL
+                WHERE leadership_club.end_date IS DISTINCT FROM excluded.end_date
+                    OR leadership_club.deleted_at IS NOT NULL
                 "#,
-        )
-        .build()
-        .execute(pool)
-        .await?;
-        Ok::<u64, Error>(result.rows_affected())
-    })
-    .try_collect()
-    .await?;
-    Ok(affected.iter().sum())
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
 }
 
-pub async fn retain_leadership(pool: &PgPool, leadership: &[Leadership]) -> Result<u64> {
+pub async fn retain_leadership<'c, A>(
+    conn: A,
+    guard: &DeleteGuard,
+    leadership: &[Leadership],
+) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if leadership.is_empty() {
         return Ok(0);
     }
 
-    let mut tx = pool.begin().await?;
+    let mut tx = conn.begin().await?;
 
     // Create temp table to hold keys to keep
     sqlx::query(
@@ -290,20 +311,14 @@ pub async fn retain_leadership(pool: &PgPool, leadership: &[Leadership]) -> Resu
     }
 
     // Delete rows not in temp table
-    let result = sqlx::query(
-        r#"DELETE FROM leadership_club lc
-           WHERE NOT EXISTS (
+    let condition = r#"NOT EXISTS (
                SELECT 1 FROM _keep_leadership_club k
-               WHERE k.club = lc.club
-                 AND k.user_id = lc.user_id
-                 AND k.role = lc.role
-                 AND k.start_date = lc.start_date
-           )"#,
-    )
-    .execute(&mut *tx)
-    .await?;
-
-    let total_affected = result.rows_affected();
+               WHERE k.club = leadership_club.club
+                 AND k.user_id = leadership_club.user_id
+                 AND k.role = leadership_club.role
+                 AND k.start_date = leadership_club.start_date
+           )"#;
+    let total_affected = guard.delete(&mut tx, "leadership_club", condition).await?;
     tx.commit().await?;
     Ok(total_affected)
 }
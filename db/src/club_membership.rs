@@ -0,0 +1,148 @@
+//! The current memberships of members in all their clubs: the home club,
+//! intraclubs and affiliate clubs. A member has a single row in `members`,
+//! for the home club, and a row here for every club.
+
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Error, Result, UpsertStats, execute_upsert,
+    guard::DeleteGuard,
+    member::{MemberClass, MemberType},
+    retain_with_keys,
+};
+use chrono::NaiveDate;
+use futures::TryFutureExt;
+use itertools::Itertools;
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
+
+#[derive(Debug, sqlx::FromRow, serde::Serialize)]
+pub struct ClubMembership {
+    pub user_id: String,
+    /// The club uid
+    pub club: i64,
+    pub member_type: MemberType,
+    pub member_class: MemberClass,
+    pub join_date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration_date: Option<NaiveDate>,
+}
+
+/// All club memberships of a user, home club first
+pub async fn by_user_id(pool: &PgPool, user_id: &str) -> Result<Vec<ClubMembership>> {
+    fetch_memberships_query()
+        .push(" AND user_id = ")
+        .push_bind(user_id)
+        .push(" ORDER BY member_type, join_date")
+        .build_query_as::<ClubMembership>()
+        .fetch_all(pool)
+        .map_err(Error::from)
+        .await
+}
+
+/// All memberships in a club, of any type
+pub async fn by_club(pool: &PgPool, uid: i64) -> Result<Vec<ClubMembership>> {
+    fetch_memberships_query()
+        .push(" AND club = ")
+        .push_bind(uid)
+        .push(" ORDER BY member_type, user_id")
+        .build_query_as::<ClubMembership>()
+        .fetch_all(pool)
+        .map_err(Error::from)
+        .await
+}
+
+/// Upsert the memberships, keeping the first of several of a user in the
+/// same club with the same type
+pub async fn upsert_many<'c, A>(conn: A, memberships: &[ClubMembership]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    if memberships.is_empty() {
+        return Ok(UpsertStats::default());
+    }
+    let unique = memberships
+        .iter()
+        .unique_by(|membership| (&membership.user_id, membership.club, membership.member_type))
+        .collect_vec();
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in unique.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            r#"INSERT INTO club_memberships (
+                    user_id,
+                    club,
+                    member_type,
+                    member_class,
+                    join_date,
+                    expiration_date
+                ) "#,
+        );
+        query
+            .push_values(chunk, |mut b, membership| {
+                b.push_bind(&membership.user_id)
+                    .push_bind(membership.club)
+                    .push_bind(membership.member_type)
+                    .push_bind(&membership.member_class)
+                    .push_bind(membership.join_date)
+                    .push_bind(membership.expiration_date);
+            })
+            .push(
+                r#"ON CONFLICT(user_id, club, member_type) DO UPDATE SET
+                member_class = excluded.member_class,
+                join_date = excluded.join_date,
+                expiration_date = excluded.expiration_date,
+                deleted_at = NULL
+            WHERE (
+                    club_memberships.member_class,
+                    club_memberships.join_date,
+                    club_memberships.expiration_date
+                ) IS DISTINCT FROM (
+                    excluded.member_class,
+                    excluded.join_date,
+                    excluded.expiration_date
+                )
+                OR club_memberships.deleted_at IS NOT NULL
+            "#,
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
+}
+
+pub async fn retain<'c, A>(
+    conn: A,
+    guard: &DeleteGuard,
+    memberships: &[ClubMembership],
+) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(
+        conn,
+        guard,
+        "club_memberships",
+        "concat_ws(':', user_id, club, member_type)",
+        memberships,
+        |membership| {
+            format!(
+                "{}:{}:{}",
+                membership.user_id, membership.club, membership.member_type
+            )
+        },
+    )
+    .await
+}
+
+const FETCH_MEMBERSHIPS_QUERY: &str = r#"
+    SELECT
+        user_id,
+        club,
+        member_type,
+        member_class,
+        join_date,
+        expiration_date
+    FROM club_memberships
+    WHERE deleted_at IS NULL
+"#;
+
+fn fetch_memberships_query<'builder>() -> QueryBuilder<'builder, Postgres> {
+    QueryBuilder::new(FETCH_MEMBERSHIPS_QUERY)
+}
@@ -0,0 +1,28 @@
+use super::{Result, connect_from_env, print_json};
+use db::{audit, user};
+
+/// Print the timeline of changes to a member, their user and leadership
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Email or uid of the member
+    pub user: String,
+}
+
+impl Cmd {
+    pub async fn run(&self) -> Result {
+        let db = connect_from_env().await?;
+        // Changes outlive the user, so removed users are looked up too
+        let user_ids = match self.user.parse::<i64>() {
+            Ok(uid) => {
+                let ids = user::ids_for_uid(&db, uid).await?;
+                if ids.is_empty() {
+                    anyhow::bail!("no user with uid {uid}");
+                }
+                ids
+            }
+            Err(_) => vec![user::id_for_email(&self.user)],
+        };
+        let timeline = audit::timeline(&db, &user_ids).await?;
+        print_json(&timeline)
+    }
+}
@@ -11,6 +11,7 @@ pub async fn connect_from_env() -> Result<PgPool> {
 }
 
 pub mod addresses;
+pub mod audit;
 pub mod brns;
 pub mod clubs;
 pub mod international;
@@ -46,6 +47,7 @@ pub enum DbCommand {
     International(international::Cmd),
     Addresses(addresses::Cmd),
     Brns(brns::Cmd),
+    Audit(audit::Cmd),
 }
 
 impl DbCommand {
@@ -59,6 +61,7 @@ impl DbCommand {
             Self::International(cmd) => cmd.run().await,
             Self::Addresses(cmd) => cmd.run().await,
             Self::Brns(cmd) => cmd.run().await,
+            Self::Audit(cmd) => cmd.run().await,
         }
     }
 }
@@ -0,0 +1,103 @@
+//! Users administering the microsite of a club or region, by their Drupal
+//! uid and the uid of the club or region.
+
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Error, Result, UpsertStats, execute_upsert, guard::DeleteGuard,
+    retain_with_keys,
+};
+use futures::TryFutureExt;
+use itertools::Itertools;
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct EntityAdmin {
+    pub user_uid: i64,
+    /// The club or region uid
+    pub entity_uid: i64,
+    pub is_region: bool,
+}
+
+const FETCH_ADMINS_QUERY: &str = r#"
+    SELECT
+        user_uid,
+        entity_uid,
+        is_region
+    FROM entity_admins
+    WHERE deleted_at IS NULL
+"#;
+
+fn fetch_admins_query<'builder>() -> QueryBuilder<'builder, Postgres> {
+    QueryBuilder::new(FETCH_ADMINS_QUERY)
+}
+
+/// The admins of a club or region
+pub async fn by_entity_uid(pool: &PgPool, uid: i64) -> Result<Vec<EntityAdmin>> {
+    fetch_admins_query()
+        .push(" AND entity_uid = ")
+        .push_bind(uid)
+        .build_query_as::<EntityAdmin>()
+        .fetch_all(pool)
+        .map_err(Error::from)
+        .await
+}
+
+/// The clubs and regions a user administers
+pub async fn by_user_uid(pool: &PgPool, uid: i64) -> Result<Vec<EntityAdmin>> {
+    fetch_admins_query()
+        .push(" AND user_uid = ")
+        .push_bind(uid)
+        .build_query_as::<EntityAdmin>()
+        .fetch_all(pool)
+        .map_err(Error::from)
+        .await
+}
+
+pub async fn upsert_many<'c, A>(conn: A, admins: &[EntityAdmin]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    if admins.is_empty() {
+        return Ok(UpsertStats::default());
+    }
+    let unique = admins
+        .iter()
+        .unique_by(|admin| (admin.user_uid, admin.entity_uid))
+        .collect_vec();
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in unique.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query =
+            QueryBuilder::new("INSERT INTO entity_admins(user_uid, entity_uid, is_region) ");
+        query
+            .push_values(chunk, |mut b, admin| {
+                b.push_bind(admin.user_uid)
+                    .push_bind(admin.entity_uid)
+                    .push_bind(admin.is_region);
+            })
+            .push(
+                r#"ON CONFLICT(user_uid, entity_uid) DO UPDATE SET
+                    is_region = excluded.is_region,
+                    deleted_at = NULL
+                WHERE entity_admins.is_region IS DISTINCT FROM excluded.is_region
+                    OR entity_admins.deleted_at IS NOT NULL
+                "#,
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
+}
+
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, admins: &[EntityAdmin]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(
+        conn,
+        guard,
+        "entity_admins",
+        "concat_ws(':', user_uid, entity_uid)",
+        admins,
+        |admin| format!("{}:{}", admin.user_uid, admin.entity_uid),
+    )
+    .await
+}
@@ -0,0 +1,304 @@
+//! Safety limits for the delete phase of a sync.
+//!
+//! A retain deletes every row that is missing from the freshly fetched set,
+//! so a partial or broken extraction would wipe a table. Before deleting,
+//! the rows about to go are counted and the delete is aborted with a
+//! [`MassDeletion`] error when that is more than the table's limit allows.
+//!
+//! With [`DeleteMode::Soft`] rows are only marked with `deleted_at`, and
+//! come back when the next upsert brings them back. They are hard deleted by
+//! [`crate::purge_deleted`] once they have been gone for long enough.
+//!
+//! A sync of one club or region only fetched that part of the portal, so its
+//! retains are limited to the [`Scope`] of the guard and never touch rows
+//! outside of it.
+
+use crate::{Error, Result};
+use serde::Serialize;
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// How many rows a single retain may delete, absolute and as percentage of
+/// the rows in the table. The delete is aborted when either is exceeded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct DeleteLimit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+}
+
+impl DeleteLimit {
+    pub fn exceeded(&self, deleting: u64, total: u64) -> bool {
+        let over_rows = self.rows.is_some_and(|rows| deleting > rows);
+        let over_percent = self
+            .percent
+            .is_some_and(|percent| total > 0 && deleting as f64 * 100.0 / total as f64 > percent);
+        over_rows || over_percent
+    }
+}
+
+/// Limits are written as `500`, `10%` or both, like `500:10%`
+impl FromStr for DeleteLimit {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let mut limit = Self::default();
+        for part in s.split(':').map(str::trim).filter(|part| !part.is_empty()) {
+            match part.strip_suffix('%') {
+                Some(percent) => {
+                    limit.percent = Some(
+                        percent
+                            .trim()
+                            .parse()
+                            .map_err(|_| anyhow::anyhow!("invalid delete limit {s}"))?,
+                    )
+                }
+                None => {
+                    limit.rows = Some(
+                        part.parse()
+                            .map_err(|_| anyhow::anyhow!("invalid delete limit {s}"))?,
+                    )
+                }
+            }
+        }
+        Ok(limit)
+    }
+}
+
+impl fmt::Display for DeleteLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.rows, self.percent) {
+            (Some(rows), Some(percent)) => write!(f, "{rows}:{percent}%"),
+            (Some(rows), None) => write!(f, "{rows}"),
+            (None, Some(percent)) => write!(f, "{percent}%"),
+            (None, None) => f.write_str("none"),
+        }
+    }
+}
+
+/// How a retain removes rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteMode {
+    /// Delete rows
+    #[default]
+    Hard,
+    /// Set `deleted_at` and leave the rows in place
+    Soft,
+}
+
+impl FromStr for DeleteMode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "hard" => Ok(Self::Hard),
+            "soft" => Ok(Self::Soft),
+            _ => Err(anyhow::anyhow!(
+                "invalid delete mode {s}, expected hard or soft"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DeleteMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hard => f.write_str("hard"),
+            Self::Soft => f.write_str("soft"),
+        }
+    }
+}
+
+/// The part of the portal a sync covers, by club or region uid
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    #[default]
+    All,
+    Club(i64),
+    Region(i64),
+}
+
+impl Scope {
+    /// The condition selecting the rows of `table` within the scope, `None`
+    /// when the table is not limited to a club or region and must not be
+    /// retained by a scoped sync
+    pub fn condition(&self, table: &str) -> Option<String> {
+        let clubs = match self {
+            Self::All => return Some("true".to_string()),
+            Self::Club(uid) => format!("SELECT number FROM clubs WHERE uid = {uid}"),
+            Self::Region(uid) => format!("SELECT number FROM clubs WHERE region = {uid}"),
+        };
+        let members = format!("SELECT primary_user FROM members WHERE local_club IN ({clubs})");
+        match (self, table) {
+            (Self::Club(uid), "clubs") => Some(format!("uid = {uid}")),
+            (Self::Region(uid), "clubs") => Some(format!("region = {uid}")),
+            (Self::Region(uid), "regions") => Some(format!("uid = {uid}")),
+            (_, "members") => Some(format!("local_club IN ({clubs})")),
+            (_, "addresses" | "brns") => Some(format!("user_id IN ({members})")),
+            (Self::Club(uid), "leadership_club") => Some(format!("club = {uid}")),
+            (Self::Region(uid), "leadership_club") => Some(format!(
+                "club IN (SELECT uid FROM clubs WHERE region = {uid})"
+            )),
+            (Self::Region(uid), "leadership_region") => Some(format!("region = {uid}")),
+            (Self::Club(uid), "club_memberships") => Some(format!("club = {uid}")),
+            (Self::Region(uid), "club_memberships") => Some(format!(
+                "club IN (SELECT uid FROM clubs WHERE region = {uid})"
+            )),
+            _ => None,
+        }
+    }
+
+    /// Whether a club, by its uid and the uid of its region, is within the
+    /// scope
+    pub fn includes(&self, club: i64, region: Option<i64>) -> bool {
+        match self {
+            Self::All => true,
+            Self::Club(uid) => club == *uid,
+            Self::Region(uid) => region == Some(*uid),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => f.write_str("all"),
+            Self::Club(uid) => write!(f, "club {uid}"),
+            Self::Region(uid) => write!(f, "region {uid}"),
+        }
+    }
+}
+
+/// Delete limits for all tables, with overrides per table. The default
+/// guard has no limits.
+#[derive(Debug, Clone, Default)]
+pub struct DeleteGuard {
+    pub limit: DeleteLimit,
+    pub tables: HashMap<String, DeleteLimit>,
+    /// Skip all checks, for a deliberate mass delete
+    pub force: bool,
+    pub mode: DeleteMode,
+    /// Only rows within the scope are deleted
+    pub scope: Scope,
+}
+
+impl DeleteGuard {
+    /// Parse a limit for all tables and the overrides, like `20%` and
+    /// `members=5%,leadership_club=100`
+    pub fn parse(limit: &str, tables: &str) -> Result<Self> {
+        let tables = tables
+            .split(',')
+            .map(str::trim)
+            .filter(|table| !table.is_empty())
+            .map(|table| {
+                let (name, limit) = table
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("expected TABLE=LIMIT, got {table}"))?;
+                Ok((name.trim().to_string(), limit.parse()?))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            limit: limit.parse()?,
+            tables,
+            force: false,
+            mode: DeleteMode::default(),
+            scope: Scope::default(),
+        })
+    }
+
+    /// The limit of a table
+    pub fn limit(&self, table: &str) -> DeleteLimit {
+        self.tables.get(table).copied().unwrap_or(self.limit)
+    }
+
+    /// Fail with a [`MassDeletion`] error if deleting `deleting` of the
+    /// `total` rows of the table is over its limit
+    pub fn check(&self, table: &str, deleting: u64, total: u64) -> Result<()> {
+        let limit = self.limit(table);
+        if self.force || !limit.exceeded(deleting, total) {
+            return Ok(());
+        }
+        Err(MassDeletion {
+            table: table.to_string(),
+            deleting,
+            total,
+            limit,
+        }
+        .into())
+    }
+
+    /// Count the rows of `table` matching the delete `condition` and check
+    /// them against the table's limit, within the deleting transaction.
+    /// Soft deleted rows are not counted in soft mode, and the total only
+    /// counts rows in scope.
+    pub(crate) async fn check_delete(
+        &self,
+        conn: &mut sqlx::PgConnection,
+        table: &str,
+        condition: &str,
+    ) -> Result<()> {
+        if self.force {
+            return Ok(());
+        }
+        let live = match self.mode {
+            DeleteMode::Hard => "true",
+            DeleteMode::Soft => "deleted_at IS NULL",
+        };
+        let scope = self
+            .scope
+            .condition(table)
+            .unwrap_or_else(|| "false".into());
+        let (deleting, total): (i64, i64) = sqlx::query_as(&format!(
+            "SELECT count(*) FILTER (WHERE {condition}), count(*) FROM {table} \
+            WHERE {live} AND ({scope})"
+        ))
+        .fetch_one(conn)
+        .await?;
+        self.check(table, deleting as u64, total as u64)
+    }
+
+    /// Check and remove the rows of `table` matching `condition` within the
+    /// scope, deleting or marking them deleted depending on the mode
+    pub(crate) async fn delete(
+        &self,
+        conn: &mut sqlx::PgConnection,
+        table: &str,
+        condition: &str,
+    ) -> Result<u64> {
+        let Some(scope) = self.scope.condition(table) else {
+            tracing::debug!(table, scope = %self.scope, "table out of scope, not deleting");
+            return Ok(0);
+        };
+        let condition = &format!("({scope}) AND ({condition})");
+        self.check_delete(conn, table, condition).await?;
+        let query = match self.mode {
+            DeleteMode::Hard => format!("DELETE FROM {table} WHERE {condition}"),
+            DeleteMode::Soft => format!(
+                "UPDATE {table} SET deleted_at = now() WHERE deleted_at IS NULL AND ({condition})"
+            ),
+        };
+        let result = sqlx::query(&query).execute(conn).await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// A delete aborted by the [`DeleteGuard`]
+#[derive(Debug, Clone, Serialize)]
+pub struct MassDeletion {
+    pub table: String,
+    pub deleting: u64,
+    pub total: u64,
+    pub limit: DeleteLimit,
+}
+
+impl fmt::Display for MassDeletion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "refusing to delete {} of {} rows from {}, the limit is {}",
+            self.deleting, self.total, self.table, self.limit
+        )
+    }
+}
+
+impl std::error::Error for MassDeletion {}
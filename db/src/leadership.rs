@@ -1,9 +1,10 @@
-use crate::{DB_INSERT_CHUNK_SIZE, Error, Result, retain_with_keys, user};
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Error, Result, UpsertStats, execute_upsert, guard::DeleteGuard,
+    retain_with_keys, user,
+};
 use chrono::NaiveDate;
-use futures::TryStreamExt;
-use futures::{StreamExt, stream};
 use itertools::Itertools;
-use sqlx::{PgPool, Postgres, QueryBuilder};
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
 
 /// Filter for leadership queries by date
 #[derive(Debug, Clone, Default)]
@@ -85,6 +86,8 @@ const FETCH_ROLES_QUERY: &str = r#"
         title
     FROM
         leadership_role
+    WHERE
+        deleted_at IS NULL
 "#;
 
 pub async fn all_roles(pool: &PgPool) -> Result<Vec<Role>> {
@@ -114,11 +117,13 @@ const FETCH_LEADERSHIP_QUERY: &str = r#"
         leadership_international li
         JOIN users u ON u.id = li.user_id
         JOIN leadership_role r ON r.uid = li.role
+    WHERE
+        li.deleted_at IS NULL
 "#;
 
 pub async fn all(pool: &PgPool, filter: DateFilter) -> Result<Vec<Leadership>> {
     let mut query = QueryBuilder::new(FETCH_LEADERSHIP_QUERY);
-    apply_date_filter(&mut query, &filter, false);
+    apply_date_filter(&mut query, &filter, true);
     query
         .build_query_as::<Leadership>()
         .fetch_all(pool)
@@ -128,79 +133,97 @@ pub async fn all(pool: &PgPool, filter: DateFilter) -> Result<Vec<Leadership>> {
 
 // ========== Role Upsert/Retain Functions ==========
 
-pub async fn upsert_roles(pool: &PgPool, roles: &[Role]) -> Result<u64> {
+pub async fn upsert_roles<'c, A>(conn: A, roles: &[Role]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if roles.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
-    let result = QueryBuilder::new("INSERT INTO leadership_role(uid, title) ")
+    let mut query = QueryBuilder::new("INSERT INTO leadership_role(uid, title) ");
+    query
         .push_values(roles, |mut b, role| {
             b.push_bind(role.uid).push_bind(&role.title);
         })
         .push(
             r#"ON CONFLICT(uid) DO UPDATE SET
-                title = excluded.title
+                title = excluded.title,
+                deleted_at = NULL
+            WHERE leadership_role.title IS DISTINCT FROM excluded.title
+                OR leadership_role.deleted_at IS NOT NULL
             "#,
-        )
-        .build()
-        .execute(pool)
-        .await?;
-    Ok(result.rows_affected())
+        );
+    execute_upsert(&mut *conn.acquire().await?, query, roles.len()).await
 }
 
-pub async fn retain_roles(pool: &PgPool, roles: &[Role]) -> Result<u64> {
-    retain_with_keys(pool, "leadership_role", "uid", roles, |role| role.uid).await
+pub async fn retain_roles<'c, A>(conn: A, guard: &DeleteGuard, roles: &[Role]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(conn, guard, "leadership_role", "uid", roles, |role| {
+        role.uid
+    })
+    .await
 }
 
 // ========== International Leadership Upsert/Retain Functions ==========
 
-pub async fn upsert_leadership(pool: &PgPool, leadership: &[Leadership]) -> Result<u64> {
+pub async fn upsert_leadership<'c, A>(conn: A, leadership: &[Leadership]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if leadership.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
 
-    let affected: Vec<u64> = stream::iter(
-        leadership
-            .iter()
-            .unique_by(|l| (&l.user.id, l.role.uid, l.start_date)),
-    )
-    .chunks(DB_INSERT_CHUNK_SIZE)
-    .map(Ok::<_, Error>)
-    .and_then(|chunk| async move {
-        let result = QueryBuilder::new(
+    let unique = leadership
+        .iter()
+        .unique_by(|l| (&l.user.id, l.role.uid, l.start_date))
+        .collect_vec();
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in unique.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
             r#"INSERT INTO leadership_international(
                     user_id,
                     role,
                     start_date,
                     end_date
                 ) "#,
-        )
-        .push_values(&chunk, |mut b, lead| {
-            b.push_bind(&lead.user.id)
-                .push_bind(lead.role.uid)
-                .push_bind(lead.start_date)
-                .push_bind(lead.end_date);
-        })
-        .push(
-            r#"ON CONFLICT(user_id, role, start_date) DO UPDATE SET
-                    end_date = excluded.end_date
+        );
+        query
+            .push_values(chunk, |mut b, lead| {
+                b.push_bind(&lead.user.id)
+                    .push_bind(lead.role.uid)
+                    .push_bind(lead.start_date)
+                    .push_bind(lead.end_date);
+            })
+            .push(
+                r#"ON CONFLICT(user_id, role, start_date) DO UPDATE SET
+                    end_date = excluded.end_date,
+                    deleted_at = NULL
+                WHERE leadership_international.end_date IS DISTINCT FROM excluded.end_date
+                    OR leadership_international.deleted_at IS NOT NULL
                 "#,
-        )
-        .build()
-        .execute(pool)
-        .await?;
-        Ok::<u64, Error>(result.rows_affected())
-    })
-    .try_collect()
-    .await?;
-    Ok(affected.iter().sum())
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
 }
 
-pub async fn retain_leadership(pool: &PgPool, leadership: &[Leadership]) -> Result<u64> {
+pub async fn retain_leadership<'c, A>(
+    conn: A,
+    guard: &DeleteGuard,
+    leadership: &[Leadership],
+) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if leadership.is_empty() {
         return Ok(0);
     }
 
-    let mut tx = pool.begin().await?;
+    let mut tx = conn.begin().await?;
 
     // Create temp table to hold keys to keep
     sqlx::query(
@@ -227,19 +250,15 @@ pub async fn retain_leadership(pool: &PgPool, leadership: &[Leadership]) -> Resu
     }
 
     // Delete rows not in temp table
-    let result = sqlx::query(
-        r#"DELETE FROM leadership_international li
-           WHERE NOT EXISTS (
+    let condition = r#"NOT EXISTS (
                SELECT 1 FROM _keep_leadership_international k
-               WHERE k.user_id = li.user_id
-                 AND k.role = li.role
-                 AND k.start_date = li.start_date
-           )"#,
-    )
-    .execute(&mut *tx)
-    .await?;
-
-    let total_affected = result.rows_affected();
+               WHERE k.user_id = leadership_international.user_id
+                 AND k.role = leadership_international.role
+                 AND k.start_date = leadership_international.start_date
+           )"#;
+    let total_affected = guard
+        .delete(&mut tx, "leadership_international", condition)
+        .await?;
     tx.commit().await?;
     Ok(total_affected)
 }
@@ -3,24 +3,161 @@ pub type Error = anyhow::Error;
 pub use anyhow::Context;
 
 pub mod address;
+pub mod airstream;
+pub mod audit;
 pub mod brn;
 pub mod club;
+pub mod club_membership;
+pub mod entity_admin;
+pub mod guard;
 pub mod leadership;
 pub mod member;
+pub mod membership_history;
 pub mod region;
 pub mod standing_committee;
 pub mod user;
+pub mod user_role;
 
 pub(crate) const DB_INSERT_CHUNK_SIZE: usize = 1000;
 
-pub(crate) async fn retain_with_keys<'a, T, F, K>(
-    pool: &sqlx::PgPool,
+/// What an upsert did with the rows it was given. Rows whose content is
+/// already current are skipped by the `IS DISTINCT FROM` guard of the
+/// `ON CONFLICT` clause and not written at all.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct UpsertStats {
+    pub inserted: u64,
+    pub updated: u64,
+    pub unchanged: u64,
+}
+
+impl UpsertStats {
+    /// Count the rows returned by an upsert of `rows` rows, by whether they
+    /// were inserted. Rows that were not returned were unchanged.
+    pub fn from_returned(rows: usize, returned: &[bool]) -> Self {
+        let inserted = returned.iter().filter(|inserted| **inserted).count() as u64;
+        let updated = returned.len() as u64 - inserted;
+        Self {
+            inserted,
+            updated,
+            unchanged: (rows as u64).saturating_sub(inserted + updated),
+        }
+    }
+
+    /// Rows that were written, inserted or updated
+    pub fn written(&self) -> u64 {
+        self.inserted + self.updated
+    }
+}
+
+impl std::ops::Add for UpsertStats {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            inserted: self.inserted + other.inserted,
+            updated: self.updated + other.updated,
+            unchanged: self.unchanged + other.unchanged,
+        }
+    }
+}
+
+impl std::ops::AddAssign for UpsertStats {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl std::iter::Sum for UpsertStats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), std::ops::Add::add)
+    }
+}
+
+/// Run an `INSERT .. ON CONFLICT DO UPDATE .. WHERE .. IS DISTINCT FROM ..`
+/// of `rows` rows. `xmax` is only zero for freshly inserted row versions, so
+/// it tells inserts from updates, and rows skipped by the guard are not
+/// returned.
+pub(crate) async fn execute_upsert<'e, E>(
+    executor: E,
+    mut query: sqlx::QueryBuilder<'_, sqlx::Postgres>,
+    rows: usize,
+) -> Result<UpsertStats>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let returned: Vec<bool> = query
+        .push(" RETURNING (xmax = 0) AS inserted")
+        .build_query_scalar()
+        .fetch_all(executor)
+        .await?;
+    Ok(UpsertStats::from_returned(rows, &returned))
+}
+
+/// Tables that can be soft deleted, children before their parents
+pub const SOFT_DELETE_TABLES: &[&str] = &[
+    "private.user_addresses",
+    "user_roles",
+    "entity_admins",
+    "airstream_ownership",
+    "airstreams",
+    "membership_history",
+    "club_memberships",
+    "leadership_club",
+    "leadership_region",
+    "leadership_international",
+    "leadership_standing_committee",
+    "brns",
+    "addresses",
+    "members",
+    "users",
+    "leadership_role",
+    "clubs",
+    "standing_committees",
+    "regions",
+];
+
+/// Hard delete rows that were soft deleted more than `days` days ago,
+/// returning how many were purged from each table
+pub async fn purge_deleted(pool: &sqlx::PgPool, days: u32) -> Result<Vec<(&'static str, u64)>> {
+    let mut tx = pool.begin().await?;
+    let mut purged = vec![];
+    for table in SOFT_DELETE_TABLES {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {table} WHERE deleted_at < now() - make_interval(days => $1::int)"
+        ))
+        .bind(i64::from(days))
+        .execute(&mut *tx)
+        .await?;
+        purged.push((*table, result.rows_affected()));
+    }
+    tx.commit().await?;
+    Ok(purged)
+}
+
+/// Count the rows that [`purge_deleted`] would purge from each table
+pub async fn count_deleted(pool: &sqlx::PgPool, days: u32) -> Result<Vec<(&'static str, u64)>> {
+    let mut counts = vec![];
+    for table in SOFT_DELETE_TABLES {
+        let (count,): (i64,) = sqlx::query_as(&format!(
+            "SELECT count(*) FROM {table} WHERE deleted_at < now() - make_interval(days => $1::int)"
+        ))
+        .bind(i64::from(days))
+        .fetch_one(pool)
+        .await?;
+        counts.push((*table, count as u64));
+    }
+    Ok(counts)
+}
+
+pub(crate) async fn retain_with_keys<'a, 'c, A, T, F, K>(
+    conn: A,
+    guard: &guard::DeleteGuard,
     table: &str,
     column: &str,
     items: &'a [T],
     mut key_fn: F,
 ) -> Result<u64>
 where
+    A: sqlx::Acquire<'c, Database = sqlx::Postgres>,
     F: FnMut(&'a T) -> K,
     for<'q> K: sqlx::Encode<'q, sqlx::Postgres> + sqlx::Type<sqlx::Postgres>,
 {
@@ -28,12 +165,12 @@ where
         return Ok(0);
     }
 
-    let mut tx = pool.begin().await?;
+    let mut tx = conn.begin().await?;
 
     // Create temporary table with unique name (timestamp-based)
     let temp_table = format!(
         "temp_retain_{table}_{timestamp}",
-        table = table,
+        table = table.replace('.', "_"),
         timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .map(|d| d.as_nanos())
@@ -61,13 +198,8 @@ where
     }
 
     // Delete rows not in temporary table (cast for comparison)
-    let delete_query = format!(
-        "DELETE FROM {table} WHERE {column}::TEXT NOT IN (SELECT retain_key FROM {temp_table})"
-    );
-
-    let result = sqlx::query(&delete_query).execute(&mut *tx).await?;
-
-    let total_affected = result.rows_affected();
+    let condition = format!("{column}::TEXT NOT IN (SELECT retain_key FROM {temp_table})");
+    let total_affected = guard.delete(&mut tx, table, &condition).await?;
 
     // Commit (temp table automatically drops)
     tx.commit().await?;
@@ -1,9 +1,10 @@
 use crate::{
-    DB_INSERT_CHUNK_SIZE, Error, Result, club, retain_with_keys,
+    DB_INSERT_CHUNK_SIZE, Result, UpsertStats, club, execute_upsert,
+    guard::DeleteGuard,
+    retain_with_keys,
     user::{self, id_for_email},
 };
-use futures::{StreamExt, TryStreamExt, stream};
-use sqlx::{PgPool, Postgres, QueryBuilder};
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
 use std::{collections::HashMap, fmt};
 
 pub async fn all(pool: &PgPool) -> Result<Vec<Member>> {
@@ -16,7 +17,7 @@ pub async fn all(pool: &PgPool) -> Result<Vec<Member>> {
 
 pub async fn by_club(pool: &PgPool, uid: i64) -> Result<Vec<Member>> {
     let members = fetch_members_query()
-        .push("WHERE local_club = ")
+        .push(" AND local_club = ")
         .push_bind(uid)
         .build_query_as::<Member>()
         .fetch_all(pool)
@@ -26,7 +27,7 @@ pub async fn by_club(pool: &PgPool, uid: i64) -> Result<Vec<Member>> {
 
 pub async fn by_region(pool: &PgPool, uid: i64) -> Result<Vec<Member>> {
     let all = fetch_members_query()
-        .push("WHERE region.uid = ")
+        .push(" AND region.uid = ")
         .push_bind(uid)
         .build_query_as::<Member>()
         .fetch_all(pool)
@@ -54,7 +55,7 @@ pub fn dedupe_members(members: Vec<Member>) -> Vec<Member> {
 
 pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<Member>> {
     let member = fetch_members_query()
-        .push("WHERE primary_user = ")
+        .push(" AND primary_user = ")
         .push_bind(uid)
         .build_query_as::<Member>()
         .fetch_optional(pool)
@@ -65,7 +66,7 @@ pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<Member>> {
 
 pub async fn by_email(pool: &PgPool, email: &str) -> Result<Option<Member>> {
     let member = fetch_members_query()
-        .push("WHERE email = ")
+        .push(" AND email = ")
         .push_bind(email)
         .build_query_as::<Member>()
         .fetch_optional(pool)
@@ -99,27 +100,33 @@ const FETCH_MEMBERS_QUERY: &str = r#"
 
     FROM
         members member
-        LEFT JOIN users "user" ON "user".id = member.primary_user
-        LEFT JOIN users partner ON partner.id = member.partner_user
-        LEFT JOIN clubs club ON club.number = member.local_club
-        LEFT JOIN regions region ON region.uid = club.region
+        LEFT JOIN users "user"
+            ON "user".id = member.primary_user AND "user".deleted_at IS NULL
+        LEFT JOIN users partner
+            ON partner.id = member.partner_user AND partner.deleted_at IS NULL
+        LEFT JOIN clubs club ON club.number = member.local_club AND club.deleted_at IS NULL
+        LEFT JOIN regions region ON region.uid = club.region AND region.deleted_at IS NULL
+    WHERE
+        member.deleted_at IS NULL
 "#;
 
 fn fetch_members_query<'builder>() -> sqlx::QueryBuilder<'builder, Postgres> {
     sqlx::QueryBuilder::new(FETCH_MEMBERS_QUERY)
 }
 
-pub async fn upsert_many(pool: &PgPool, members: &[Member]) -> Result<u64> {
+pub async fn upsert_many<'c, A>(conn: A, members: &[Member]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if members.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
 
-    let affected: Vec<u64> = stream::iter(members)
-        .chunks(DB_INSERT_CHUNK_SIZE)
-        .map(Ok)
-        .and_then(|chunk| async move {
-            let result = QueryBuilder::new(
-                r#"INSERT INTO members(
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in members.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            r#"INSERT INTO members(
                     primary_user,
                     partner_user,
                     member_class,
@@ -128,12 +135,13 @@ pub async fn upsert_many(pool: &PgPool, members: &[Member]) -> Result<u64> {
                     join_date,
                     local_club
                 ) "#,
-            )
-            .push_values(&chunk, |mut b, member| {
+        );
+        query
+            .push_values(chunk, |mut b, member| {
                 b.push_bind(&member.primary.id)
                     .push_bind(member.partner.as_ref().map(|user| &user.id))
                     .push_bind(&member.member_class)
-                    .push_bind(&member.member_type)
+                    .push_bind(member.member_type)
                     .push_bind(member.expiration_date)
                     .push_bind(member.join_date)
                     .push_bind(member.local_club.number);
@@ -145,21 +153,36 @@ pub async fn upsert_many(pool: &PgPool, members: &[Member]) -> Result<u64> {
                 member_type = excluded.member_type,
                 expiration_date = excluded.expiration_date,
                 join_date = excluded.join_date,
-                local_club = excluded.local_club
+                local_club = excluded.local_club,
+                deleted_at = NULL
+            WHERE (
+                    members.partner_user,
+                    members.member_class,
+                    members.member_type,
+                    members.expiration_date,
+                    members.join_date,
+                    members.local_club
+                ) IS DISTINCT FROM (
+                    excluded.partner_user,
+                    excluded.member_class,
+                    excluded.member_type,
+                    excluded.expiration_date,
+                    excluded.join_date,
+                    excluded.local_club
+                )
+                OR members.deleted_at IS NOT NULL
             "#,
-            )
-            .build()
-            .execute(pool)
-            .await?;
-            Ok::<u64, Error>(result.rows_affected())
-        })
-        .try_collect()
-        .await?;
-    Ok(affected.iter().sum())
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
 }
 
-pub async fn retain(pool: &PgPool, members: &[Member]) -> Result<u64> {
-    retain_with_keys(pool, "members", "primary_user", members, |member| {
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, members: &[Member]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(conn, guard, "members", "primary_user", members, |member| {
         member.primary.id.as_str()
     })
     .await
@@ -169,7 +192,7 @@ pub mod mailing_address {
 
     pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<Address>> {
         let member = fetch_mailing_address_query()
-            .push("WHERE user = ")
+            .push(" AND user = ")
             .push_bind(uid)
             .build_query_as::<Address>()
             .fetch_optional(pool)
@@ -221,6 +244,7 @@ pub mod mailing_address {
                 state,
                 country
             FROM addresses
+            WHERE deleted_at IS NULL
             "#,
         )
     }
@@ -303,13 +327,15 @@ impl TryFrom<i32> for MemberStatus {
     }
 }
 
-#[derive(Debug, serde::Serialize, Default, PartialEq, Eq, sqlx::Type)]
+#[derive(Debug, Clone, Copy, serde::Serialize, Default, PartialEq, Eq, Hash, sqlx::Type)]
 #[serde(rename_all = "lowercase")]
 #[sqlx(type_name = "member_type", rename_all = "lowercase")]
 pub enum MemberType {
     #[default]
     Regular,
     Affiliate,
+    /// A membership of a club besides the home club
+    Intraclub,
 }
 
 impl fmt::Display for MemberType {
@@ -317,6 +343,7 @@ impl fmt::Display for MemberType {
         match self {
             Self::Regular => f.write_str("regular"),
             Self::Affiliate => f.write_str("affiliate"),
+            Self::Intraclub => f.write_str("intraclub"),
         }
     }
 }
@@ -327,6 +354,7 @@ impl TryFrom<String> for MemberType {
         match value.to_lowercase().as_str() {
             "field_home_club" | "regular" => Ok(Self::Regular),
             "field_memberships" | "affiliate" => Ok(Self::Affiliate),
+            "field_intraclub_memberships" | "intraclub" => Ok(Self::Intraclub),
             other => Err(sqlx::Error::decode(format!(
                 "unexpected member type {other}",
             ))),
@@ -0,0 +1,155 @@
+//! The membership periods of users, one per Drupal membership paragraph,
+//! including lapsed periods and international memberships.
+
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Error, Result, UpsertStats, execute_upsert,
+    guard::DeleteGuard,
+    member::{MemberClass, MemberType},
+    retain_with_keys,
+};
+use chrono::NaiveDate;
+use futures::TryFutureExt;
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
+
+#[derive(Debug, sqlx::FromRow, serde::Serialize)]
+pub struct Period {
+    pub paragraph_id: i64,
+    pub user_uid: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partner_uid: Option<i64>,
+    /// The club uid, none for international memberships
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub club: Option<i64>,
+    pub member_class: MemberClass,
+    /// None for international memberships
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member_type: Option<MemberType>,
+    pub join_date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leave_date: Option<NaiveDate>,
+}
+
+/// All membership periods of a user, oldest first
+pub async fn by_user_uid(pool: &PgPool, uid: i64) -> Result<Vec<Period>> {
+    fetch_periods_query()
+        .push(" AND user_uid = ")
+        .push_bind(uid)
+        .push(" ORDER BY join_date, paragraph_id")
+        .build_query_as::<Period>()
+        .fetch_all(pool)
+        .map_err(Error::from)
+        .await
+}
+
+/// All membership periods in a club, oldest first
+pub async fn by_club(pool: &PgPool, uid: i64) -> Result<Vec<Period>> {
+    fetch_periods_query()
+        .push(" AND club = ")
+        .push_bind(uid)
+        .push(" ORDER BY join_date, paragraph_id")
+        .build_query_as::<Period>()
+        .fetch_all(pool)
+        .map_err(Error::from)
+        .await
+}
+
+pub async fn upsert_many<'c, A>(conn: A, periods: &[Period]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    if periods.is_empty() {
+        return Ok(UpsertStats::default());
+    }
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in periods.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            r#"INSERT INTO membership_history (
+                    paragraph_id,
+                    user_uid,
+                    partner_uid,
+                    club,
+                    member_class,
+                    member_type,
+                    join_date,
+                    leave_date
+                ) "#,
+        );
+        query
+            .push_values(chunk, |mut b, period| {
+                b.push_bind(period.paragraph_id)
+                    .push_bind(period.user_uid)
+                    .push_bind(period.partner_uid)
+                    .push_bind(period.club)
+                    .push_bind(&period.member_class)
+                    .push_bind(period.member_type)
+                    .push_bind(period.join_date)
+                    .push_bind(period.leave_date);
+            })
+            .push(
+                r#"ON CONFLICT(paragraph_id) DO UPDATE SET
+                user_uid = excluded.user_uid,
+                partner_uid = excluded.partner_uid,
+                club = excluded.club,
+                member_class = excluded.member_class,
+                member_type = excluded.member_type,
+                join_date = excluded.join_date,
+                leave_date = excluded.leave_date,
+                deleted_at = NULL
+            WHERE (
+                    membership_history.user_uid,
+                    membership_history.partner_uid,
+                    membership_history.club,
+                    membership_history.member_class,
+                    membership_history.member_type,
+                    membership_history.join_date,
+                    membership_history.leave_date
+                ) IS DISTINCT FROM (
+                    excluded.user_uid,
+                    excluded.partner_uid,
+                    excluded.club,
+                    excluded.member_class,
+                    excluded.member_type,
+                    excluded.join_date,
+                    excluded.leave_date
+                )
+                OR membership_history.deleted_at IS NOT NULL
+            "#,
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
+}
+
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, periods: &[Period]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(
+        conn,
+        guard,
+        "membership_history",
+        "paragraph_id",
+        periods,
+        |period| period.paragraph_id,
+    )
+    .await
+}
+
+const FETCH_PERIODS_QUERY: &str = r#"
+    SELECT
+        paragraph_id,
+        user_uid,
+        partner_uid,
+        club,
+        member_class,
+        member_type,
+        join_date,
+        leave_date
+    FROM membership_history
+    WHERE deleted_at IS NULL
+"#;
+
+fn fetch_periods_query<'builder>() -> QueryBuilder<'builder, Postgres> {
+    QueryBuilder::new(FETCH_PERIODS_QUERY)
+}
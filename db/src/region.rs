@@ -1,7 +1,10 @@
-use crate::{DB_INSERT_CHUNK_SIZE, Error, Result, leadership, retain_with_keys, user};
-use futures::{StreamExt, TryFutureExt, TryStreamExt, stream};
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Error, Result, UpsertStats, execute_upsert, guard::DeleteGuard,
+    leadership, retain_with_keys, user,
+};
+use futures::TryFutureExt;
 use itertools::Itertools;
-use sqlx::{PgPool, Postgres, QueryBuilder};
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
 
 pub async fn all(pool: &PgPool) -> Result<Vec<Region>> {
     sqlx::query_as::<_, Region>(FETCH_REGIONS_QUERY)
@@ -12,7 +15,7 @@ pub async fn all(pool: &PgPool) -> Result<Vec<Region>> {
 
 pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<Region>> {
     let region = fetch_regions_query()
-        .push("where uid = ")
+        .push(" and uid = ")
         .push_bind(uid)
         .build_query_as::<Region>()
         .fetch_optional(pool)
@@ -23,7 +26,7 @@ pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<Region>> {
 
 pub async fn by_number(pool: &PgPool, number: i32) -> Result<Option<Region>> {
     let region = fetch_regions_query()
-        .push("where number = ")
+        .push(" and number = ")
         .push_bind(number)
         .build_query_as::<Region>()
         .fetch_optional(pool)
@@ -32,11 +35,15 @@ pub async fn by_number(pool: &PgPool, number: i32) -> Result<Option<Region>> {
     Ok(region)
 }
 
-pub async fn upsert_many(pool: &PgPool, regions: &[Region]) -> Result<u64> {
+pub async fn upsert_many<'c, A>(conn: A, regions: &[Region]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if regions.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
-    let result = sqlx::QueryBuilder::new("INSERT INTO regions(uid, number, name) ")
+    let mut query = sqlx::QueryBuilder::new("INSERT INTO regions(uid, number, name) ");
+    query
         .push_values(regions, |mut b, region| {
             b.push_bind(region.uid)
                 .push_bind(region.number)
@@ -45,17 +52,20 @@ pub async fn upsert_many(pool: &PgPool, regions: &[Region]) -> Result<u64> {
         .push(
             r#"ON CONFLICT(number) DO UPDATE SET
                 name = excluded.name,
-                uid = excluded.uid
+                uid = excluded.uid,
+                deleted_at = NULL
+            WHERE (regions.name, regions.uid) IS DISTINCT FROM (excluded.name, excluded.uid)
+                OR regions.deleted_at IS NOT NULL
             "#,
-        )
-        .build()
-        .execute(pool)
-        .await?;
-    Ok(result.rows_affected())
+        );
+    execute_upsert(&mut *conn.acquire().await?, query, regions.len()).await
 }
 
-pub async fn retain(pool: &PgPool, regions: &[Region]) -> Result<u64> {
-    retain_with_keys(pool, "regions", "uid", regions, |region| region.uid).await
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, regions: &[Region]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(conn, guard, "regions", "uid", regions, |region| region.uid).await
 }
 
 const FETCH_REGIONS_QUERY: &str = r#"
@@ -64,6 +74,7 @@ const FETCH_REGIONS_QUERY: &str = r#"
             number,
             name
         from regions
+        where deleted_at is null
     "#;
 
 fn fetch_regions_query<'builder>() -> sqlx::QueryBuilder<'builder, Postgres> {
@@ -152,6 +163,8 @@ const FETCH_LEADERSHIP_QUERY: &str = r#"
         JOIN regions reg ON reg.uid = lr.region
         JOIN users u ON u.id = lr.user_id
         JOIN leadership_role r ON r.uid = lr.role
+    WHERE
+        lr.deleted_at IS NULL
 "#;
 
 fn fetch_leadership_query<'builder>() -> QueryBuilder<'builder, Postgres> {
@@ -163,7 +176,7 @@ pub async fn all_leadership(
     filter: leadership::DateFilter,
 ) -> Result<Vec<Leadership>> {
     let mut query = fetch_leadership_query();
-    leadership::apply_date_filter(&mut query, &filter, false);
+    leadership::apply_date_filter(&mut query, &filter, true);
     query
         .build_query_as::<Leadership>()
         .fetch_all(pool)
@@ -178,7 +191,7 @@ pub async fn leadership_by_number(
 ) -> Result<Vec<Leadership>> {
     let mut query = fetch_leadership_query();
     query
-        .push("WHERE lr.region = ")
+        .push(" AND lr.region = ")
         .push_bind(region_number as i64);
     leadership::apply_date_filter(&mut query, &filter, true);
     query
@@ -194,7 +207,7 @@ pub async fn leadership_by_uid(
     filter: leadership::DateFilter,
 ) -> Result<Vec<Leadership>> {
     let mut query = fetch_leadership_query();
-    query.push("WHERE reg.uid = ").push_bind(region_uid);
+    query.push(" AND reg.uid = ").push_bind(region_uid);
     leadership::apply_date_filter(&mut query, &filter, true);
     query
         .build_query_as::<Leadership>()
@@ -203,20 +216,22 @@ pub async fn leadership_by_uid(
         .map_err(Error::from)
 }
 
-pub async fn upsert_leadership(pool: &PgPool, leadership: &[Leadership]) -> Result<u64> {
+pub async fn upsert_leadership<'c, A>(conn: A, leadership: &[Leadership]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if leadership.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
 
-    let affected: Vec<u64> = stream::iter(
-        leadership
-            .iter()
-            .unique_by(|l| (l.region.uid, &l.user.id, l.role.uid, l.start_date)),
-    )
-    .chunks(DB_INSERT_CHUNK_SIZE)
-    .map(Ok::<_, Error>)
-    .and_then(|chunk| async move {
-        let result = QueryBuilder::new(
+    let unique = leadership
+        .iter()
+        .unique_by(|l| (l.region.uid, &l.user.id, l.role.uid, l.start_date))
+        .collect_vec();
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in unique.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
             r#"INSERT INTO leadership_region(
                     region,
                     user_id,
@@ -224,35 +239,41 @@ pub async fn upsert_leadership(pool: &PgPool, leadership: &[Leadership]) -> Resu
                     start_date,
                     end_date
                 ) "#,
-        )
-        .push_values(&chunk, |mut b, lead| {
-            b.push_bind(lead.region.uid)
-                .push_bind(&lead.user.id)
-                .push_bind(lead.role.uid)
-                .push_bind(lead.start_date)
-                .push_bind(lead.end_date);
-        })
-        .push(
-            r#"ON CONFLICT(region, user_id, role, start_date) DO UPDATE SET
-                    end_date = excluded.end_date
+        );
+        query
+            .push_values(chunk, |mut b, lead| {
+                b.push_bind(lead.region.uid)
+                    .push_bind(&lead.user.id)
+                    .push_bind(lead.role.uid)
+                    .push_bind(lead.start_date)
+                    .push_bind(lead.end_date);
+            })
+            .push(
+                r#"ON CONFLICT(region, user_id, role, start_date) DO UPDATE SET
+                    end_date = excluded.end_date,
+                    deleted_at = NULL
+                WHERE leadership_region.end_date IS DISTINCT FROM excluded.end_date
+                    OR leadership_region.deleted_at IS NOT NULL
                 "#,
-        )
-        .build()
-        .execute(pool)
-        .await?;
-        Ok::<u64, Error>(result.rows_affected())
-    })
-    .try_collect()
-    .await?;
-    Ok(affected.iter().sum())
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
 }
 
-pub async fn retain_leadership(pool: &PgPool, leadership: &[Leadership]) -> Result<u64> {
+pub async fn retain_leadership<'c, A>(
+    conn: A,
+    guard: &DeleteGuard,
+    leadership: &[Leadership],
+) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if leadership.is_empty() {
         return Ok(0);
     }
 
-    let mut tx = pool.begin().await?;
+    let mut tx = conn.begin().await?;
 
     // Create temp table to hold keys to keep
     sqlx::query(
@@ -283,20 +304,16 @@ pub async fn retain_leadership(pool: &PgPool, leadership: &[Leadership]) -> Resu
     }
 
     // Delete rows not in temp table
-    let result = sqlx::query(
-        r#"DELETE FROM leadership_region lr
-           WHERE NOT EXISTS (
+    let condition = r#"NOT EXISTS (
                SELECT 1 FROM _keep_leadership_region k
-               WHERE k.region = lr.region
-                 AND k.user_id = lr.user_id
-                 AND k.role = lr.role
-                 AND k.start_date = lr.start_date
-           )"#,
-    )
-    .execute(&mut *tx)
-    .await?;
-
-    let total_affected = result.rows_affected();
+               WHERE k.region = leadership_region.region
+                 AND k.user_id = leadership_region.user_id
+                 AND k.role = leadership_region.role
+                 AND k.start_date = leadership_region.start_date
+           )"#;
+    let total_affected = guard
+        .delete(&mut tx, "leadership_region", condition)
+        .await?;
     tx.commit().await?;
     Ok(total_affected)
 }
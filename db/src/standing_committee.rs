@@ -1,7 +1,10 @@
-use crate::{DB_INSERT_CHUNK_SIZE, Error, Result, leadership, retain_with_keys, user};
-use futures::{StreamExt, TryFutureExt, TryStreamExt, stream};
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Error, Result, UpsertStats, execute_upsert, guard::DeleteGuard,
+    leadership, retain_with_keys, user,
+};
+use futures::TryFutureExt;
 use itertools::Itertools;
-use sqlx::{PgPool, Postgres, QueryBuilder};
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
 
 pub async fn all(pool: &PgPool) -> Result<Vec<StandingCommittee>> {
     sqlx::query_as::<_, StandingCommittee>(FETCH_STANDING_COMMITTEES_QUERY)
@@ -12,7 +15,7 @@ pub async fn all(pool: &PgPool) -> Result<Vec<StandingCommittee>> {
 
 pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<StandingCommittee>> {
     fetch_standing_committees_query()
-        .push(" WHERE uid = ")
+        .push(" AND uid = ")
         .push_bind(uid)
         .build_query_as::<StandingCommittee>()
         .fetch_optional(pool)
@@ -20,14 +23,19 @@ pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<StandingCommittee>
         .await
 }
 
-pub async fn upsert_many(pool: &PgPool, committees: &[StandingCommittee]) -> Result<u64> {
+pub async fn upsert_many<'c, A>(conn: A, committees: &[StandingCommittee]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if committees.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
 
-    let mut total_affected = 0u64;
+    let mut conn = conn.acquire().await?;
+    let mut total = UpsertStats::default();
     for chunk in committees.chunks(DB_INSERT_CHUNK_SIZE) {
-        let result = QueryBuilder::new("INSERT INTO standing_committees(uid, name, active) ")
+        let mut query = QueryBuilder::new("INSERT INTO standing_committees(uid, name, active) ");
+        query
             .push_values(chunk, |mut b, committee| {
                 b.push_bind(committee.uid)
                     .push_bind(&committee.name)
@@ -36,19 +44,30 @@ pub async fn upsert_many(pool: &PgPool, committees: &[StandingCommittee]) -> Res
             .push(
                 r#"ON CONFLICT(uid) DO UPDATE SET
                     name = excluded.name,
-                    active = excluded.active
+                    active = excluded.active,
+                    deleted_at = NULL
+                WHERE (standing_committees.name, standing_committees.active)
+                    IS DISTINCT FROM (excluded.name, excluded.active)
+                    OR standing_committees.deleted_at IS NOT NULL
                 "#,
-            )
-            .build()
-            .execute(pool)
-            .await?;
-        total_affected += result.rows_affected();
+            );
+        total += execute_upsert(&mut *conn, query, chunk.len()).await?;
     }
-    Ok(total_affected)
+    Ok(total)
 }
 
-pub async fn retain(pool: &PgPool, committees: &[StandingCommittee]) -> Result<u64> {
-    retain_with_keys(pool, "standing_committees", "uid", committees, |c| c.uid).await
+pub async fn retain<'c, A>(
+    conn: A,
+    guard: &DeleteGuard,
+    committees: &[StandingCommittee],
+) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(conn, guard, "standing_committees", "uid", committees, |c| {
+        c.uid
+    })
+    .await
 }
 
 const FETCH_STANDING_COMMITTEES_QUERY: &str = r#"
@@ -57,6 +76,7 @@ const FETCH_STANDING_COMMITTEES_QUERY: &str = r#"
         name,
         active
     FROM standing_committees
+    WHERE deleted_at IS NULL
 "#;
 
 fn fetch_standing_committees_query<'builder>() -> QueryBuilder<'builder, Postgres> {
@@ -147,6 +167,8 @@ const FETCH_LEADERSHIP_QUERY: &str = r#"
         JOIN standing_committees sc ON sc.uid = lsc.standing_committee
         JOIN users u ON u.id = lsc.user_id
         JOIN leadership_role r ON r.uid = lsc.role
+    WHERE
+        lsc.deleted_at IS NULL
 "#;
 
 fn fetch_leadership_query<'builder>() -> QueryBuilder<'builder, Postgres> {
@@ -158,7 +180,7 @@ pub async fn all_leadership(
     filter: leadership::DateFilter,
 ) -> Result<Vec<Leadership>> {
     let mut query = fetch_leadership_query();
-    leadership::apply_date_filter(&mut query, &filter, false);
+    leadership::apply_date_filter(&mut query, &filter, true);
     query
         .build_query_as::<Leadership>()
         .fetch_all(pool)
@@ -172,7 +194,7 @@ pub async fn leadership_by_uid(
     filter: leadership::DateFilter,
 ) -> Result<Vec<Leadership>> {
     let mut query = fetch_leadership_query();
-    query.push("WHERE sc.uid = ").push_bind(committee_uid);
+    query.push(" AND sc.uid = ").push_bind(committee_uid);
     leadership::apply_date_filter(&mut query, &filter, true);
     query
         .build_query_as::<Leadership>()
@@ -181,23 +203,29 @@ pub async fn leadership_by_uid(
         .map_err(Error::from)
 }
 
-pub async fn upsert_leadership(pool: &PgPool, leadership: &[Leadership]) -> Result<u64> {
+pub async fn upsert_leadership<'c, A>(conn: A, leadership: &[Leadership]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if leadership.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
 
-    let affected: Vec<u64> = stream::iter(leadership.iter().unique_by(|l| {
-        (
-            l.standing_committee.uid,
-            &l.user.id,
-            l.role.uid,
-            l.start_date,
-        )
-    }))
-    .chunks(DB_INSERT_CHUNK_SIZE)
-    .map(Ok::<_, Error>)
-    .and_then(|chunk| async move {
-        let result = QueryBuilder::new(
+    let unique = leadership
+        .iter()
+        .unique_by(|l| {
+            (
+                l.standing_committee.uid,
+                &l.user.id,
+                l.role.uid,
+                l.start_date,
+            )
+        })
+        .collect_vec();
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in unique.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
             r#"INSERT INTO leadership_standing_committee(
                     standing_committee,
                     user_id,
@@ -205,35 +233,41 @@ pub async fn upsert_leadership(pool: &PgPool, leadership: &[Leadership]) -> Resu
                     start_date,
                     end_date
                 ) "#,
-        )
-        .push_values(&chunk, |mut b, lead| {
-            b.push_bind(lead.standing_committee.uid)
-                .push_bind(&lead.user.id)
-                .push_bind(lead.role.uid)
-                .push_bind(lead.start_date)
-                .push_bind(lead.end_date);
-        })
-        .push(
-            r#"ON CONFLICT(standing_committee, user_id, role, start_date) DO UPDATE SET
-                    end_date = excluded.end_date
+        );
+        query
+            .push_values(chunk, |mut b, lead| {
+                b.push_bind(lead.standing_committee.uid)
+                    .push_bind(&lead.user.id)
+                    .push_bind(lead.role.uid)
+                    .push_bind(lead.start_date)
+                    .push_bind(lead.end_date);
+            })
+            .push(
+                r#"ON CONFLICT(standing_committee, user_id, role, start_date) DO UPDATE SET
+                    end_date = excluded.end_date,
+                    deleted_at = NULL
+                WHERE leadership_standing_committee.end_date IS DISTINCT FROM excluded.end_date
+                    OR leadership_standing_committee.deleted_at IS NOT NULL
                 "#,
-        )
-        .build()
-        .execute(pool)
-        .await?;
-        Ok::<u64, Error>(result.rows_affected())
-    })
-    .try_collect()
-    .await?;
-    Ok(affected.iter().sum())
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
 }
 
-pub async fn retain_leadership(pool: &PgPool, leadership: &[Leadership]) -> Result<u64> {
+pub async fn retain_leadership<'c, A>(
+    conn: A,
+    guard: &DeleteGuard,
+    leadership: &[Leadership],
+) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if leadership.is_empty() {
         return Ok(0);
     }
 
-    let mut tx = pool.begin().await?;
+    let mut tx = conn.begin().await?;
 
     // Create temp table to hold keys to keep
     sqlx::query(
@@ -264,20 +298,16 @@ pub async fn retain_leadership(pool: &PgPool, leadership: &[Leadership]) -> Resu
     }
 
     // Delete rows not in temp table
-    let result = sqlx::query(
-        r#"DELETE FROM leadership_standing_committee lsc
-           WHERE NOT EXISTS (
+    let condition = r#"NOT EXISTS (
                SELECT 1 FROM _keep_leadership_standing_committee k
-               WHERE k.standing_committee = lsc.standing_committee
-                 AND k.user_id = lsc.user_id
-                 AND k.role = lsc.role
-                 AND k.start_date = lsc.start_date
-           )"#,
-    )
-    .execute(&mut *tx)
-    .await?;
-
-    let total_affected = result.rows_affected();
+               WHERE k.standing_committee = leadership_standing_committee.standing_committee
+                 AND k.user_id = leadership_standing_committee.user_id
+                 AND k.role = leadership_standing_committee.role
+                 AND k.start_date = leadership_standing_committee.start_date
+           )"#;
+    let total_affected = guard
+        .delete(&mut tx, "leadership_standing_committee", condition)
+        .await?;
     tx.commit().await?;
     Ok(total_affected)
 }
@@ -1,6 +1,7 @@
-use crate::{DB_INSERT_CHUNK_SIZE, Error, Result, retain_with_keys};
-use futures::{StreamExt, TryStreamExt, stream};
-use sqlx::{PgPool, Postgres};
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Result, UpsertStats, execute_upsert, guard::DeleteGuard, retain_with_keys,
+};
+use sqlx::{Acquire, PgPool, Postgres};
 
 #[derive(Debug, sqlx::FromRow, serde::Serialize)]
 pub struct User {
@@ -22,6 +23,8 @@ pub const FETCH_USER_QUERY: &str = r#"
         last_name
     FROM
         users
+    WHERE
+        deleted_at IS NULL
 "#;
 
 fn fetch_user_query<'builder>() -> sqlx::QueryBuilder<'builder, Postgres> {
@@ -36,7 +39,7 @@ pub fn id_for_email(email: &str) -> String {
 
 pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<User>> {
     let user = fetch_user_query()
-        .push("WHERE uid = ")
+        .push(" AND uid = ")
         .push_bind(uid)
         .build_query_as::<User>()
         .fetch_optional(pool)
@@ -45,9 +48,20 @@ pub async fn by_uid(pool: &PgPool, uid: i64) -> Result<Option<User>> {
     Ok(user)
 }
 
+/// The ids of the users with the given uid, including removed users. A uid
+/// has several after its email changed, as the id derives from the email.
+pub async fn ids_for_uid(pool: &PgPool, uid: i64) -> Result<Vec<String>> {
+    let ids = sqlx::query_scalar("SELECT id FROM users WHERE uid = $1")
+        .bind(uid)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(ids)
+}
+
 pub async fn by_email(pool: &PgPool, email: &str) -> Result<Option<User>> {
     let user = fetch_user_query()
-        .push("WHERE id = ")
+        .push(" AND id = ")
         .push_bind(id_for_email(email))
         .build_query_as::<User>()
         .fetch_optional(pool)
@@ -56,23 +70,26 @@ pub async fn by_email(pool: &PgPool, email: &str) -> Result<Option<User>> {
     Ok(user)
 }
 
-pub async fn upsert_many(pool: &PgPool, users: &[User]) -> Result<u64> {
+pub async fn upsert_many<'c, A>(conn: A, users: &[User]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
     if users.is_empty() {
-        return Ok(0);
+        return Ok(UpsertStats::default());
     }
-    let affected: Vec<u64> = stream::iter(users)
-        .chunks(DB_INSERT_CHUNK_SIZE)
-        .map(Ok)
-        .and_then(|chunk| async move {
-            let result = sqlx::QueryBuilder::new(
-                r#"INSERT INTO users (
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in users.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = sqlx::QueryBuilder::new(
+            r#"INSERT INTO users (
                     id,
                     uid,
                     email,
                     first_name,
                     last_name
                 ) "#,
-            )
+        );
+        query
             .push_values(chunk, |mut b, user| {
                 b.push_bind(&user.id)
                     .push_bind(user.uid)
@@ -85,19 +102,21 @@ pub async fn upsert_many(pool: &PgPool, users: &[User]) -> Result<u64> {
                 uid = excluded.uid,
                 email = excluded.email,
                 first_name = excluded.first_name,
-                last_name = excluded.last_name
+                last_name = excluded.last_name,
+                deleted_at = NULL
+            WHERE (users.uid, users.email, users.first_name, users.last_name)
+                IS DISTINCT FROM (excluded.uid, excluded.email, excluded.first_name, excluded.last_name)
+                OR users.deleted_at IS NOT NULL
             "#,
-            )
-            .build()
-            .execute(pool)
-            .await?;
-            Ok::<u64, Error>(result.rows_affected())
-        })
-        .try_collect()
-        .await?;
-    Ok(affected.iter().sum())
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
 }
 
-pub async fn retain(pool: &PgPool, users: &[User]) -> Result<u64> {
-    retain_with_keys(pool, "users", "id", users, |user| user.id.as_str()).await
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, users: &[User]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(conn, guard, "users", "id", users, |user| user.id.as_str()).await
 }
@@ -0,0 +1,76 @@
+//! Drupal roles of users, by their Drupal uid.
+
+use crate::{
+    DB_INSERT_CHUNK_SIZE, Error, Result, UpsertStats, execute_upsert, guard::DeleteGuard,
+    retain_with_keys,
+};
+use futures::TryFutureExt;
+use itertools::Itertools;
+use sqlx::{Acquire, PgPool, Postgres, QueryBuilder};
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct UserRole {
+    pub user_uid: i64,
+    pub role: String,
+}
+
+/// The roles of a user
+pub async fn by_user_uid(pool: &PgPool, uid: i64) -> Result<Vec<UserRole>> {
+    sqlx::query_as::<_, UserRole>(
+        r#"
+        SELECT user_uid, role
+        FROM user_roles
+        WHERE deleted_at IS NULL AND user_uid = $1
+        ORDER BY role
+        "#,
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .map_err(Error::from)
+    .await
+}
+
+pub async fn upsert_many<'c, A>(conn: A, roles: &[UserRole]) -> Result<UpsertStats>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    if roles.is_empty() {
+        return Ok(UpsertStats::default());
+    }
+    let unique = roles
+        .iter()
+        .unique_by(|role| (role.user_uid, &role.role))
+        .collect_vec();
+    let mut conn = conn.acquire().await?;
+    let mut stats = UpsertStats::default();
+    for chunk in unique.chunks(DB_INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new("INSERT INTO user_roles(user_uid, role) ");
+        query
+            .push_values(chunk, |mut b, role| {
+                b.push_bind(role.user_uid).push_bind(&role.role);
+            })
+            .push(
+                r#"ON CONFLICT(user_uid, role) DO UPDATE SET
+                    deleted_at = NULL
+                WHERE user_roles.deleted_at IS NOT NULL
+                "#,
+            );
+        stats += execute_upsert(&mut *conn, query, chunk.len()).await?;
+    }
+    Ok(stats)
+}
+
+pub async fn retain<'c, A>(conn: A, guard: &DeleteGuard, roles: &[UserRole]) -> Result<u64>
+where
+    A: Acquire<'c, Database = Postgres>,
+{
+    retain_with_keys(
+        conn,
+        guard,
+        "user_roles",
+        "concat_ws(':', user_uid, role)",
+        roles,
+        |role| format!("{}:{}", role.user_uid, role.role),
+    )
+    .await
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::TestDb;
+use db::address::{self, UserAddress};
+
+fn address(paragraph_id: i64, user_uid: i64, delta: i32, city: &str) -> UserAddress {
+    UserAddress {
+        paragraph_id,
+        user_uid,
+        delta,
+        street_address: None,
+        street_address_2: None,
+        city: Some(city.to_string()),
+        state: None,
+        zip_code: None,
+        country: Some("US".to_string()),
+        is_primary: false,
+        is_mailing_address: false,
+    }
+}
+
+fn city(address: Option<UserAddress>) -> Option<String> {
+    address.and_then(|address| address.city)
+}
+
+#[tokio::test]
+async fn addresses_are_picked_by_their_flags() {
+    let Some(db) = TestDb::create().await else {
+        return;
+    };
+    // User 1 flags a primary and a mailing address, user 2 only a primary
+    // address, user 3 neither
+    let mut addresses = vec![
+        address(10, 1, 0, "Jackson Center"),
+        address(11, 1, 1, "Quartzsite"),
+        address(12, 1, 2, "Asheville"),
+        address(20, 2, 0, "Sedona"),
+        address(21, 2, 1, "Moab"),
+        address(30, 3, 1, "Bend"),
+        address(31, 3, 0, "Eugene"),
+    ];
+    addresses[1].is_primary = true;
+    addresses[2].is_mailing_address = true;
+    addresses[4].is_primary = true;
+    address::upsert_user_addresses(&db.pool, &addresses)
+        .await
+        .unwrap();
+
+    let all = address::all_for_user(&db.pool, 3).await.unwrap();
+    assert_eq!(
+        all.iter()
+            .map(|address| address.paragraph_id)
+            .collect::<Vec<_>>(),
+        [31, 30]
+    );
+
+    let primary = address::primary(&db.pool, 1).await.unwrap();
+    assert_eq!(city(primary).as_deref(), Some("Quartzsite"));
+    let mailing = address::mailing(&db.pool, 1).await.unwrap();
+    assert_eq!(city(mailing).as_deref(), Some("Asheville"));
+
+    // Without a mailing address, users are mailed at their primary address,
+    // and without that at their first
+    let mailing = address::mailing(&db.pool, 2).await.unwrap();
+    assert_eq!(city(mailing).as_deref(), Some("Moab"));
+    let primary = address::primary(&db.pool, 3).await.unwrap();
+    assert_eq!(city(primary).as_deref(), Some("Eugene"));
+    let mailing = address::mailing(&db.pool, 3).await.unwrap();
+    assert_eq!(city(mailing).as_deref(), Some("Eugene"));
+
+    assert!(address::mailing(&db.pool, 4).await.unwrap().is_none());
+
+    let mut mailing = address::mailing_many(&db.pool, &[1, 2, 3, 4])
+        .await
+        .unwrap();
+    assert_eq!(mailing.len(), 3);
+    assert_eq!(city(mailing.remove(&1)).as_deref(), Some("Asheville"));
+    assert_eq!(city(mailing.remove(&2)).as_deref(), Some("Moab"));
+    assert_eq!(city(mailing.remove(&3)).as_deref(), Some("Eugene"));
+
+    db.drop().await;
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use chrono::NaiveDate;
+use common::TestDb;
+use db::{
+    airstream::{self, Airstream, Ownership},
+    guard::DeleteGuard,
+};
+
+fn airstream(airstream_id: i64, model: &str) -> Airstream {
+    Airstream {
+        airstream_id,
+        vin: None,
+        model: Some(model.to_string()),
+        airstream_type: Some("Trailer".to_string()),
+        year: Some(1961),
+        length: Some(26.0),
+    }
+}
+
+fn ownership(paragraph_id: i64, airstream: Airstream, user_uid: i64) -> Ownership {
+    Ownership {
+        paragraph_id,
+        airstream,
+        user_uid,
+        include_partner: false,
+        join_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+        leave_date: None,
+    }
+}
+
+/// The paragraphs and partner flags of the ownership of a user
+async fn owned(db: &TestDb, uid: i64) -> Vec<(i64, bool)> {
+    airstream::by_user_uid(&db.pool, uid)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|owned| (owned.paragraph_id, owned.include_partner))
+        .collect()
+}
+
+#[tokio::test]
+async fn ownership_is_synced_with_its_airstreams() {
+    let Some(db) = TestDb::create().await else {
+        return;
+    };
+    let bambi = airstream(100, "Bambi");
+    let overlander = airstream(101, "Overlander");
+    let airstreams = [bambi.clone(), overlander.clone()];
+    let mut ownership = vec![
+        ownership(10, bambi, 1),
+        ownership(11, overlander.clone(), 1),
+        ownership(12, overlander, 2),
+    ];
+    airstream::upsert_many(&db.pool, &airstreams).await.unwrap();
+    let stats = airstream::upsert_ownership(&db.pool, &ownership)
+        .await
+        .unwrap();
+    assert_eq!(stats.inserted, 3);
+    assert_eq!(owned(&db, 1).await, [(10, false), (11, false)]);
+
+    // Sharing with the partner is an update of the ownership
+    ownership[1].include_partner = true;
+    let stats = airstream::upsert_ownership(&db.pool, &ownership)
+        .await
+        .unwrap();
+    assert_eq!((stats.updated, stats.unchanged), (1, 2));
+    assert_eq!(owned(&db, 1).await, [(10, false), (11, true)]);
+
+    // The Bambi is gone. Its ownership references it, so it has to be
+    // removed before the airstream.
+    let guard = DeleteGuard::default();
+    let remaining_airstreams = &airstreams[1..];
+    let remaining_ownership = &ownership[1..];
+    assert!(
+        airstream::retain(&db.pool, &guard, remaining_airstreams)
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        airstream::retain_ownership(&db.pool, &guard, remaining_ownership)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        airstream::retain(&db.pool, &guard, remaining_airstreams)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(owned(&db, 1).await, [(11, true)]);
+    assert_eq!(owned(&db, 2).await, [(12, false)]);
+
+    db.drop().await;
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::TestDb;
+use db::audit;
+
+/// The recorded changes of a user as `field: old -> new`
+async fn changes(db: &TestDb, user_id: &str) -> Vec<String> {
+    audit::timeline(&db.pool, &[user_id.to_string()])
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|entry| {
+            format!(
+                "{} {}: {} -> {}",
+                entry.source,
+                entry.field,
+                entry.old_value.as_deref().unwrap_or("null"),
+                entry.new_value.as_deref().unwrap_or("null")
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn records_changes_of_audited_transactions() {
+    let Some(db) = TestDb::create().await else {
+        return;
+    };
+    let (run_id,): (i64,) = sqlx::query_as("INSERT INTO sync_runs DEFAULT VALUES RETURNING id")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+
+    let mut tx = db.pool.begin().await.unwrap();
+    audit::start(&mut tx, Some(run_id)).await.unwrap();
+    sqlx::raw_sql(
+        r#"
+        INSERT INTO users (id, email, uid) VALUES ('a', 'a@airstream.test', 1),
+            ('b', 'b@airstream.test', 2);
+        INSERT INTO members (primary_user, member_class, member_type, expiration_date)
+        VALUES ('a', 'regular', 'regular', '2026-01-31'), ('b', 'lifetime', 'regular', NULL);
+        INSERT INTO leadership_role (uid, title) VALUES (3, 'President');
+        INSERT INTO leadership_international (user_id, role, start_date)
+        VALUES ('a', 3, '2025-01-01');
+        "#,
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+    assert_eq!(audit::finish(&mut tx).await.unwrap(), 8);
+    tx.commit().await.unwrap();
+
+    let mut tx = db.pool.begin().await.unwrap();
+    audit::start(&mut tx, None).await.unwrap();
+    sqlx::raw_sql(
+        r#"
+        UPDATE members SET expiration_date = '2027-01-31' WHERE primary_user = 'a';
+        UPDATE members SET member_class = 'lifetime' WHERE primary_user = 'b'; -- unchanged
+        UPDATE members SET deleted_at = now() WHERE primary_user = 'b';
+        UPDATE leadership_international SET end_date = '2025-12-31';
+        "#,
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+    assert_eq!(audit::finish(&mut tx).await.unwrap(), 4);
+    tx.commit().await.unwrap();
+
+    // Writes outside of audited transactions are not recorded
+    sqlx::query("UPDATE members SET deleted_at = NULL WHERE primary_user = 'b'")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        changes(&db, "a").await,
+        [
+            "leadership role: null -> President",
+            "member expiration_date: null -> 2026-01-31",
+            "member member_class: null -> regular",
+            "member member_type: null -> regular",
+            "user email: null -> a@airstream.test",
+            "leadership end_date: null -> 2025-12-31",
+            "member expiration_date: 2026-01-31 -> 2027-01-31",
+        ]
+    );
+    assert_eq!(
+        changes(&db, "b").await,
+        [
+            "member member_class: null -> lifetime",
+            "member member_type: null -> regular",
+            "user email: null -> b@airstream.test",
+            "member member_class: lifetime -> null",
+            "member member_type: regular -> null",
+        ]
+    );
+    let runs: Vec<Option<i64>> =
+        sqlx::query_scalar("SELECT DISTINCT run_id FROM member_changes ORDER BY 1")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!(runs, [Some(run_id), None]);
+
+    db.drop().await;
+}
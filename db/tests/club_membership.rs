@@ -0,0 +1,85 @@
+mod common;
+
+use chrono::NaiveDate;
+use common::TestDb;
+use db::{
+    club_membership::{self, ClubMembership},
+    guard::DeleteGuard,
+    member::{MemberClass, MemberType},
+};
+
+fn membership(club: i64, member_type: MemberType, join_date: &str) -> ClubMembership {
+    ClubMembership {
+        user_id: "wally".to_string(),
+        club,
+        member_type,
+        member_class: MemberClass::Regular,
+        join_date: join_date.parse().unwrap(),
+        expiration_date: None,
+    }
+}
+
+/// The clubs and types of the memberships of the user, home club first
+async fn memberships(db: &TestDb) -> Vec<(i64, MemberType, NaiveDate)> {
+    club_membership::by_user_id(&db.pool, "wally")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|membership| {
+            (
+                membership.club,
+                membership.member_type,
+                membership.join_date,
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn memberships_are_kept_per_club_and_type() {
+    let Some(db) = TestDb::create().await else {
+        return;
+    };
+    sqlx::raw_sql(
+        r#"
+        INSERT INTO users (id, email, uid) VALUES ('wally', 'wally@airstream.test', 1);
+        INSERT INTO clubs (number, uid, name) VALUES (7, 70, 'Silver Bullets'),
+            (8, 80, 'Wally Byam');
+        "#,
+    )
+    .execute(&db.pool)
+    .await
+    .unwrap();
+    // The first of several memberships in a club with the same type is kept,
+    // the sync passes the latest first
+    let latest = [
+        membership(80, MemberType::Intraclub, "2024-01-01"),
+        membership(70, MemberType::Regular, "2020-01-01"),
+        membership(80, MemberType::Intraclub, "2019-01-01"),
+    ];
+    let stats = club_membership::upsert_many(&db.pool, &latest)
+        .await
+        .unwrap();
+    assert_eq!(stats.inserted, 2);
+    assert_eq!(
+        memberships(&db).await,
+        [
+            (70, MemberType::Regular, "2020-01-01".parse().unwrap()),
+            (80, MemberType::Intraclub, "2024-01-01".parse().unwrap()),
+        ]
+    );
+
+    let guard = DeleteGuard::default();
+    assert_eq!(
+        club_membership::retain(&db.pool, &guard, &latest[1..2])
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        club_membership::by_club(&db.pool, 80).await.unwrap().len(),
+        0
+    );
+
+    db.drop().await;
+}
@@ -0,0 +1,80 @@
+//! A scratch database with the app schema, for tests that need Postgres.
+//!
+//! Set `DATABASE_URL` to a server the tests may create databases on, like
+//! `postgres://postgres@localhost:5432/postgres`. Tests are skipped without
+//! it.
+
+use sqlx::{Connection, Executor, PgConnection, PgPool, postgres::PgConnectOptions};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The migration dropping `clubs_region_fkey` a second time, which only
+/// works on databases that predate the first
+const CLUB_REGION_UID: i64 = 20251209120001;
+
+pub struct TestDb {
+    pub pool: PgPool,
+    name: String,
+    url: String,
+}
+
+impl TestDb {
+    /// A fresh database with all migrations applied, or `None` when
+    /// `DATABASE_URL` is not set
+    pub async fn create() -> Option<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let Ok(url) = std::env::var("DATABASE_URL") else {
+            eprintln!("DATABASE_URL is not set, skipping");
+            return None;
+        };
+        let name = format!(
+            "db_test_{}_{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        );
+        let mut admin = PgConnection::connect(&url).await.unwrap();
+        admin
+            .execute(format!(r#"DROP DATABASE IF EXISTS "{name}""#).as_str())
+            .await
+            .unwrap();
+        admin
+            .execute(format!(r#"CREATE DATABASE "{name}""#).as_str())
+            .await
+            .unwrap();
+        admin.close().await.unwrap();
+
+        let options = url.parse::<PgConnectOptions>().unwrap().database(&name);
+        let pool = PgPool::connect_with(options).await.unwrap();
+        sqlx::raw_sql(include_str!("supabase.sql"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let migrations = concat!(env!("CARGO_MANIFEST_DIR"), "/../sync-app/migrations");
+        let migrator = sqlx::migrate::Migrator::new(std::path::Path::new(migrations))
+            .await
+            .unwrap();
+        for migration in migrator.iter() {
+            let mut sql = migration.sql.to_string();
+            if migration.version == CLUB_REGION_UID {
+                sql = sql.replace(
+                    "DROP CONSTRAINT clubs_region_fkey;",
+                    "DROP CONSTRAINT IF EXISTS clubs_region_fkey;",
+                );
+            }
+            sqlx::raw_sql(&sql)
+                .execute(&pool)
+                .await
+                .unwrap_or_else(|e| panic!("migration {}: {e}", migration.version));
+        }
+        Some(Self { pool, name, url })
+    }
+
+    /// Close the pool and drop the database
+    pub async fn drop(self) {
+        self.pool.close().await;
+        let mut admin = PgConnection::connect(&self.url).await.unwrap();
+        admin
+            .execute(format!(r#"DROP DATABASE "{}" WITH (FORCE)"#, self.name).as_str())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,20 @@
+-- The parts of Supabase the migrations rely on: its roles, which are shared
+-- by all databases of the server, and the `auth.jwt()` of requests
+do $$
+declare
+    role text;
+begin
+    foreach role in array array['anon', 'authenticated', 'service_role'] loop
+        begin
+            execute format('create role %I', role);
+        exception
+            -- It exists already, or another test created it meanwhile
+            when duplicate_object or unique_violation then null;
+        end;
+    end loop;
+end $$;
+
+create schema if not exists auth;
+create or replace function auth.jwt() returns jsonb language sql stable as $$
+    select coalesce(nullif(current_setting('request.jwt.claims', true), ''), '{}')::jsonb
+$$;
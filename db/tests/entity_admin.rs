@@ -0,0 +1,98 @@
+mod common;
+
+use common::TestDb;
+use db::{
+    entity_admin::{self, EntityAdmin},
+    user,
+};
+
+/// The numbers of the clubs administered by the user signed in with the email
+async fn administered_by(db: &TestDb, email: &str) -> Vec<i64> {
+    let mut tx = db.pool.begin().await.unwrap();
+    sqlx::query("SELECT set_config('request.jwt.claims', $1, true)")
+        .bind(serde_json::json!({ "email": email }).to_string())
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    let numbers = sqlx::query_scalar(
+        "SELECT number FROM public.administered_club_numbers() AS number ORDER BY 1",
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .unwrap();
+    tx.rollback().await.unwrap();
+    numbers
+}
+
+#[tokio::test]
+async fn admins_administer_their_club_or_the_clubs_of_their_region() {
+    let Some(db) = TestDb::create().await else {
+        return;
+    };
+    let users = [
+        (1, "club.admin@airstream.test"),
+        (2, "region.admin@airstream.test"),
+        (3, "removed.admin@airstream.test"),
+    ];
+    for (uid, email) in users {
+        sqlx::query("INSERT INTO users (id, email, uid) VALUES ($1, $2, $3)")
+            .bind(user::id_for_email(email))
+            .bind(email)
+            .bind(uid)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+    // Region 30 has clubs 7 and 8, region 40 has club 9. Numbers and uids
+    // differ, so mixing them up gives the wrong clubs.
+    sqlx::raw_sql(
+        r#"
+        INSERT INTO regions (number, uid, name) VALUES (3, 30, 'Region 3'), (4, 40, 'Region 4');
+        INSERT INTO clubs (number, uid, name, region)
+        VALUES (7, 70, 'Silver Bullets', 30), (8, 80, 'Wally Byam', 30),
+            (9, 90, 'Land Yachts', 40);
+        "#,
+    )
+    .execute(&db.pool)
+    .await
+    .unwrap();
+    let admins = [
+        EntityAdmin {
+            user_uid: 1,
+            entity_uid: 90,
+            is_region: false,
+        },
+        EntityAdmin {
+            user_uid: 2,
+            entity_uid: 30,
+            is_region: true,
+        },
+        EntityAdmin {
+            user_uid: 3,
+            entity_uid: 70,
+            is_region: false,
+        },
+    ];
+    entity_admin::upsert_many(&db.pool, &admins).await.unwrap();
+    sqlx::query("UPDATE entity_admins SET deleted_at = now() WHERE user_uid = 3")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+    assert_eq!(administered_by(&db, "club.admin@airstream.test").await, [9]);
+    // The email is matched regardless of case and surrounding space
+    assert_eq!(
+        administered_by(&db, " Region.Admin@airstream.test").await,
+        [7, 8]
+    );
+    assert_eq!(
+        administered_by(&db, "removed.admin@airstream.test").await,
+        [] as [i64; 0]
+    );
+    assert_eq!(
+        administered_by(&db, "member@airstream.test").await,
+        [] as [i64; 0]
+    );
+
+    db.drop().await;
+}
@@ -0,0 +1,102 @@
+use db::guard::{DeleteGuard, DeleteLimit, DeleteMode, MassDeletion, Scope};
+
+#[test]
+fn parse_limits() {
+    let guard = DeleteGuard::parse("20%", "members=500:5%, leadership_club=100").unwrap();
+    assert_eq!(guard.limit("users").percent, Some(20.0));
+    assert_eq!(
+        guard.limit("members"),
+        DeleteLimit {
+            rows: Some(500),
+            percent: Some(5.0)
+        }
+    );
+    assert_eq!(guard.limit("leadership_club").rows, Some(100));
+    assert_eq!(guard.limit("leadership_club").percent, None);
+
+    assert_eq!(
+        DeleteGuard::parse("", "").unwrap().limit("users"),
+        DeleteLimit::default()
+    );
+    assert!(DeleteGuard::parse("lots", "").is_err());
+    assert!(DeleteGuard::parse("20%", "members").is_err());
+}
+
+#[test]
+fn check_limits() {
+    let mut guard = DeleteGuard::parse("20%", "members=500").unwrap();
+    assert!(guard.check("users", 20, 100).is_ok());
+    assert!(guard.check("users", 0, 0).is_ok());
+
+    let err = guard.check("users", 21, 100).unwrap_err();
+    let err = err.downcast_ref::<MassDeletion>().unwrap();
+    assert_eq!(
+        (err.table.as_str(), err.deleting, err.total),
+        ("users", 21, 100)
+    );
+
+    // Per table limits replace the default
+    assert!(guard.check("members", 500, 600).is_ok());
+    assert!(guard.check("members", 501, 100_000).is_err());
+
+    guard.force = true;
+    assert!(guard.check("members", 600, 600).is_ok());
+}
+
+#[test]
+fn parse_delete_modes() {
+    assert_eq!(DeleteGuard::default().mode, DeleteMode::Hard);
+    assert_eq!("soft".parse::<DeleteMode>().unwrap(), DeleteMode::Soft);
+    assert_eq!("hard".parse::<DeleteMode>().unwrap(), DeleteMode::Hard);
+    assert!("archive".parse::<DeleteMode>().is_err());
+    assert_eq!(DeleteMode::Soft.to_string(), "soft");
+}
+
+#[test]
+fn scope_conditions() {
+    assert_eq!(Scope::All.condition("users").as_deref(), Some("true"));
+
+    let club = Scope::Club(42);
+    assert_eq!(club.condition("clubs").as_deref(), Some("uid = 42"));
+    assert_eq!(
+        club.condition("leadership_club").as_deref(),
+        Some("club = 42")
+    );
+    assert!(
+        club.condition("members")
+            .unwrap()
+            .contains("WHERE uid = 42")
+    );
+    assert_eq!(
+        club.condition("club_memberships").as_deref(),
+        Some("club = 42")
+    );
+    assert_eq!(club.condition("regions"), None);
+    assert_eq!(club.condition("users"), None);
+
+    let region = Scope::Region(7);
+    assert_eq!(region.condition("regions").as_deref(), Some("uid = 7"));
+    assert_eq!(region.condition("clubs").as_deref(), Some("region = 7"));
+    assert!(
+        region
+            .condition("brns")
+            .unwrap()
+            .contains("WHERE region = 7")
+    );
+    assert!(
+        region
+            .condition("club_memberships")
+            .unwrap()
+            .contains("WHERE region = 7")
+    );
+    assert_eq!(region.condition("leadership_international"), None);
+}
+
+#[test]
+fn scope_includes_clubs() {
+    assert!(Scope::All.includes(42, None));
+    assert!(Scope::Club(42).includes(42, Some(7)));
+    assert!(!Scope::Club(42).includes(43, Some(7)));
+    assert!(Scope::Region(7).includes(43, Some(7)));
+    assert!(!Scope::Region(7).includes(42, None));
+}
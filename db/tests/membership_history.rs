@@ -0,0 +1,88 @@
+mod common;
+
+use chrono::NaiveDate;
+use common::TestDb;
+use db::{
+    guard::{DeleteGuard, DeleteMode},
+    member::{MemberClass, MemberType},
+    membership_history::{self, Period},
+};
+
+fn date(date: &str) -> NaiveDate {
+    date.parse().unwrap()
+}
+
+fn period(paragraph_id: i64, join_date: &str, leave_date: Option<&str>) -> Period {
+    Period {
+        paragraph_id,
+        user_uid: 1,
+        partner_uid: None,
+        club: Some(7),
+        member_class: MemberClass::Regular,
+        member_type: Some(MemberType::Regular),
+        join_date: date(join_date),
+        leave_date: leave_date.map(date),
+    }
+}
+
+/// The paragraphs and leave dates of the periods of user 1
+async fn periods(db: &TestDb) -> Vec<(i64, Option<NaiveDate>)> {
+    membership_history::by_user_uid(&db.pool, 1)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|period| (period.paragraph_id, period.leave_date))
+        .collect()
+}
+
+#[tokio::test]
+async fn periods_are_kept_per_paragraph() {
+    let Some(db) = TestDb::create().await else {
+        return;
+    };
+    // A lapsed period and the current one, in the same club
+    let mut history = vec![
+        period(10, "2015-03-01", Some("2018-02-28")),
+        period(11, "2021-06-01", None),
+    ];
+    let stats = membership_history::upsert_many(&db.pool, &history)
+        .await
+        .unwrap();
+    assert_eq!((stats.inserted, stats.updated, stats.unchanged), (2, 0, 0));
+    assert_eq!(
+        periods(&db).await,
+        [(10, Some(date("2018-02-28"))), (11, None)]
+    );
+
+    // The current period lapses, a later one begins
+    history[1].leave_date = Some(date("2024-05-31"));
+    history.push(period(12, "2025-01-01", None));
+    let stats = membership_history::upsert_many(&db.pool, &history)
+        .await
+        .unwrap();
+    assert_eq!((stats.inserted, stats.updated, stats.unchanged), (1, 1, 1));
+
+    // Paragraphs that are gone are removed, and restored when they return
+    let guard = DeleteGuard {
+        mode: DeleteMode::Soft,
+        ..DeleteGuard::default()
+    };
+    let retained = &history[1..];
+    assert_eq!(
+        membership_history::retain(&db.pool, &guard, retained)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        periods(&db).await,
+        [(11, Some(date("2024-05-31"))), (12, None)]
+    );
+    let stats = membership_history::upsert_many(&db.pool, &history)
+        .await
+        .unwrap();
+    assert_eq!((stats.inserted, stats.updated, stats.unchanged), (0, 1, 2));
+    assert_eq!(periods(&db).await.len(), 3);
+
+    db.drop().await;
+}
@@ -0,0 +1,21 @@
+use db::UpsertStats;
+
+#[test]
+fn upsert_stats_from_returned_rows() {
+    let stats = UpsertStats::from_returned(5, &[true, false, true]);
+    assert_eq!(
+        stats,
+        UpsertStats {
+            inserted: 2,
+            updated: 1,
+            unchanged: 2,
+        }
+    );
+    assert_eq!(stats.written(), 3);
+
+    let total: UpsertStats = [stats, UpsertStats::from_returned(1000, &[])]
+        .into_iter()
+        .sum();
+    assert_eq!(total.unchanged, 1002);
+    assert_eq!(total.written(), 3);
+}
@@ -4,7 +4,7 @@
 //! Each user can have multiple addresses with primary/mailing flags.
 
 use crate::Result;
-use sqlx::{MySqlPool, mysql::MySql};
+use sqlx::{Executor, mysql::MySql};
 
 /// User address record from Drupal database
 /// Each row represents an address paragraph entity
@@ -34,6 +34,29 @@ pub struct Address {
     pub is_mailing_address: bool,
 }
 
+pub mod db {
+    use super::*;
+    use ::db as app_db;
+
+    impl From<Address> for app_db::address::UserAddress {
+        fn from(value: Address) -> Self {
+            Self {
+                paragraph_id: value.paragraph_id as i64,
+                user_uid: value.user_uid as i64,
+                delta: value.delta as i32,
+                street_address: value.street_address,
+                street_address_2: value.street_address_2,
+                city: value.city,
+                state: value.state,
+                zip_code: value.zip_code,
+                country: value.country,
+                is_primary: value.is_primary,
+                is_mailing_address: value.is_mailing_address,
+            }
+        }
+    }
+}
+
 fn fetch_address_query<'builder>() -> sqlx::QueryBuilder<'builder, MySql> {
     sqlx::QueryBuilder::new(
         r#"
@@ -72,23 +95,29 @@ fn fetch_address_query<'builder>() -> sqlx::QueryBuilder<'builder, MySql> {
 }
 
 /// Fetch all addresses from Drupal
-pub async fn all(pool: &MySqlPool) -> Result<Vec<Address>> {
+pub async fn all<'e, E>(executor: E) -> Result<Vec<Address>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     fetch_address_query()
         .push(" ORDER BY ua.entity_id, ua.delta")
         .build_query_as::<Address>()
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await
         .map_err(Into::into)
 }
 
 /// Fetch addresses for a specific user
-pub async fn by_user_id(pool: &MySqlPool, user_uid: u64) -> Result<Vec<Address>> {
+pub async fn by_user_id<'e, E>(executor: E, user_uid: u64) -> Result<Vec<Address>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     fetch_address_query()
         .push(" AND ua.entity_id = ")
         .push_bind(user_uid)
         .push(" ORDER BY ua.delta")
         .build_query_as::<Address>()
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await
         .map_err(Into::into)
 }
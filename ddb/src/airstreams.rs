@@ -5,7 +5,7 @@
 
 use crate::Result;
 use chrono::NaiveDate;
-use sqlx::{MySqlPool, mysql::MySql};
+use sqlx::{Executor, mysql::MySql};
 
 /// Airstream ownership record from Drupal database
 /// Each row represents an ownership period (paragraph entity)
@@ -39,6 +39,31 @@ pub struct Airstream {
     pub length: Option<String>,
 }
 
+pub mod db {
+    use super::*;
+    use ::db as app_db;
+
+    impl From<Airstream> for app_db::airstream::Ownership {
+        fn from(value: Airstream) -> Self {
+            Self {
+                paragraph_id: value.paragraph_id as i64,
+                airstream: app_db::airstream::Airstream {
+                    airstream_id: value.airstream_id as i64,
+                    vin: value.vin,
+                    model: value.model,
+                    airstream_type: value.airstream_type,
+                    year: value.year,
+                    length: value.length.and_then(|length| length.trim().parse().ok()),
+                },
+                user_uid: value.user_id as i64,
+                include_partner: value.include_partner,
+                join_date: value.join_date,
+                leave_date: value.leave_date,
+            }
+        }
+    }
+}
+
 fn fetch_airstream_query<'builder>() -> sqlx::QueryBuilder<'builder, MySql> {
     // Return all ownership paragraphs with their dates
     // An airstream may have multiple ownership records (current + historical)
@@ -78,22 +103,28 @@ fn fetch_airstream_query<'builder>() -> sqlx::QueryBuilder<'builder, MySql> {
 }
 
 /// Fetch all airstream ownership records from Drupal
-pub async fn all(pool: &MySqlPool) -> Result<Vec<Airstream>> {
+pub async fn all<'e, E>(executor: E) -> Result<Vec<Airstream>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let airstreams = fetch_airstream_query()
         .build_query_as::<Airstream>()
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await?;
 
     Ok(airstreams)
 }
 
 /// Fetch airstream ownership records for a specific user
-pub async fn by_user_id(pool: &MySqlPool, user_id: u64) -> Result<Vec<Airstream>> {
+pub async fn by_user_id<'e, E>(executor: E, user_id: u64) -> Result<Vec<Airstream>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let airstreams = fetch_airstream_query()
         .push(" AND m.field_member_target_id = ")
         .push_bind(user_id)
         .build_query_as::<Airstream>()
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await?;
 
     Ok(airstreams)
@@ -1,31 +1,40 @@
 use crate::{Error, Result};
 use futures::TryFutureExt;
-use sqlx::{MySql, MySqlPool};
+use sqlx::{Executor, MySql};
 
-pub async fn all(pool: &MySqlPool) -> Result<Vec<Club>> {
+pub async fn all<'e, E>(executor: E) -> Result<Vec<Club>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     sqlx::query_as::<_, Club>(FETCH_CLUBS_QUERY)
-        .fetch_all(pool)
+        .fetch_all(executor)
         .map_err(Error::from)
         .await
 }
 
-pub async fn by_uid(pool: &MySqlPool, uid: u64) -> Result<Option<Club>> {
+pub async fn by_uid<'e, E>(executor: E, uid: u64) -> Result<Option<Club>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let club = fetch_clubs_query()
         .push(" AND nd.nid = ")
         .push_bind(uid)
         .build_query_as::<Club>()
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
     Ok(club)
 }
 
-pub async fn by_number(pool: &MySqlPool, number: i32) -> Result<Option<Club>> {
+pub async fn by_number<'e, E>(executor: E, number: i32) -> Result<Option<Club>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let club = fetch_clubs_query()
         .push(" AND cn.field_club_number_value = ")
         .push_bind(number)
         .build_query_as::<Club>()
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
     Ok(club)
@@ -1,6 +1,6 @@
 use crate::{Error, Result, users::User};
 use chrono::NaiveDate;
-use sqlx::{MySql, MySqlPool, QueryBuilder};
+use sqlx::{Acquire, Executor, MySql, QueryBuilder};
 
 /// Filter for leadership queries by date
 #[derive(Debug, Clone, Default)]
@@ -130,12 +130,15 @@ fn fetch_leadership_query<'builder>(
     query
 }
 
-async fn fetch_leadership_for_type(
-    pool: &MySqlPool,
+async fn fetch_leadership_for_type<'e, E>(
+    executor: E,
     entity_type: &str,
     entity_id: Option<u64>,
     filter: DateFilter,
-) -> Result<Vec<Leadership>> {
+) -> Result<Vec<Leadership>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     use futures::TryFutureExt;
 
     // Standing committees don't have explicit roles - they use implicit "Chair" role
@@ -150,66 +153,95 @@ async fn fetch_leadership_for_type(
 
     query
         .build_query_as::<Leadership>()
-        .fetch_all(pool)
+        .fetch_all(executor)
         .map_err(Error::from)
         .await
 }
 
-pub async fn for_club(pool: &MySqlPool, uid: u64, filter: DateFilter) -> Result<Vec<Leadership>> {
-    fetch_leadership_for_type(pool, "ssp_club", Some(uid), filter).await
+pub async fn for_club<'e, E>(executor: E, uid: u64, filter: DateFilter) -> Result<Vec<Leadership>>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    fetch_leadership_for_type(executor, "ssp_club", Some(uid), filter).await
 }
 
-pub async fn for_all_clubs(pool: &MySqlPool, filter: DateFilter) -> Result<Vec<Leadership>> {
-    fetch_leadership_for_type(pool, "ssp_club", None, filter).await
+pub async fn for_all_clubs<'e, E>(executor: E, filter: DateFilter) -> Result<Vec<Leadership>>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    fetch_leadership_for_type(executor, "ssp_club", None, filter).await
 }
 
-pub async fn for_region(pool: &MySqlPool, uid: u64, filter: DateFilter) -> Result<Vec<Leadership>> {
-    fetch_leadership_for_type(pool, "ssp_region", Some(uid), filter).await
+pub async fn for_region<'e, E>(executor: E, uid: u64, filter: DateFilter) -> Result<Vec<Leadership>>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    fetch_leadership_for_type(executor, "ssp_region", Some(uid), filter).await
 }
 
-pub async fn for_all_regions(pool: &MySqlPool, filter: DateFilter) -> Result<Vec<Leadership>> {
-    fetch_leadership_for_type(pool, "ssp_region", None, filter).await
+pub async fn for_all_regions<'e, E>(executor: E, filter: DateFilter) -> Result<Vec<Leadership>>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    fetch_leadership_for_type(executor, "ssp_region", None, filter).await
 }
 
-pub async fn for_club_by_number(
-    pool: &MySqlPool,
+pub async fn for_club_by_number<'c, A>(
+    conn: A,
     number: i32,
     filter: DateFilter,
-) -> Result<Vec<Leadership>> {
-    let club = crate::clubs::by_number(pool, number)
+) -> Result<Vec<Leadership>>
+where
+    A: Acquire<'c, Database = MySql>,
+{
+    let mut conn = conn.acquire().await?;
+    let club = crate::clubs::by_number(&mut *conn, number)
         .await?
         .ok_or_else(|| Error::Request(sqlx::Error::RowNotFound))?;
-    for_club(pool, club.uid, filter).await
+    for_club(&mut *conn, club.uid, filter).await
 }
 
-pub async fn for_region_by_number(
-    pool: &MySqlPool,
+pub async fn for_region_by_number<'c, A>(
+    conn: A,
     number: i32,
     filter: DateFilter,
-) -> Result<Vec<Leadership>> {
-    let region = crate::regions::by_number(pool, number)
+) -> Result<Vec<Leadership>>
+where
+    A: Acquire<'c, Database = MySql>,
+{
+    let mut conn = conn.acquire().await?;
+    let region = crate::regions::by_number(&mut *conn, number)
         .await?
         .ok_or_else(|| Error::Request(sqlx::Error::RowNotFound))?;
-    for_region(pool, region.uid, filter).await
+    for_region(&mut *conn, region.uid, filter).await
 }
 
-pub async fn for_international(pool: &MySqlPool, filter: DateFilter) -> Result<Vec<Leadership>> {
-    fetch_leadership_for_type(pool, "ssp_international_leadership", None, filter).await
+pub async fn for_international<'e, E>(executor: E, filter: DateFilter) -> Result<Vec<Leadership>>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    fetch_leadership_for_type(executor, "ssp_international_leadership", None, filter).await
 }
 
-pub async fn for_standing_committee(
-    pool: &MySqlPool,
+pub async fn for_standing_committee<'e, E>(
+    executor: E,
     uid: u64,
     filter: DateFilter,
-) -> Result<Vec<Leadership>> {
-    fetch_leadership_for_type(pool, "ssp_standing_committees", Some(uid), filter).await
+) -> Result<Vec<Leadership>>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    fetch_leadership_for_type(executor, "ssp_standing_committees", Some(uid), filter).await
 }
 
-pub async fn for_all_standing_committees(
-    pool: &MySqlPool,
+pub async fn for_all_standing_committees<'e, E>(
+    executor: E,
     filter: DateFilter,
-) -> Result<Vec<Leadership>> {
-    fetch_leadership_for_type(pool, "ssp_standing_committees", None, filter).await
+) -> Result<Vec<Leadership>>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    fetch_leadership_for_type(executor, "ssp_standing_committees", None, filter).await
 }
 
 pub mod db {
@@ -19,8 +19,16 @@ pub type Future<'a, T> = futures::future::BoxFuture<'a, Result<T>>;
 pub type Stream<'a, T> = futures::stream::BoxStream<'a, Result<T>>;
 
 pub async fn connect(url: &str) -> Result<sqlx::MySqlPool> {
-    use sqlx::{Executor, MySqlPool};
-    let pool = MySqlPool::connect(url).await?;
+    connect_with(url, 10).await
+}
+
+/// Connect with a pool of at most `max_connections` connections
+pub async fn connect_with(url: &str, max_connections: u32) -> Result<sqlx::MySqlPool> {
+    use sqlx::{Executor, mysql::MySqlPoolOptions};
+    let pool = MySqlPoolOptions::new()
+        .max_connections(max_connections)
+        .connect(url)
+        .await?;
     let _ = pool
         .execute(
             r#"
@@ -31,3 +39,58 @@ pub async fn connect(url: &str) -> Result<sqlx::MySqlPool> {
         .await?;
     Ok(pool)
 }
+
+/// A consistent, read only view of the membership database. Every query run
+/// on the snapshot sees the database as it was when the snapshot was taken,
+/// so edits made in Drupal while a sync extracts do not mix into its data.
+///
+/// Queries run on the snapshot by passing `&mut *snapshot` as executor.
+pub struct Snapshot(sqlx::Transaction<'static, sqlx::MySql>);
+
+impl Snapshot {
+    /// Start a `REPEATABLE READ, READ ONLY` transaction and take its
+    /// snapshot right away
+    pub async fn begin(pool: &sqlx::MySqlPool) -> Result<Self> {
+        let tx = pool
+            .begin_with(
+                "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY; \
+                 START TRANSACTION WITH CONSISTENT SNAPSHOT",
+            )
+            .await?;
+        Ok(Self(tx))
+    }
+
+    /// Take `count` snapshots of the same state of the database, so queries
+    /// can run concurrently and still see the same data. Writes are blocked
+    /// by a global read lock while the snapshots are taken, which needs the
+    /// `RELOAD` privilege and one connection besides the snapshots.
+    pub async fn begin_many(pool: &sqlx::MySqlPool, count: usize) -> Result<Vec<Self>> {
+        use sqlx::Executor;
+        if count <= 1 {
+            return Ok(vec![Self::begin(pool).await?]);
+        }
+        let mut lock = pool.acquire().await?;
+        lock.execute("FLUSH TABLES WITH READ LOCK").await?;
+        let snapshots = futures::future::try_join_all((0..count).map(|_| Self::begin(pool))).await;
+        lock.execute("UNLOCK TABLES").await?;
+        snapshots
+    }
+
+    /// End the transaction
+    pub async fn finish(self) -> Result<()> {
+        self.0.commit().await.map_err(Into::into)
+    }
+}
+
+impl std::ops::Deref for Snapshot {
+    type Target = sqlx::MySqlConnection;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Snapshot {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
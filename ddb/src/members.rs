@@ -1,37 +1,46 @@
 use crate::{Result, clubs, clubs::Club, users::User};
 use chrono::NaiveDate;
 use itertools::Itertools;
-use sqlx::{MySql, MySqlPool};
+use sqlx::{Executor, MySql};
 use std::{collections::HashMap, fmt};
 
-pub async fn all(pool: &MySqlPool) -> Result<Vec<Member>> {
+pub async fn all<'e, E>(executor: E) -> Result<Vec<Member>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let all = fetch_members_query()
         .push(" AND paragraphs_item_field_data.parent_field_name = 'field_home_club'")
         .build_query_as::<Member>()
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await?;
     Ok(dedupe_members(all))
 }
 
-pub async fn by_club(pool: &MySqlPool, uid: u64) -> Result<Vec<Member>> {
+pub async fn by_club<'e, E>(executor: E, uid: u64) -> Result<Vec<Member>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let all = fetch_club_members_query()
         .build_query_as::<Member>()
         .bind(Some(uid))
         .bind(Some(uid))
         .bind(None::<u64>)
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await?;
 
     Ok(dedupe_members(all))
 }
 
-pub async fn by_region(pool: &MySqlPool, uid: u64) -> Result<Vec<Member>> {
+pub async fn by_region<'e, E>(executor: E, uid: u64) -> Result<Vec<Member>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let all = fetch_club_members_query()
         .build_query_as::<Member>()
         .bind(None::<u64>)
         .bind(None::<u64>)
         .bind(Some(uid))
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await?;
 
     Ok(dedupe_members(all))
@@ -54,24 +63,30 @@ pub fn dedupe_members(members: Vec<Member>) -> Vec<Member> {
     member_map.into_values().collect()
 }
 
-pub async fn by_uid(pool: &MySqlPool, uid: u64) -> Result<Option<Member>> {
+pub async fn by_uid<'e, E>(executor: E, uid: u64) -> Result<Option<Member>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let member = fetch_members_query()
         .push("AND paragraphs_item_field_data.parent_field_name = 'field_home_club'")
         .push("AND users_field_data.uid = ")
         .push_bind(uid)
         .build_query_as::<Member>()
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
     Ok(member)
 }
 
-pub async fn by_email(pool: &MySqlPool, email: &str) -> Result<Option<Member>> {
+pub async fn by_email<'e, E>(executor: E, email: &str) -> Result<Option<Member>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let member = fetch_members_query()
         .push("AND users_field_data.mail = ")
         .push_bind(email)
         .build_query_as::<Member>()
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
     Ok(member)
@@ -99,12 +114,24 @@ pub struct MembershipPeriod {
     pub leave_date: Option<NaiveDate>,
 }
 
+impl MembershipPeriod {
+    /// Whether the period has started and has not been over for more than a
+    /// year, the same window [`all`] lists members in
+    pub fn is_current(&self, today: NaiveDate) -> bool {
+        let lapsed = today - chrono::Months::new(12);
+        self.join_date <= today && self.leave_date.is_none_or(|leave| leave >= lapsed)
+    }
+}
+
 /// Fetch all membership periods (full history, no date filtering)
 /// Unlike `all()` which returns current members only, this returns every
 /// membership paragraph for portal history sync
-pub async fn history_all(pool: &MySqlPool) -> Result<Vec<MembershipPeriod>> {
+pub async fn history_all<'e, E>(executor: E) -> Result<Vec<MembershipPeriod>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     sqlx::query_as::<_, MembershipPeriod>(FETCH_MEMBERSHIP_HISTORY_QUERY)
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await
         .map_err(Into::into)
 }
@@ -129,11 +156,14 @@ pub struct InternationalMembershipPeriod {
 }
 
 /// Fetch all international membership periods (full history, no date filtering)
-pub async fn international_history_all(
-    pool: &MySqlPool,
-) -> Result<Vec<InternationalMembershipPeriod>> {
+pub async fn international_history_all<'e, E>(
+    executor: E,
+) -> Result<Vec<InternationalMembershipPeriod>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     sqlx::query_as::<_, InternationalMembershipPeriod>(FETCH_INTERNATIONAL_MEMBERSHIP_HISTORY_QUERY)
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await
         .map_err(Into::into)
 }
@@ -269,9 +299,9 @@ const FETCH_ALL_MEMBERS_QUERY: &str = r#"
         NULL AS pass,
         NULL AS gender,
         NULL AS race_tid,
-        NULL AS communication_preference,
-        NULL AS blue_beret_mail,
-        NULL AS publish_info,
+        ufcp.field_communication_preferences_value AS communication_preference,
+        ufbb.field_blue_beret_mail_value AS blue_beret_mail,
+        ufpi.field_publish_info_value AS publish_info,
         NULL AS special_needs,
         NULL AS ada_parking,
         NULL AS member_notes,
@@ -324,6 +354,9 @@ const FETCH_ALL_MEMBERS_QUERY: &str = r#"
     		AND(paragraph__field_join_date.langcode = paragraphs_item_field_data.langcode
     			OR paragraph__field_join_date.bundle = 'membership')
     		INNER JOIN users_field_data users_field_data ON paragraphs_item_field_data.parent_id = users_field_data.uid
+    		LEFT JOIN user__field_communication_preferences ufcp ON ufcp.entity_id = users_field_data.uid AND ufcp.deleted = '0'
+    		LEFT JOIN user__field_blue_beret_mail ufbb ON ufbb.entity_id = users_field_data.uid AND ufbb.deleted = '0'
+    		LEFT JOIN user__field_publish_info ufpi ON ufpi.entity_id = users_field_data.uid AND ufpi.deleted = '0'
     		LEFT JOIN user__field_primary_member user_is_primary_member ON users_field_data.uid = user_is_primary_member.entity_id
     		INNER JOIN z_member_search_main alldata ON users_field_data.uid = alldata.user_id
     		INNER JOIN ssp_membership_international_membership rightmembership ON users_field_data.uid = rightmembership.user_id
@@ -398,7 +431,8 @@ WITH acp AS (
 flags AS (
   SELECT
     a.uid,
-    GREATEST(MAX(uhc.entity_id IS NOT NULL), MAX(uic.entity_id IS NOT NULL)) AS member_flag,
+    MAX(uhc.entity_id IS NOT NULL)                                          AS member_flag,
+    MAX(uic.entity_id IS NOT NULL)                                          AS intraclub_flag,
     MAX(uac.entity_id IS NOT NULL)                                          AS affiliate_flag,
     MAX(DATE(a.join_dt_raw))                                                AS latest_join_date,
     MAX(DATE(a.leave_dt_raw))                                               AS latest_expiration_date
@@ -440,9 +474,9 @@ SELECT
   NULL                                         AS pass,
   NULL                                         AS gender,
   NULL                                         AS race_tid,
-  NULL                                         AS communication_preference,
-  NULL                                         AS blue_beret_mail,
-  NULL                                         AS publish_info,
+  ufcp.field_communication_preferences_value   AS communication_preference,
+  ufbb.field_blue_beret_mail_value             AS blue_beret_mail,
+  ufpi.field_publish_info_value                AS publish_info,
   NULL                                         AS special_needs,
   NULL                                         AS ada_parking,
   NULL                                         AS member_notes,
@@ -453,6 +487,7 @@ SELECT
   /* ===================== MEMBER INFORMATION FIELDS ===================== */
   CASE
     WHEN flags.member_flag = 1 THEN 'regular'
+    WHEN flags.intraclub_flag = 1 THEN 'intraclub'
     WHEN flags.affiliate_flag = 1 THEN 'affiliate'
     ELSE NULL
   END                                          AS member_type,
@@ -486,6 +521,13 @@ JOIN z_member_search_main md
 LEFT JOIN users_field_data pu
   ON pu.uid = md.partner_user_id  /* get partner’s last_login */
 
+LEFT JOIN user__field_communication_preferences ufcp
+  ON ufcp.entity_id = u.uid AND ufcp.deleted = '0'
+LEFT JOIN user__field_blue_beret_mail ufbb
+  ON ufbb.entity_id = u.uid AND ufbb.deleted = '0'
+LEFT JOIN user__field_publish_info ufpi
+  ON ufpi.entity_id = u.uid AND ufpi.deleted = '0'
+
 LEFT JOIN user__field_primary_member pm_self
   ON pm_self.entity_id = u.uid
  AND pm_self.field_primary_member_target_id IS NOT NULL
@@ -516,7 +558,7 @@ LEFT JOIN v_brns brns
 WHERE
   md.personal_status_id IN ('947', '951', '1099')
   AND pm_self.entity_id IS NULL
-  AND (flags.member_flag = 1 OR flags.affiliate_flag = 1)
+  AND (flags.member_flag = 1 OR flags.intraclub_flag = 1 OR flags.affiliate_flag = 1)
 "#;
 
 fn fetch_club_members_query<'builder>() -> sqlx::QueryBuilder<'builder, MySql> {
@@ -525,20 +567,26 @@ fn fetch_club_members_query<'builder>() -> sqlx::QueryBuilder<'builder, MySql> {
 pub mod mailing_address {
     use super::*;
 
-    pub async fn by_uid(pool: &MySqlPool, uid: u64) -> Result<Option<Address>> {
+    pub async fn by_uid<'e, E>(executor: E, uid: u64) -> Result<Option<Address>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
         let member = fetch_mailing_address_query()
             .push("AND user__field_address.entity_id = ")
             .push_bind(uid)
             .build_query_as::<Address>()
-            .fetch_optional(pool)
+            .fetch_optional(executor)
             .await?;
         Ok(member)
     }
 
-    pub async fn by_uids<I: IntoIterator<Item = u64>>(
-        pool: &MySqlPool,
+    pub async fn by_uids<'e, E, I: IntoIterator<Item = u64>>(
+        executor: E,
         uids: I,
-    ) -> Result<HashMap<u64, Address>> {
+    ) -> Result<HashMap<u64, Address>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
         let mut builder = fetch_mailing_address_query();
         let mut seperated = builder
             .push("AND user__field_address.entity_id IN (")
@@ -549,7 +597,7 @@ pub mod mailing_address {
         seperated.push_unseparated(") ");
         let members: HashMap<u64, Address> = builder
             .build_query_as::<Address>()
-            .fetch_all(pool)
+            .fetch_all(executor)
             .await?
             .into_iter()
             .filter_map(|address| address.user_id.map(|user_id| (user_id, address)))
@@ -558,17 +606,27 @@ pub mod mailing_address {
     }
 
     /// Get addresses for given members primary user ids
-    pub async fn for_members(
-        pool: &MySqlPool,
+    pub async fn for_members<'e, E>(
+        executor: E,
         members: impl IntoIterator<Item = &Member>,
-    ) -> Result<HashMap<u64, Address>> {
-        by_uids(pool, members.into_iter().map(|member| member.primary.uid)).await
+    ) -> Result<HashMap<u64, Address>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        by_uids(
+            executor,
+            members.into_iter().map(|member| member.primary.uid),
+        )
+        .await
     }
 
-    pub async fn all(pool: &MySqlPool) -> Result<Vec<Address>> {
+    pub async fn all<'e, E>(executor: E) -> Result<Vec<Address>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
         let members = fetch_mailing_address_query()
             .build_query_as::<Address>()
-            .fetch_all(pool)
+            .fetch_all(executor)
             .await?;
         Ok(members)
     }
@@ -600,7 +658,7 @@ pub mod mailing_address {
     }
 }
 
-#[derive(Debug, serde::Serialize, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, serde::Serialize, Default, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum MemberClass {
     #[default]
@@ -619,6 +677,17 @@ impl fmt::Display for MemberClass {
     }
 }
 
+impl MemberClass {
+    /// The name of the class as shown to members
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Regular => "Regular",
+            Self::Lifetime => "Lifetime",
+            Self::Complimentary => "Complimentary",
+        }
+    }
+}
+
 impl TryFrom<String> for MemberClass {
     type Error = sqlx::Error;
     fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
@@ -676,7 +745,7 @@ impl TryFrom<i32> for MemberStatus {
     }
 }
 
-#[derive(Debug, serde::Serialize, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, serde::Serialize, Default, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum MemberType {
     #[default]
@@ -759,7 +828,8 @@ pub mod db {
     impl From<MemberType> for app_db::member::MemberType {
         fn from(value: MemberType) -> Self {
             match value {
-                MemberType::Regular | MemberType::Intraclub => Self::Regular,
+                MemberType::Regular => Self::Regular,
+                MemberType::Intraclub => Self::Intraclub,
                 MemberType::Affiliate => Self::Affiliate,
             }
         }
@@ -793,6 +863,56 @@ pub mod db {
         }
     }
 
+    impl From<MembershipPeriod> for app_db::membership_history::Period {
+        fn from(value: MembershipPeriod) -> Self {
+            Self {
+                paragraph_id: value.paragraph_id as i64,
+                user_uid: value.user_uid as i64,
+                partner_uid: value.partner_uid.map(|uid| uid as i64),
+                club: value.club_uid.map(|uid| uid as i64),
+                member_class: value.member_class.into(),
+                member_type: Some(value.member_type.into()),
+                join_date: value.join_date,
+                leave_date: value.leave_date,
+            }
+        }
+    }
+
+    impl MembershipPeriod {
+        /// The membership of the member in the club of the period, none for
+        /// periods without a club
+        pub fn to_db_club_membership(
+            &self,
+            member: &Member,
+        ) -> Option<app_db::club_membership::ClubMembership> {
+            Some(app_db::club_membership::ClubMembership {
+                user_id: app_db::user::id_for_email(&member.primary.email),
+                club: self.club_uid? as i64,
+                member_type: self.member_type.into(),
+                member_class: self.member_class.into(),
+                join_date: self.join_date,
+                expiration_date: self.leave_date,
+            })
+        }
+    }
+
+    impl InternationalMembershipPeriod {
+        /// The period without a club, none for orphaned or malformed
+        /// paragraphs without a user or join date
+        pub fn to_db_period(self) -> Option<app_db::membership_history::Period> {
+            Some(app_db::membership_history::Period {
+                paragraph_id: self.paragraph_id as i64,
+                user_uid: self.user_uid? as i64,
+                partner_uid: self.partner_uid.map(|uid| uid as i64),
+                club: None,
+                member_class: self.member_class.into(),
+                member_type: None,
+                join_date: self.join_date?,
+                leave_date: self.leave_date,
+            })
+        }
+    }
+
     impl Address {
         pub fn to_db_address_for_member(self, member: &Member) -> app_db::address::Address {
             app_db::address::Address {
@@ -850,7 +970,12 @@ pub mod mailchimp {
             to_update("affiliate", member, |m| {
                 m.member_type == MemberType::Affiliate
             }),
-            to_update("member", member, |m| m.member_type == MemberType::Regular),
+            to_update("member", member, |m| {
+                matches!(m.member_type, MemberType::Regular | MemberType::Intraclub)
+            }),
+            to_update("intraclub", member, |m| {
+                m.member_type == MemberType::Intraclub
+            }),
             to_update("lifetime", member, |m| {
                 m.member_class == MemberClass::Lifetime
             }),
@@ -863,13 +988,14 @@ pub mod mailchimp {
         members: &[Member],
         addresses: &HashMap<u64, Address>,
         merge_fields: &mc::merge_fields::MergeFields,
+        interests: &mc::interests::Interests,
     ) -> mc::Result<Vec<mc::members::Member>> {
         // Convert ddb members to mailchimp members while injecting address
         let result_vecs: Vec<Vec<mc::members::Member>> = members
             .iter()
             .map(|member| {
                 let address = addresses.get(&member.primary.uid);
-                to_members(member, &address.cloned(), merge_fields)
+                to_members(member, &address.cloned(), merge_fields, interests)
             })
             .try_collect()?;
 
@@ -880,12 +1006,13 @@ pub mod mailchimp {
         member: &Member,
         address: &Option<Address>,
         merge_fields: &mc::merge_fields::MergeFields,
+        interests: &mc::interests::Interests,
     ) -> mc::Result<Vec<mc::members::Member>> {
-        let primary = to_member(member, address, &member.primary, merge_fields)?;
+        let primary = to_member(member, address, &member.primary, merge_fields, interests)?;
 
         let mut result = Vec::with_capacity(2);
         if let Some(partner_user) = &member.partner {
-            let mut partner = to_member(member, address, partner_user, merge_fields)?;
+            let mut partner = to_member(member, address, partner_user, merge_fields, interests)?;
             if let Some(ref mut merge_fields) = partner.merge_fields {
                 merge_fields.insert("PRIMARY".into(), member.primary.email.clone().into());
             }
@@ -906,6 +1033,7 @@ pub mod mailchimp {
         address: &Option<Address>,
         user: &User,
         merge_fields: &mc::merge_fields::MergeFields,
+        interests: &mc::interests::Interests,
     ) -> mc::Result<mc::members::Member> {
         let user_fields: Vec<mc::merge_fields::MergeFieldValue> = [
             merge_fields.to_value("FNAME", user.first_name.as_ref()),
@@ -916,6 +1044,7 @@ pub mod mailchimp {
             merge_fields.to_value("JOIN", member.join_date),
             merge_fields.to_value("EXPIRE", member.expiration_date),
             merge_fields.to_value("BRN", member.brns.first()),
+            merge_fields.to_value("MCLASS", member.member_class.label()),
         ]
         .into_iter()
         .filter_map(|value| value.transpose())
@@ -926,11 +1055,52 @@ pub mod mailchimp {
             id: mc::members::member_id(&user.email),
             email_address: user.email.clone(),
             merge_fields: Some(user_fields.into_iter().collect()),
+            interests: to_interests(user, interests),
             status_if_new: Some(mc::members::MemberStatus::Subscribed),
             ..Default::default()
         })
     }
 
+    /// The allowed values of the Drupal `field_communication_preferences`
+    /// and the interest of the "Communication Preference" group each maps to
+    const COMMUNICATION_PREFERENCES: &[(&str, &str)] =
+        &[("email", "Email only"), ("print", "Email and print")];
+
+    /// Map the communication preferences of a user to Mailchimp groups.
+    /// Preferences the user never stated, or stated with a value we don't
+    /// know, are left untouched in Mailchimp.
+    fn to_interests(
+        user: &User,
+        interests: &mc::interests::Interests,
+    ) -> Option<HashMap<String, bool>> {
+        let mut values = vec![];
+        if let Some(preference) = user.communication_preference.as_deref() {
+            match COMMUNICATION_PREFERENCES
+                .iter()
+                .find(|(value, _)| *value == preference)
+            {
+                Some((_, selected)) => {
+                    for (_, name) in COMMUNICATION_PREFERENCES {
+                        values.push(interests.to_value(name, name == selected));
+                    }
+                }
+                None => tracing::warn!(
+                    uid = user.uid,
+                    preference,
+                    "unknown communication preference"
+                ),
+            }
+        }
+        if let Some(blue_beret_mail) = user.blue_beret_mail {
+            values.push(interests.to_value("Blue Beret print", blue_beret_mail));
+        }
+        if let Some(publish_info) = user.publish_info {
+            values.push(interests.to_value("Publish contact info", publish_info));
+        }
+        let values: HashMap<String, bool> = values.into_iter().flatten().collect();
+        (!values.is_empty()).then_some(values)
+    }
+
     fn address_to_values(
         address: &Option<Address>,
         merge_fields: &mc::merge_fields::MergeFields,
@@ -940,6 +1110,7 @@ pub mod mailchimp {
         };
 
         vec![
+            merge_fields.to_value("ADDRESS", to_merge_address(address)),
             merge_fields.to_value("ZIP", address.zip_code.as_ref()),
             merge_fields.to_value("STATE", address.state.as_ref()),
             merge_fields.to_value("COUNTRY", address.country.as_ref()),
@@ -949,6 +1120,19 @@ pub mod mailchimp {
         .collect()
     }
 
+    /// The full mailing address, for an `address` merge field
+    fn to_merge_address(address: &Address) -> mc::merge_fields::Address {
+        let part = |value: &Option<String>| value.as_deref().unwrap_or_default().trim().to_string();
+        mc::merge_fields::Address {
+            addr1: part(&address.street_address),
+            addr2: part(&address.street_address_2),
+            city: part(&address.city),
+            state: part(&address.state),
+            zip: part(&address.zip_code),
+            country: part(&address.country),
+        }
+    }
+
     fn club_to_values(
         club: &clubs::Club,
         merge_fields: &mc::merge_fields::MergeFields,
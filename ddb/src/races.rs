@@ -1,6 +1,6 @@
 use crate::{Error, Result};
 use futures::TryFutureExt;
-use sqlx::MySqlPool;
+use sqlx::{Executor, MySql};
 
 /// Race taxonomy term from Drupal (vocabulary: ssp_race)
 #[derive(Debug, sqlx::FromRow, serde::Serialize)]
@@ -11,7 +11,10 @@ pub struct Race {
 }
 
 /// Fetch all race taxonomy terms from Drupal
-pub async fn all(pool: &MySqlPool) -> Result<Vec<Race>> {
+pub async fn all<'e, E>(executor: E) -> Result<Vec<Race>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     sqlx::query_as::<_, Race>(
         r#"
         SELECT tid AS uid, name
@@ -20,7 +23,7 @@ pub async fn all(pool: &MySqlPool) -> Result<Vec<Race>> {
         ORDER BY tid
         "#,
     )
-    .fetch_all(pool)
+    .fetch_all(executor)
     .map_err(Error::from)
     .await
 }
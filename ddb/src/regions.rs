@@ -1,31 +1,40 @@
 use crate::{Error, Result};
 use futures::TryFutureExt;
-use sqlx::{MySql, MySqlPool};
+use sqlx::{Executor, MySql};
 
-pub async fn all(pool: &MySqlPool) -> Result<Vec<Region>> {
+pub async fn all<'e, E>(executor: E) -> Result<Vec<Region>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     sqlx::query_as::<_, Region>(FETCH_REGIONS_QUERY)
-        .fetch_all(pool)
+        .fetch_all(executor)
         .map_err(Error::from)
         .await
 }
 
-pub async fn by_uid(pool: &MySqlPool, uid: u64) -> Result<Option<Region>> {
+pub async fn by_uid<'e, E>(executor: E, uid: u64) -> Result<Option<Region>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let region = fetch_regions_query()
         .push("where region.entity_id = ")
         .push_bind(uid)
         .build_query_as::<Region>()
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
     Ok(region)
 }
 
-pub async fn by_number(pool: &MySqlPool, number: i32) -> Result<Option<Region>> {
+pub async fn by_number<'e, E>(executor: E, number: i32) -> Result<Option<Region>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let region = fetch_regions_query()
         .push("where region.field_region_number_value = ")
         .push_bind(number)
         .build_query_as::<Region>()
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
     Ok(region)
@@ -2,7 +2,7 @@
 
 use crate::{Error, Result};
 use futures::TryFutureExt;
-use sqlx::MySqlPool;
+use sqlx::{Executor, MySql};
 
 /// User role assignment from Drupal's user__roles table
 #[derive(Debug, sqlx::FromRow, serde::Serialize)]
@@ -22,8 +22,35 @@ pub struct MicrositeAdmin {
     pub is_region: bool,
 }
 
+pub mod db {
+    use super::*;
+    use ::db as app_db;
+
+    impl From<UserRole> for app_db::user_role::UserRole {
+        fn from(value: UserRole) -> Self {
+            Self {
+                user_uid: value.user_uid as i64,
+                role: value.role,
+            }
+        }
+    }
+
+    impl From<MicrositeAdmin> for app_db::entity_admin::EntityAdmin {
+        fn from(value: MicrositeAdmin) -> Self {
+            Self {
+                user_uid: value.user_uid as i64,
+                entity_uid: value.entity_uid as i64,
+                is_region: value.is_region,
+            }
+        }
+    }
+}
+
 /// Fetch all user role assignments from Drupal
-pub async fn all(pool: &MySqlPool) -> Result<Vec<UserRole>> {
+pub async fn all<'e, E>(executor: E) -> Result<Vec<UserRole>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     sqlx::query_as::<_, UserRole>(
         r#"
         SELECT entity_id AS user_uid, roles_target_id AS role
@@ -31,7 +58,7 @@ pub async fn all(pool: &MySqlPool) -> Result<Vec<UserRole>> {
         WHERE deleted = 0
         "#,
     )
-    .fetch_all(pool)
+    .fetch_all(executor)
     .map_err(Error::from)
     .await
 }
@@ -40,7 +67,10 @@ pub async fn all(pool: &MySqlPool) -> Result<Vec<UserRole>> {
 ///
 /// This query joins via `field_main_site_club` which links ssp_club/ssp_region
 /// nodes to their corresponding microsite_homepage (no title matching needed).
-pub async fn microsite_admins(pool: &MySqlPool) -> Result<Vec<MicrositeAdmin>> {
+pub async fn microsite_admins<'e, E>(executor: E) -> Result<Vec<MicrositeAdmin>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     sqlx::query_as::<_, MicrositeAdmin>(
         r#"
         SELECT
@@ -60,7 +90,7 @@ pub async fn microsite_admins(pool: &MySqlPool) -> Result<Vec<MicrositeAdmin>> {
           AND (club_link.entity_id IS NOT NULL OR region_link.entity_id IS NOT NULL)
         "#,
     )
-    .fetch_all(pool)
+    .fetch_all(executor)
     .map_err(Error::from)
     .await
 }
@@ -1,20 +1,26 @@
 use crate::{Error, Result};
 use futures::TryFutureExt;
-use sqlx::{MySql, MySqlPool};
+use sqlx::{Executor, MySql};
 
-pub async fn all(pool: &MySqlPool) -> Result<Vec<StandingCommittee>> {
+pub async fn all<'e, E>(executor: E) -> Result<Vec<StandingCommittee>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     sqlx::query_as::<_, StandingCommittee>(FETCH_STANDING_COMMITTEES_QUERY)
-        .fetch_all(pool)
+        .fetch_all(executor)
         .map_err(Error::from)
         .await
 }
 
-pub async fn by_uid(pool: &MySqlPool, uid: u64) -> Result<Option<StandingCommittee>> {
+pub async fn by_uid<'e, E>(executor: E, uid: u64) -> Result<Option<StandingCommittee>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     fetch_standing_committees_query()
         .push(" WHERE nd.nid = ")
         .push_bind(uid)
         .build_query_as::<StandingCommittee>()
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .map_err(Error::from)
         .await
 }
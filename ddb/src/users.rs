@@ -1,5 +1,5 @@
 use crate::Result;
-use sqlx::{MySqlPool, mysql::MySql};
+use sqlx::{Executor, mysql::MySql};
 
 /// Drupal user data.
 ///
@@ -96,35 +96,44 @@ fn fetch_user_query<'builder>() -> sqlx::QueryBuilder<'builder, MySql> {
     )
 }
 
-pub async fn by_uid(pool: &MySqlPool, uid: u64) -> Result<Option<User>> {
+pub async fn by_uid<'e, E>(executor: E, uid: u64) -> Result<Option<User>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let user = fetch_user_query()
         .push("users_field_data.uid = ")
         .push_bind(uid)
         .build_query_as::<User>()
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
     Ok(user)
 }
 
-pub async fn by_email(pool: &MySqlPool, email: &str) -> Result<Option<User>> {
+pub async fn by_email<'e, E>(executor: E, email: &str) -> Result<Option<User>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let user = fetch_user_query()
         .push("users_field_data.mail = ")
         .push_bind(email)
         .build_query_as::<User>()
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
     Ok(user)
 }
 
 /// Fetch all users with valid email addresses
-pub async fn all(pool: &MySqlPool) -> Result<Vec<User>> {
+pub async fn all<'e, E>(executor: E) -> Result<Vec<User>>
+where
+    E: Executor<'e, Database = MySql>,
+{
     use futures::TryFutureExt;
     fetch_user_query()
         .push("users_field_data.mail != ''")
         .build_query_as::<User>()
-        .fetch_all(pool)
+        .fetch_all(executor)
         .map_err(Into::into)
         .await
 }
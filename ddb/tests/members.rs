@@ -0,0 +1,147 @@
+use aci_ddb::{
+    clubs::Club,
+    members::{
+        Member, MemberClass, MemberStatus, MemberType, MembershipPeriod, mailchimp::to_members,
+    },
+    users::User,
+};
+use chrono::NaiveDate;
+use mailchimp::{interests::Interests, merge_fields::MergeFields};
+use std::collections::HashMap;
+
+fn user(uid: u64, email: &str) -> User {
+    User {
+        uid,
+        email: email.to_string(),
+        first_name: Some("Wally".to_string()),
+        last_name: Some("Byam".to_string()),
+        birthday: None,
+        last_login: None,
+        pass: None,
+        gender: None,
+        race_tid: None,
+        communication_preference: None,
+        blue_beret_mail: None,
+        publish_info: None,
+        special_needs: None,
+        ada_parking: None,
+        member_notes: None,
+        military_status: None,
+        first_responder_status: None,
+        active: true,
+    }
+}
+
+fn member(primary: User) -> Member {
+    Member {
+        member_class: MemberClass::Regular,
+        member_type: MemberType::Regular,
+        member_status: MemberStatus::Current,
+        primary,
+        partner: None,
+        expiration_date: None,
+        join_date: None,
+        local_club: Club {
+            uid: 7,
+            number: Some(42),
+            name: "Silver Bullets".to_string(),
+            region: Some(3),
+            active: true,
+        },
+        brns: vec![],
+    }
+}
+
+/// The interests a member with the given communication preference is put in
+fn interests_for(preference: Option<&str>) -> Option<HashMap<String, bool>> {
+    let interests = Interests::from(HashMap::from([
+        ("Email only".to_string(), "email-only".to_string()),
+        ("Email and print".to_string(), "email-print".to_string()),
+    ]));
+    let mut user = user(1, "wally@airstream.test");
+    user.communication_preference = preference.map(str::to_string);
+    let members = to_members(
+        &member(user),
+        &None,
+        &MergeFields::club().unwrap(),
+        &interests,
+    )
+    .unwrap();
+    members.into_iter().next().unwrap().interests
+}
+
+#[test]
+fn communication_preferences_map_to_interests() {
+    assert_eq!(
+        interests_for(Some("email")),
+        Some(HashMap::from([
+            ("email-only".to_string(), true),
+            ("email-print".to_string(), false),
+        ]))
+    );
+    assert_eq!(
+        interests_for(Some("print")),
+        Some(HashMap::from([
+            ("email-only".to_string(), false),
+            ("email-print".to_string(), true),
+        ]))
+    );
+}
+
+#[test]
+fn unknown_communication_preferences_are_left_untouched() {
+    assert_eq!(interests_for(None), None);
+    assert_eq!(interests_for(Some("Email")), None);
+    assert_eq!(interests_for(Some("carrier pigeon")), None);
+}
+
+fn date(date: &str) -> NaiveDate {
+    date.parse().unwrap()
+}
+
+fn period(club_uid: Option<u64>, join_date: &str, leave_date: Option<&str>) -> MembershipPeriod {
+    MembershipPeriod {
+        paragraph_id: 10,
+        user_uid: 1,
+        partner_uid: None,
+        club_uid,
+        member_class: MemberClass::Lifetime,
+        member_type: MemberType::Intraclub,
+        join_date: date(join_date),
+        leave_date: leave_date.map(date),
+    }
+}
+
+#[test]
+fn periods_are_current_until_a_year_after_they_end() {
+    let today = date("2026-10-18");
+    assert!(period(Some(7), "2020-01-01", None).is_current(today));
+    assert!(period(Some(7), "2026-10-18", None).is_current(today));
+    assert!(!period(Some(7), "2026-10-19", None).is_current(today));
+    assert!(period(Some(7), "2020-01-01", Some("2025-10-18")).is_current(today));
+    assert!(!period(Some(7), "2020-01-01", Some("2025-10-17")).is_current(today));
+}
+
+#[test]
+fn periods_map_to_club_memberships_of_the_member() {
+    let member = member(user(1, "Wally@Airstream.test"));
+    let membership = period(Some(7), "2020-01-01", Some("2025-12-31"))
+        .to_db_club_membership(&member)
+        .unwrap();
+    assert_eq!(
+        membership.user_id,
+        db::user::id_for_email("wally@airstream.test")
+    );
+    assert_eq!(membership.club, 7);
+    assert_eq!(membership.member_type, db::member::MemberType::Intraclub);
+    assert_eq!(membership.member_class, db::member::MemberClass::Lifetime);
+    assert_eq!(membership.join_date, date("2020-01-01"));
+    assert_eq!(membership.expiration_date, Some(date("2025-12-31")));
+
+    // International periods have no club
+    assert!(
+        period(None, "2020-01-01", None)
+            .to_db_club_membership(&member)
+            .is_none()
+    );
+}
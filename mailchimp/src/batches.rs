@@ -1,8 +1,15 @@
 use crate::{
-    Client, NO_QUERY, Result, Stream, paged_query_impl, paged_response_impl, query_default_impl,
+    Client, Error, NO_QUERY, Result, RetryPolicy, Stream, error::MailchimError, paged_query_impl,
+    paged_response_impl, query_default_impl,
 };
 use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryFutureExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use std::io::Read;
+use tokio_retry2::Retry;
+
+/// The number of operations submitted per batch
+pub const OPERATIONS_PER_BATCH: usize = 1000;
 
 pub async fn all(client: &Client, query: BatchesQuery) -> Stream<BatchInfo> {
     client.fetch_stream::<BatchesQuery, BatchesResponse>("/3.0/batches", query)
@@ -12,6 +19,35 @@ pub async fn for_id(client: &Client, id: &str) -> Result<BatchInfo> {
     client.fetch(&format!("/3.0/batches/{id}",), NO_QUERY).await
 }
 
+/// Download the results archive of a finished batch and split the operation
+/// results into succeeded and failed ones
+pub async fn results(client: &Client, info: &BatchInfo) -> Result<BatchOutcome> {
+    if info.response_body_url.is_empty() {
+        return Ok(BatchOutcome::default());
+    }
+    let archive = client.download(&info.response_body_url).await?;
+    let results = parse_results(&archive)?;
+    Ok(results.into_iter().collect())
+}
+
+/// Parse a gzipped results archive. Every JSON file in the archive holds an
+/// array of operation results.
+fn parse_results(archive: &[u8]) -> Result<Vec<BatchOperationResult>> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    let mut results = vec![];
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let mut contents = vec![];
+        entry.read_to_end(&mut contents)?;
+        let mut file: Vec<BatchOperationResult> = serde_json::from_slice(&contents)?;
+        results.append(&mut file);
+    }
+    Ok(results)
+}
+
 #[derive(Serialize, Debug, Default)]
 pub struct Batch {
     operations: Vec<BatchOperation>,
@@ -26,12 +62,12 @@ impl Batch {
             .push(BatchOperation::new(BatchMethod::POST, path, json)?);
         Ok(self.operations.last_mut().unwrap())
     }
-    pub fn patch<'a, T>(&'a mut self, path: &str, json: &T) -> Result<&'a BatchOperation>
+    pub fn patch<'a, T>(&'a mut self, path: &str, json: &T) -> Result<&'a mut BatchOperation>
     where
         T: Serialize + ?Sized,
     {
         self.operations
-            .push(BatchOperation::new(BatchMethod::POST, path, json)?);
+            .push(BatchOperation::new(BatchMethod::PATCH, path, json)?);
         Ok(self.operations.last_mut().unwrap())
     }
 
@@ -53,6 +89,14 @@ impl Batch {
         Ok(self.operations.last_mut().unwrap())
     }
 
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
     pub async fn run(&self, client: &Client, to_completion: bool) -> Result<BatchInfo> {
         let mut info: BatchInfo = client.post("/3.0/batches", self).await?;
         while to_completion && info.status != BatchStatus::Finished {
@@ -61,6 +105,42 @@ impl Batch {
         }
         Ok(info)
     }
+
+    /// Run the batch to completion and fetch the result of every operation
+    pub async fn run_with_outcome(&self, client: &Client) -> Result<BatchOutcome> {
+        let info = self.run(client, true).await?;
+        results(client, &info).await
+    }
+}
+
+/// Run batches to completion, up to 10 at a time, and return the results of
+/// the operations that failed. Submitting a batch and downloading its results
+/// are retried according to the given policy.
+pub async fn run_many(
+    client: &Client,
+    batches: Vec<Batch>,
+    retries: RetryPolicy,
+) -> Result<Vec<BatchOperationResult>> {
+    futures::stream::iter(batches)
+        .map(Ok::<_, Error>)
+        .map_ok(|batch| async move {
+            let info = Retry::spawn_notify(
+                retries,
+                || batch.run(client, true).map_err(Error::into_retry),
+                |err, sleep| tracing::warn!(%err, sleep = sleep.as_secs(), "batch"),
+            )
+            .await?;
+            let outcome = Retry::spawn_notify(
+                retries,
+                || results(client, &info).map_err(Error::into_retry),
+                |err, sleep| tracing::warn!(%err, sleep = sleep.as_secs(), "batch results"),
+            )
+            .await?;
+            Ok::<_, Error>(outcome.failed)
+        })
+        .try_buffer_unordered(10)
+        .try_concat()
+        .await
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -96,13 +176,75 @@ pub enum BatchMethod {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BatchInfo {
     pub id: String,
-    status: BatchStatus,
-    total_operations: u16,
-    finished_operations: u16,
-    errored_operations: u16,
-    submitted_at: DateTime<Utc>,
-    completed_at: String,
-    response_body_url: String,
+    pub status: BatchStatus,
+    pub total_operations: u16,
+    pub finished_operations: u16,
+    pub errored_operations: u16,
+    pub submitted_at: DateTime<Utc>,
+    pub completed_at: String,
+    pub response_body_url: String,
+}
+
+/// The result of a single operation of a finished batch
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchOperationResult {
+    pub status_code: u16,
+    pub operation_id: String,
+    /// The raw response body of the operation
+    pub response: String,
+}
+
+impl BatchOperationResult {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status_code)
+    }
+
+    /// The error returned by Mailchimp for a failed operation
+    pub fn error(&self) -> Option<MailchimError> {
+        if self.is_success() {
+            return None;
+        }
+        let error = serde_json::from_str(&self.response).unwrap_or_else(|_| MailchimError {
+            status: self.status_code,
+            r#type: None,
+            title: String::new(),
+            detail: self.response.clone(),
+            instance: String::new(),
+        });
+        Some(error)
+    }
+}
+
+/// The operation results of a finished batch, split by outcome
+#[derive(Serialize, Debug, Default)]
+pub struct BatchOutcome {
+    pub succeeded: Vec<BatchOperationResult>,
+    pub failed: Vec<BatchOperationResult>,
+}
+
+impl BatchOutcome {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl FromIterator<BatchOperationResult> for BatchOutcome {
+    fn from_iter<I: IntoIterator<Item = BatchOperationResult>>(iter: I) -> Self {
+        let (succeeded, failed) = iter.into_iter().partition(BatchOperationResult::is_success);
+        Self { succeeded, failed }
+    }
+}
+
+impl Extend<BatchOperationResult> for BatchOutcome {
+    fn extend<I: IntoIterator<Item = BatchOperationResult>>(&mut self, iter: I) {
+        for result in iter {
+            if result.is_success() {
+                self.succeeded.push(result);
+            } else {
+                self.failed.push(result);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -0,0 +1,349 @@
+use crate::{
+    Client, NO_QUERY, Result, Stream, deserialize_null_string, error::Error, paged_query_impl,
+    paged_response_impl,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// All campaigns of the account. Use the query's `list_id` and `status` to
+/// restrict the results to a single audience or state.
+pub fn all(client: &Client, query: CampaignsQuery) -> Stream<Campaign> {
+    client.fetch_stream::<CampaignsQuery, CampaignsResponse>("/3.0/campaigns", query)
+}
+
+/// Get a single campaign, including its current status
+pub async fn get(client: &Client, campaign_id: &str) -> Result<Campaign> {
+    client
+        .fetch(&format!("/3.0/campaigns/{campaign_id}"), NO_QUERY)
+        .await
+}
+
+/// Create a regular email campaign for the recipients and settings given
+pub async fn create(client: &Client, campaign: &NewCampaign) -> Result<Campaign> {
+    client.post("/3.0/campaigns", campaign).await
+}
+
+/// Update the recipients or settings of a campaign that hasn't been sent
+pub async fn update(
+    client: &Client,
+    campaign_id: &str,
+    campaign: &NewCampaign,
+) -> Result<Campaign> {
+    client
+        .patch(&format!("/3.0/campaigns/{campaign_id}"), campaign)
+        .await
+}
+
+pub async fn delete(client: &Client, campaign_id: &str) -> Result<()> {
+    client
+        .delete(&format!("/3.0/campaigns/{campaign_id}"))
+        .await
+}
+
+pub async fn content(client: &Client, campaign_id: &str) -> Result<CampaignContent> {
+    client
+        .fetch(&format!("/3.0/campaigns/{campaign_id}/content"), NO_QUERY)
+        .await
+}
+
+/// Replace the content of a campaign, either with raw HTML or with a saved
+/// template and the content of its editable sections
+pub async fn set_content(
+    client: &Client,
+    campaign_id: &str,
+    content: &Content,
+) -> Result<CampaignContent> {
+    client
+        .put(&format!("/3.0/campaigns/{campaign_id}/content"), content)
+        .await
+}
+
+/// Send a test email of a campaign to the given addresses. Test emails
+/// don't change the status of the campaign.
+pub async fn send_test(client: &Client, campaign_id: &str, emails: &[String]) -> Result<()> {
+    #[derive(Serialize)]
+    struct TestEmail<'a> {
+        test_emails: &'a [String],
+        send_type: &'static str,
+    }
+    if emails.is_empty() {
+        return Err(Error::value("no test email addresses".into()));
+    }
+    client
+        .post(
+            &format!("/3.0/campaigns/{campaign_id}/actions/test"),
+            &TestEmail {
+                test_emails: emails,
+                send_type: "html",
+            },
+        )
+        .await
+}
+
+/// Schedule a campaign for delivery. Mailchimp only accepts times on the
+/// quarter hour.
+pub async fn schedule(client: &Client, campaign_id: &str, time: DateTime<Utc>) -> Result<()> {
+    #[derive(Serialize)]
+    struct Schedule {
+        schedule_time: DateTime<Utc>,
+    }
+    client
+        .post(
+            &format!("/3.0/campaigns/{campaign_id}/actions/schedule"),
+            &Schedule {
+                schedule_time: time,
+            },
+        )
+        .await
+}
+
+/// Return a scheduled campaign to draft
+pub async fn unschedule(client: &Client, campaign_id: &str) -> Result<()> {
+    client
+        .post(
+            &format!("/3.0/campaigns/{campaign_id}/actions/unschedule"),
+            &serde_json::json!({}),
+        )
+        .await
+}
+
+/// Send a campaign right away
+pub async fn send(client: &Client, campaign_id: &str) -> Result<()> {
+    client
+        .post(
+            &format!("/3.0/campaigns/{campaign_id}/actions/send"),
+            &serde_json::json!({}),
+        )
+        .await
+}
+
+/// Review whether a campaign is ready to send
+pub async fn send_checklist(client: &Client, campaign_id: &str) -> Result<SendChecklist> {
+    client
+        .fetch(
+            &format!("/3.0/campaigns/{campaign_id}/send-checklist"),
+            NO_QUERY,
+        )
+        .await
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CampaignType {
+    #[default]
+    Regular,
+    Plaintext,
+    Rss,
+    Variate,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CampaignStatus {
+    Save,
+    Paused,
+    Schedule,
+    Sending,
+    Sent,
+    Canceled,
+    Canceling,
+    Archived,
+}
+
+impl std::str::FromStr for CampaignStatus {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(&format!("\"{s}\"")).map_err(|_| Error::value(s.into()))
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct SegmentOpts {
+    pub saved_segment_id: u64,
+}
+
+/// The audience, and optionally the segment of it, a campaign is sent to
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Recipients {
+    pub list_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment_opts: Option<SegmentOpts>,
+    #[serde(default, skip_serializing)]
+    pub recipient_count: u64,
+}
+
+impl Recipients {
+    /// All subscribed members of a list
+    pub fn list(list_id: &str) -> Self {
+        Self {
+            list_id: list_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The subscribed members of a list matching a saved or static segment
+    pub fn segment(list_id: &str, segment_id: u64) -> Self {
+        Self {
+            list_id: list_id.to_string(),
+            segment_opts: Some(SegmentOpts {
+                saved_segment_id: segment_id,
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct CampaignSettings {
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub subject_line: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub preview_text: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub title: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub from_name: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub reply_to: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Campaign {
+    pub id: String,
+    #[serde(default)]
+    pub web_id: u64,
+    #[serde(default)]
+    pub r#type: CampaignType,
+    pub status: CampaignStatus,
+    #[serde(default)]
+    pub emails_sent: u64,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub create_time: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub send_time: String,
+    #[serde(default)]
+    pub recipients: Recipients,
+    #[serde(default)]
+    pub settings: CampaignSettings,
+}
+
+/// The request body to create or update a campaign
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct NewCampaign {
+    pub r#type: CampaignType,
+    pub recipients: Recipients,
+    pub settings: CampaignSettings,
+}
+
+/// A saved template and the content of its `mc:edit` sections
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct TemplateContent {
+    pub id: u64,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub sections: HashMap<String, String>,
+}
+
+/// The content of a campaign, as set by [`set_content`]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum Content {
+    Html(String),
+    Template(TemplateContent),
+}
+
+/// The content of a campaign as rendered by Mailchimp
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct CampaignContent {
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub html: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub plain_text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SendChecklist {
+    pub is_ready: bool,
+    #[serde(default)]
+    pub items: Vec<SendChecklistItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SendChecklistItem {
+    #[serde(default)]
+    pub r#type: String,
+    #[serde(default)]
+    pub heading: String,
+    #[serde(default)]
+    pub details: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CampaignsQuery {
+    pub fields: String,
+    pub count: usize,
+    pub offset: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<CampaignStatus>,
+}
+
+impl Default for CampaignsQuery {
+    fn default() -> Self {
+        use crate::PagedQuery;
+        Self {
+            fields: Self::default_fields().join(","),
+            count: crate::DEFAULT_QUERY_COUNT,
+            offset: 0,
+            list_id: None,
+            status: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CampaignsResponse {
+    pub campaigns: Vec<Campaign>,
+}
+
+paged_query_impl!(CampaignsQuery, &[]);
+paged_response_impl!(CampaignsResponse, campaigns, Campaign);
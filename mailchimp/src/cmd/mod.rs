@@ -5,13 +5,21 @@ use anyhow::Context;
 pub fn client_from_env() -> Result<mailchimp::Client> {
     let api_key = std::env::var("MAILCHIMP_API_KEY")
         .context("MAILCHIMP_API_KEY environment variable not set")?;
-    Ok(mailchimp::client::from_api_key(&api_key)?)
+    // Allow pointing the CLI at a non-default API endpoint, like a local
+    // stand-in server
+    match std::env::var("MAILCHIMP_ENDPOINT") {
+        Ok(endpoint) => Ok(mailchimp::client::from_api_key_with_endpoint(
+            &api_key, &endpoint,
+        )?),
+        Err(_) => Ok(mailchimp::client::from_api_key(&api_key)?),
+    }
 }
 
 pub mod lists;
 pub mod members;
 pub mod merge_fields;
 pub mod ping;
+pub mod segments;
 
 pub fn print_json<T: ?Sized + serde::Serialize>(value: &T) -> Result<()> {
     println!("{}", serde_json::to_string_pretty(value)?);
@@ -36,6 +44,7 @@ pub enum MailchimpCommand {
     Members(members::Cmd),
     MergeFields(merge_fields::Cmd),
     Ping(ping::Cmd),
+    Segments(segments::Cmd),
 }
 
 impl MailchimpCommand {
@@ -45,6 +54,7 @@ impl MailchimpCommand {
             Self::Members(cmd) => cmd.run().await,
             Self::MergeFields(cmd) => cmd.run().await,
             Self::Ping(cmd) => cmd.run().await,
+            Self::Segments(cmd) => cmd.run().await,
         }
     }
 }
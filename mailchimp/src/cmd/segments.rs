@@ -0,0 +1,248 @@
+use super::{Result, client_from_env, print_json};
+use futures::TryStreamExt;
+use mailchimp::segments::{
+    NewSegment, SegmentCondition, SegmentMatch, SegmentOptions, SegmentType, SegmentsQuery,
+};
+
+/// Commands on the static and saved segments of an audience list.
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    #[command(subcommand)]
+    cmd: SegmentsCommand,
+}
+
+impl Cmd {
+    pub async fn run(&self) -> Result<()> {
+        self.cmd.run().await
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum SegmentsCommand {
+    List(List),
+    Create(Create),
+    Update(Update),
+    Delete(Delete),
+    Members(Members),
+    Add(Add),
+    Remove(Remove),
+}
+
+impl SegmentsCommand {
+    pub async fn run(&self) -> Result<()> {
+        match self {
+            Self::List(cmd) => cmd.run().await,
+            Self::Create(cmd) => cmd.run().await,
+            Self::Update(cmd) => cmd.run().await,
+            Self::Delete(cmd) => cmd.run().await,
+            Self::Members(cmd) => cmd.run().await,
+            Self::Add(cmd) => cmd.run().await,
+            Self::Remove(cmd) => cmd.run().await,
+        }
+    }
+}
+
+/// Parse a `TAG=VALUE` merge field condition
+fn parse_condition(s: &str) -> std::result::Result<SegmentCondition, String> {
+    let (tag, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected TAG=VALUE, got {s}"))?;
+    Ok(SegmentCondition::merge_field_is(tag, value))
+}
+
+fn to_options(conditions: &[SegmentCondition], r#match: SegmentMatch) -> Option<SegmentOptions> {
+    if conditions.is_empty() {
+        return None;
+    }
+    Some(SegmentOptions {
+        r#match,
+        conditions: conditions.to_vec(),
+    })
+}
+
+/// List one or all the segments of a given audience list.
+#[derive(Debug, clap::Args)]
+pub struct List {
+    /// The list ID to get segments for.
+    list: String,
+    /// The ID of a specific segment to get
+    #[arg(long)]
+    id: Option<u64>,
+    /// Only list segments of the given type (saved, static or fuzzy)
+    #[arg(long)]
+    r#type: Option<SegmentType>,
+}
+
+impl List {
+    pub async fn run(&self) -> Result<()> {
+        let client = client_from_env()?;
+        if let Some(segment_id) = self.id {
+            let segment = mailchimp::segments::get(&client, &self.list, segment_id).await?;
+            print_json(&segment)
+        } else {
+            let query = SegmentsQuery {
+                r#type: self.r#type,
+                ..Default::default()
+            };
+            let segments = mailchimp::segments::all(&client, &self.list, query)
+                .try_collect::<Vec<_>>()
+                .await?;
+            print_json(&segments)
+        }
+    }
+}
+
+/// Create a segment for a given audience list.
+///
+/// Passing `--condition` creates a saved segment matching contacts by merge
+/// field, otherwise a static segment with the given `--email` addresses is
+/// created.
+#[derive(Debug, clap::Args)]
+pub struct Create {
+    /// The audience list ID.
+    list: String,
+    /// The name of the segment.
+    name: String,
+    /// Email addresses of the members of a static segment.
+    #[arg(long)]
+    email: Vec<String>,
+    /// A merge field condition of a saved segment, as `TAG=VALUE`.
+    #[arg(long, value_parser = parse_condition)]
+    condition: Vec<SegmentCondition>,
+    /// Whether contacts need to match all or any of the conditions.
+    #[arg(long, default_value = "all")]
+    r#match: SegmentMatch,
+}
+
+impl Create {
+    pub async fn run(&self) -> Result<()> {
+        let client = client_from_env()?;
+        let options = to_options(&self.condition, self.r#match);
+        let segment = NewSegment {
+            name: self.name.clone(),
+            static_segment: options.is_none().then(|| self.email.clone()),
+            options,
+        };
+        let segment = mailchimp::segments::create(&client, &self.list, &segment).await?;
+        print_json(&segment)
+    }
+}
+
+/// Update the name or the conditions of a segment.
+#[derive(Debug, clap::Args)]
+pub struct Update {
+    /// The audience list ID.
+    list: String,
+    /// The segment ID.
+    id: u64,
+    /// The new name of the segment.
+    #[arg(long)]
+    name: Option<String>,
+    /// The merge field conditions replacing the current ones, as `TAG=VALUE`.
+    #[arg(long, value_parser = parse_condition)]
+    condition: Vec<SegmentCondition>,
+    /// Whether contacts need to match all or any of the conditions.
+    #[arg(long, default_value = "all")]
+    r#match: SegmentMatch,
+}
+
+impl Update {
+    pub async fn run(&self) -> Result<()> {
+        let client = client_from_env()?;
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => {
+                mailchimp::segments::get(&client, &self.list, self.id)
+                    .await?
+                    .name
+            }
+        };
+        let segment = NewSegment {
+            name,
+            options: to_options(&self.condition, self.r#match),
+            ..Default::default()
+        };
+        let segment = mailchimp::segments::update(&client, &self.list, self.id, &segment).await?;
+        print_json(&segment)
+    }
+}
+
+/// Delete a segment from an audience list.
+#[derive(Debug, clap::Args)]
+pub struct Delete {
+    /// The audience list ID.
+    list: String,
+    /// The segment ID.
+    id: u64,
+}
+
+impl Delete {
+    pub async fn run(&self) -> Result<()> {
+        mailchimp::segments::delete(&client_from_env()?, &self.list, self.id).await?;
+        Ok(())
+    }
+}
+
+/// List the members of a segment.
+#[derive(Debug, clap::Args)]
+pub struct Members {
+    /// The audience list ID.
+    list: String,
+    /// The segment ID.
+    id: u64,
+}
+
+impl Members {
+    pub async fn run(&self) -> Result<()> {
+        let client = client_from_env()?;
+        let members =
+            mailchimp::segments::members(&client, &self.list, self.id, Default::default())
+                .try_collect::<Vec<_>>()
+                .await?;
+        print_json(&members)
+    }
+}
+
+/// Add members to a static segment by email address.
+#[derive(Debug, clap::Args)]
+pub struct Add {
+    /// The audience list ID.
+    list: String,
+    /// The segment ID.
+    id: u64,
+    /// The email addresses to add.
+    #[arg(required = true)]
+    emails: Vec<String>,
+}
+
+impl Add {
+    pub async fn run(&self) -> Result<()> {
+        let client = client_from_env()?;
+        let response =
+            mailchimp::segments::update_members(&client, &self.list, self.id, &self.emails, &[])
+                .await?;
+        print_json(&response)
+    }
+}
+
+/// Remove members from a static segment by email address.
+#[derive(Debug, clap::Args)]
+pub struct Remove {
+    /// The audience list ID.
+    list: String,
+    /// The segment ID.
+    id: u64,
+    /// The email addresses to remove.
+    #[arg(required = true)]
+    emails: Vec<String>,
+}
+
+impl Remove {
+    pub async fn run(&self) -> Result<()> {
+        let client = client_from_env()?;
+        let response =
+            mailchimp::segments::update_members(&client, &self.list, self.id, &[], &self.emails)
+                .await?;
+        print_json(&response)
+    }
+}
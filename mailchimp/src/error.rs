@@ -11,6 +11,8 @@ pub enum Error {
     Request(#[from] reqwest::Error),
     #[error("json error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
     #[error("mailchimp error {}: {}", .0.status, .0.detail)]
     Mailchimp(MailchimError),
     #[error("unexpected value: {0}")]
@@ -23,6 +25,15 @@ pub enum Error {
     InvalidMergeField(String),
     #[error("config: {0}")]
     Config(#[from] config::ConfigError),
+    #[error(
+        "refusing to remove {removing} of {total} contacts from list {list_id}, the limit is {limit}"
+    )]
+    MassDeletion {
+        list_id: String,
+        removing: usize,
+        total: usize,
+        limit: crate::members::DeleteLimit,
+    },
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
@@ -51,19 +62,35 @@ impl Error {
         Self::InvalidMergeField(msg.to_string())
     }
 
+    /// Build an error from an unsuccessful API response. Mailchimp answers
+    /// with a problem document, but proxies and load balancers in front of
+    /// the API might not, in which case the raw body is kept as the detail.
+    pub fn from_response(status: reqwest::StatusCode, body: &[u8]) -> Self {
+        let error =
+            serde_json::from_slice::<MailchimError>(body).unwrap_or_else(|_| MailchimError {
+                status: status.as_u16(),
+                r#type: None,
+                title: status.canonical_reason().unwrap_or_default().to_string(),
+                detail: String::from_utf8_lossy(body).into_owned(),
+                instance: String::new(),
+            });
+        Self::Mailchimp(error)
+    }
+
     /// Returns true if this error is transient and the operation should be retried
     pub fn is_retryable(&self) -> bool {
         match self {
-            // 401 Unauthorized - invalid API key, don't retry
-            // 403 Forbidden - don't retry
-            // 404 Not Found - don't retry
-            Self::Mailchimp(err) if matches!(err.status, 401 | 403 | 404) => false,
+            // 429 Too Many Requests - back off and retry
+            Self::Mailchimp(err) if err.status == 429 => true,
+            // 5xx - server side issues, retry
+            Self::Mailchimp(err) if err.status >= 500 => true,
+            // Other mailchimp errors (validation, auth, not found) fail fast
+            Self::Mailchimp(_) => false,
             // Malformed API key - don't retry
             Self::MalformedAPIKey => false,
-            // Other mailchimp errors (rate limits, server errors) - retry
-            Self::Mailchimp(_) => true,
-            // Request errors (network issues, timeouts) - retry
-            Self::Request(_) => true,
+            // Request errors (network issues, timeouts) - retry, unless the
+            // response itself could not be decoded
+            Self::Request(err) => !err.is_decode(),
             // All other errors - don't retry
             _ => false,
         }
@@ -0,0 +1,395 @@
+use crate::{
+    Client, NO_QUERY, Result, Stream, deserialize_null_string, error::Error, paged_query_impl,
+    paged_response_impl, query_default_impl, read_config,
+};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// All interest categories (groups) of a list
+pub fn categories(
+    client: &Client,
+    list_id: &str,
+    query: InterestCategoriesQuery,
+) -> Stream<InterestCategory> {
+    client.fetch_stream::<InterestCategoriesQuery, InterestCategoriesResponse>(
+        &format!("/3.0/lists/{list_id}/interest-categories"),
+        query,
+    )
+}
+
+pub async fn get_category(
+    client: &Client,
+    list_id: &str,
+    category_id: &str,
+) -> Result<InterestCategory> {
+    client
+        .fetch(
+            &format!("/3.0/lists/{list_id}/interest-categories/{category_id}"),
+            NO_QUERY,
+        )
+        .await
+}
+
+pub async fn create_category(
+    client: &Client,
+    list_id: &str,
+    category: &InterestCategory,
+) -> Result<InterestCategory> {
+    client
+        .post(
+            &format!("/3.0/lists/{list_id}/interest-categories"),
+            category,
+        )
+        .await
+}
+
+pub async fn update_category(
+    client: &Client,
+    list_id: &str,
+    category_id: &str,
+    category: &InterestCategory,
+) -> Result<InterestCategory> {
+    client
+        .patch(
+            &format!("/3.0/lists/{list_id}/interest-categories/{category_id}"),
+            category,
+        )
+        .await
+}
+
+pub async fn delete_category(client: &Client, list_id: &str, category_id: &str) -> Result<()> {
+    client
+        .delete(&format!(
+            "/3.0/lists/{list_id}/interest-categories/{category_id}"
+        ))
+        .await
+}
+
+/// All interests of an interest category
+pub fn all(
+    client: &Client,
+    list_id: &str,
+    category_id: &str,
+    query: InterestsQuery,
+) -> Stream<Interest> {
+    client.fetch_stream::<InterestsQuery, InterestsResponse>(
+        &format!("/3.0/lists/{list_id}/interest-categories/{category_id}/interests"),
+        query,
+    )
+}
+
+pub async fn get(
+    client: &Client,
+    list_id: &str,
+    category_id: &str,
+    interest_id: &str,
+) -> Result<Interest> {
+    client
+        .fetch(
+            &format!(
+                "/3.0/lists/{list_id}/interest-categories/{category_id}/interests/{interest_id}"
+            ),
+            NO_QUERY,
+        )
+        .await
+}
+
+pub async fn create(
+    client: &Client,
+    list_id: &str,
+    category_id: &str,
+    interest: &Interest,
+) -> Result<Interest> {
+    client
+        .post(
+            &format!("/3.0/lists/{list_id}/interest-categories/{category_id}/interests"),
+            interest,
+        )
+        .await
+}
+
+pub async fn update(
+    client: &Client,
+    list_id: &str,
+    category_id: &str,
+    interest_id: &str,
+    interest: &Interest,
+) -> Result<Interest> {
+    client
+        .patch(
+            &format!(
+                "/3.0/lists/{list_id}/interest-categories/{category_id}/interests/{interest_id}"
+            ),
+            interest,
+        )
+        .await
+}
+
+pub async fn delete(
+    client: &Client,
+    list_id: &str,
+    category_id: &str,
+    interest_id: &str,
+) -> Result<()> {
+    client
+        .delete(&format!(
+            "/3.0/lists/{list_id}/interest-categories/{category_id}/interests/{interest_id}"
+        ))
+        .await
+}
+
+/// Make sure all configured interest categories and their interests exist
+/// on the given list, creating missing ones. Categories and interests are
+/// matched by title and name, nothing is ever deleted.
+///
+/// Returns the ids of all configured interests keyed by name
+pub async fn sync(client: &Client, list_id: &str, groups: &InterestGroups) -> Result<Interests> {
+    let current: Vec<InterestCategory> = categories(client, list_id, Default::default())
+        .try_collect()
+        .await?;
+
+    let mut interests = Interests::default();
+    for group in groups.iter() {
+        let category = match current.iter().find(|c| c.title == group.title) {
+            Some(category) => category.clone(),
+            None => {
+                let category = InterestCategory {
+                    title: group.title.clone(),
+                    r#type: group.r#type.clone(),
+                    ..Default::default()
+                };
+                create_category(client, list_id, &category).await?
+            }
+        };
+
+        let existing: Vec<Interest> = all(client, list_id, &category.id, Default::default())
+            .try_collect()
+            .await?;
+        for name in &group.interests {
+            let interest = match existing.iter().find(|i| &i.name == name) {
+                Some(interest) => interest.clone(),
+                None => {
+                    let interest = Interest {
+                        name: name.clone(),
+                        ..Default::default()
+                    };
+                    create(client, list_id, &category.id, &interest).await?
+                }
+            };
+            interests.0.insert(interest.name, interest.id);
+        }
+    }
+    Ok(interests)
+}
+
+/// The configured interests that already exist in the list, and the names
+/// of those `sync` would create. Nothing is changed.
+pub async fn lookup(
+    client: &Client,
+    list_id: &str,
+    groups: &InterestGroups,
+) -> Result<(Interests, Vec<String>)> {
+    let current: Vec<InterestCategory> = categories(client, list_id, Default::default())
+        .try_collect()
+        .await?;
+
+    let mut interests = Interests::default();
+    let mut missing = vec![];
+    for group in groups.iter() {
+        let existing: Vec<Interest> = match current.iter().find(|c| c.title == group.title) {
+            Some(category) => {
+                all(client, list_id, &category.id, Default::default())
+                    .try_collect()
+                    .await?
+            }
+            None => vec![],
+        };
+        for name in &group.interests {
+            match existing.iter().find(|i| &i.name == name) {
+                Some(interest) => {
+                    interests
+                        .0
+                        .insert(interest.name.clone(), interest.id.clone());
+                }
+                None => missing.push(name.clone()),
+            }
+        }
+    }
+    Ok((interests, missing))
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InterestType {
+    #[default]
+    Checkboxes,
+    Dropdown,
+    Radio,
+    Hidden,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct InterestCategory {
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub id: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_order: Option<i32>,
+    #[serde(default)]
+    pub r#type: InterestType,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Interest {
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub id: String,
+    #[serde(
+        default,
+        skip_serializing,
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub category_id: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_order: Option<i32>,
+}
+
+/// An interest category and the names of its interests as configured in
+/// `data/interests.toml`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterestGroup {
+    pub title: String,
+    #[serde(default)]
+    pub r#type: InterestType,
+    pub interests: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct InterestGroups(Vec<InterestGroup>);
+
+impl InterestGroups {
+    pub fn from_config<S>(source: S) -> Result<Self>
+    where
+        S: config::Source + Send + Sync + 'static,
+    {
+        #[derive(Debug, serde::Deserialize)]
+        struct InterestGroupsConfig {
+            categories: Vec<InterestGroup>,
+        }
+        let config: InterestGroupsConfig = read_config(source)?;
+        for group in &config.categories {
+            if group.interests.is_empty() {
+                return Err(Error::Config(config::ConfigError::Message(format!(
+                    "interest category without interests: {}",
+                    group.title
+                ))));
+            }
+        }
+        Ok(Self(config.categories))
+    }
+
+    /// Load the groups members' communication preferences are mapped to
+    pub fn preferences() -> Result<Self> {
+        let str = include_str!("../data/interests.toml");
+        Self::from_config(config::File::from_str(str, config::FileFormat::Toml))
+    }
+}
+
+impl std::ops::Deref for InterestGroups {
+    type Target = Vec<InterestGroup>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Interest ids of a list keyed by interest name
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Interests(HashMap<String, String>);
+
+impl Interests {
+    /// The member interest entry for the interest with the given name, or
+    /// `None` when the list has no such interest
+    pub fn to_value(&self, name: &str, selected: bool) -> Option<(String, bool)> {
+        self.0.get(name).map(|id| (id.clone(), selected))
+    }
+}
+
+impl std::ops::Deref for Interests {
+    type Target = HashMap<String, String>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<HashMap<String, String>> for Interests {
+    fn from(value: HashMap<String, String>) -> Self {
+        Self(value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterestCategoriesQuery {
+    pub fields: String,
+    pub count: usize,
+    pub offset: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterestCategoriesResponse {
+    pub categories: Vec<InterestCategory>,
+}
+
+query_default_impl!(InterestCategoriesQuery);
+paged_query_impl!(
+    InterestCategoriesQuery,
+    &[
+        "categories.id",
+        "categories.title",
+        "categories.display_order",
+        "categories.type"
+    ]
+);
+paged_response_impl!(InterestCategoriesResponse, categories, InterestCategory);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterestsQuery {
+    pub fields: String,
+    pub count: usize,
+    pub offset: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterestsResponse {
+    pub interests: Vec<Interest>,
+}
+
+query_default_impl!(InterestsQuery);
+paged_query_impl!(
+    InterestsQuery,
+    &[
+        "interests.id",
+        "interests.category_id",
+        "interests.name",
+        "interests.display_order"
+    ]
+);
+paged_response_impl!(InterestsResponse, interests, Interest);
@@ -1,12 +1,21 @@
+use bytes::Bytes;
 use futures::{
     Future as StdFuture, FutureExt, Stream as StdStream, StreamExt, TryFutureExt, future, stream,
 };
 use reqwest::{
-    Method, RequestBuilder, Url,
-    header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue},
+    Method, RequestBuilder, StatusCode, Url,
+    header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue, RETRY_AFTER},
 };
 use serde::{Serialize, de::DeserializeOwned};
-use std::{fmt::Debug, pin::Pin, str::FromStr, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+use tokio::sync::Semaphore;
 use tokio_retry2::strategy::jitter;
 
 /// A type alias for `Future` that may return `crate::error::Error`
@@ -18,10 +27,16 @@ pub type Stream<T> = Pin<Box<dyn StdStream<Item = Result<T>> + Send>>;
 mod error;
 
 pub mod batches;
+pub mod campaigns;
 pub mod health;
+pub mod interests;
 pub mod lists;
 pub mod members;
 pub mod merge_fields;
+pub mod segments;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod webhooks;
 
 pub use error::{Error, Result};
 
@@ -32,6 +47,12 @@ pub const DEFAULT_TIMEOUT: u64 = 20;
 pub const NO_QUERY: &[&str; 0] = &[""; 0];
 /// Default number of items to return in a query
 pub const DEFAULT_QUERY_COUNT: usize = 1000;
+/// Maximum number of simultaneous connections Mailchimp allows per API key
+pub const MAX_CONNECTIONS_PER_KEY: usize = 10;
+/// Number of times a rate limited request is retried by the client itself
+pub const RATE_LIMIT_RETRIES: usize = 5;
+/// Delay before retrying a rate limited request without a `Retry-After` header
+pub const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone)]
 pub struct BasicAuth {
@@ -79,6 +100,15 @@ impl AuthMode {
         }))
     }
 
+    /// Replace the API endpoint derived from the API key's datacenter with the
+    /// given base URL. This is mostly useful to point a client at a local
+    /// stand-in server.
+    pub fn with_endpoint(self, endpoint: Url) -> Self {
+        match self {
+            Self::Basic(auth) => Self::Basic(BasicAuth { endpoint, ..auth }),
+        }
+    }
+
     pub fn has_token(&self) -> bool {
         match self {
             Self::Basic(_) => true,
@@ -124,6 +154,34 @@ where
 pub struct Client {
     auth: AuthMode,
     client: reqwest::Client,
+    connections: Arc<Semaphore>,
+}
+
+/// Connection budgets shared by all clients using the same API key
+static CONNECTIONS: LazyLock<std::sync::Mutex<HashMap<String, Arc<Semaphore>>>> =
+    LazyLock::new(Default::default);
+
+/// The connection budget for the account behind the given credentials
+fn connections_for(auth: &AuthMode) -> Arc<Semaphore> {
+    let key = String::from_utf8_lossy(auth.to_authorization_header().as_bytes()).into_owned();
+    CONNECTIONS
+        .lock()
+        .unwrap()
+        .entry(key)
+        .or_insert_with(|| Arc::new(Semaphore::new(MAX_CONNECTIONS_PER_KEY)))
+        .clone()
+}
+
+/// Parse a `Retry-After` header given in seconds
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
 }
 
 pub mod client {
@@ -133,6 +191,13 @@ pub mod client {
         let auth = crate::AuthMode::new_basic_auth(key)?;
         Ok(crate::Client::new(auth))
     }
+
+    /// Construct a client for the given API key that talks to the given base
+    /// URL instead of the `https://{dc}.api.mailchimp.com` endpoint
+    pub fn from_api_key_with_endpoint(key: &str, endpoint: &str) -> Result<crate::Client> {
+        let auth = crate::AuthMode::new_basic_auth(key)?.with_endpoint(Url::parse(endpoint)?);
+        Ok(crate::Client::new(auth))
+    }
 }
 
 impl Client {
@@ -146,13 +211,21 @@ impl Client {
     /// Create a new client using a given base URL, and request
     /// timeout value.  The library will use absoluate paths based on
     /// the given base_url.
+    ///
+    /// All clients created for the same API key share a budget of
+    /// [`MAX_CONNECTIONS_PER_KEY`] simultaneous requests.
     pub fn new_with_timeout(auth: AuthMode, timeout: u64) -> Self {
         let client = reqwest::Client::builder()
             .gzip(true)
             .timeout(Duration::from_secs(timeout))
             .build()
             .unwrap();
-        Self { auth, client }
+        let connections = connections_for(&auth);
+        Self {
+            auth,
+            client,
+            connections,
+        }
     }
 
     fn request(&self, method: Method, path: &str) -> Result<RequestBuilder> {
@@ -166,36 +239,52 @@ impl Client {
         Ok(self.client.request(method, url).headers(headers))
     }
 
+    /// Send a request within the account's connection budget and return the
+    /// body of a successful response.
+    ///
+    /// Rate limited requests are retried after the delay given by Mailchimp
+    /// in the `Retry-After` header, up to [`RATE_LIMIT_RETRIES`] times. Any
+    /// other unsuccessful response is returned as a [`Error::Mailchimp`].
+    async fn send(connections: Arc<Semaphore>, builder: RequestBuilder) -> Result<Bytes> {
+        let mut rate_limited = 0;
+        loop {
+            // Request bodies are always buffered json, so cloning can't fail
+            let request = builder.try_clone().expect("cloneable request");
+            let (status, headers, bytes) = {
+                let _permit = connections
+                    .acquire()
+                    .await
+                    .expect("connection budget is never closed");
+                let response = request.send().await?;
+                let status = response.status();
+                let headers = response.headers().clone();
+                (status, headers, response.bytes().await?)
+            };
+
+            if status == StatusCode::TOO_MANY_REQUESTS && rate_limited < RATE_LIMIT_RETRIES {
+                rate_limited += 1;
+                let wait = retry_after(&headers).unwrap_or(DEFAULT_RETRY_AFTER);
+                tracing::warn!(wait = wait.as_secs(), rate_limited, "mailchimp rate limit");
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+
+            if status.is_success() {
+                return Ok(bytes);
+            }
+            return Err(Error::from_response(status, &bytes));
+        }
+    }
+
     pub fn fetch<T, Q>(&self, path: &str, query: &Q) -> Future<T>
     where
         T: 'static + DeserializeOwned + Send,
         Q: Serialize + ?Sized,
     {
         match self.request(Method::GET, path) {
-            Ok(builder) => builder
-                .query(query)
-                .send()
-                .map_err(Error::from)
-                .and_then(|response| {
-                    let status = response.status();
-                    if status.is_client_error() {
-                        return response
-                            .json::<error::MailchimError>()
-                            .map_err(error::Error::from)
-                            .and_then(|e| async move { Err(Error::mailchimp(e)) })
-                            .boxed();
-                    }
-                    match response.error_for_status() {
-                        Ok(result) => result
-                            .bytes()
-                            .map_err(Error::from)
-                            .and_then(|bytes| async move {
-                                // println!("{}", String::from_utf8_lossy(&bytes));
-                                serde_json::from_slice(&bytes).map_err(error::Error::from)
-                            })
-                            .boxed(),
-                        Err(e) => future::err(error::Error::from(e)).boxed(),
-                    }
+            Ok(builder) => Self::send(self.connections.clone(), builder.query(query))
+                .and_then(|bytes| async move {
+                    serde_json::from_slice(&bytes).map_err(error::Error::from)
                 })
                 .boxed(),
             Err(e) => future::err(e).boxed(),
@@ -254,34 +343,12 @@ impl Client {
         R: 'static + DeserializeOwned + std::marker::Send,
     {
         match self.request(method, path) {
-            Ok(builder) => builder
-                .json(json)
-                .send()
-                .map_err(error::Error::from)
-                .and_then(|response| {
-                    let status = response.status();
-                    if status.is_client_error() {
-                        return response
-                            .json::<error::MailchimError>()
-                            .map_err(error::Error::from)
-                            .and_then(|e| async move { Err(Error::mailchimp(e)) })
-                            .boxed();
-                    }
-                    match response.error_for_status() {
-                        Ok(result) => result
-                            .bytes()
-                            .map_err(error::Error::from)
-                            .and_then(|bytes| async move {
-                                if bytes.is_empty() {
-                                    serde_json::from_str("null").map_err(error::Error::from)
-                                } else {
-                                    // println!("{}", String::from_utf8_lossy(&bytes));
-                                    serde_json::from_slice(&bytes).map_err(error::Error::from)
-                                }
-                            })
-                            .boxed(),
-                        // Ok(result) => result.json().map_err(error::Error::from).boxed(),
-                        Err(e) => future::err(error::Error::from(e)).boxed(),
+            Ok(builder) => Self::send(self.connections.clone(), builder.json(json))
+                .and_then(|bytes| async move {
+                    if bytes.is_empty() {
+                        serde_json::from_str("null").map_err(error::Error::from)
+                    } else {
+                        serde_json::from_slice(&bytes).map_err(error::Error::from)
                     }
                 })
                 .boxed(),
@@ -313,15 +380,27 @@ impl Client {
         self.submit(Method::PUT, path, json)
     }
 
+    /// Download a file Mailchimp links to, like batch result archives. These
+    /// live outside the API and are fetched without credentials.
+    pub fn download(&self, url: &str) -> Future<Bytes> {
+        let request = self.client.get(url);
+        async move {
+            let response = request.send().await?;
+            let status = response.status();
+            let bytes = response.bytes().await?;
+            if status.is_success() {
+                Ok(bytes)
+            } else {
+                Err(Error::from_response(status, &bytes))
+            }
+        }
+        .boxed()
+    }
+
     pub fn delete(&self, path: &str) -> Future<()> {
         match self.request(Method::DELETE, path) {
-            Ok(builder) => builder
-                .send()
-                .map_err(error::Error::from)
-                .and_then(|response| match response.error_for_status() {
-                    Ok(_) => future::ok(()).boxed(),
-                    Err(e) => future::err(error::Error::from(e)).boxed(),
-                })
+            Ok(builder) => Self::send(self.connections.clone(), builder)
+                .map_ok(|_| ())
                 .boxed(),
             Err(e) => future::err(e).boxed(),
         }
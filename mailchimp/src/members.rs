@@ -62,38 +62,475 @@ pub async fn delete(client: &Client, list_id: &str, member_id: &str) -> Result<(
         .await
 }
 
-/// Delete any members from the given list id not in a given retained set
-///
-/// Returns the numebr of deleted members from the given list
-pub async fn retain(client: &Client, list_id: &str, keep_keys: &HashSet<String>) -> Result<usize> {
+/// Permanently delete a member and its history. Mailchimp refuses to
+/// re-import permanently deleted addresses.
+pub async fn delete_permanent(client: &Client, list_id: &str, member_id: &str) -> Result<()> {
+    client
+        .post(
+            &format!("/3.0/lists/{list_id}/members/{member_id}/actions/delete-permanent"),
+            &serde_json::json!({}),
+        )
+        .await
+}
+
+/// The tag put on members unsubscribed by [`RemovalPolicy::Unsubscribe`]
+pub const REMOVED_TAG: &str = "removed";
+
+/// How [`retain`] removes members that are no longer retained
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemovalPolicy {
+    /// Archive the member. Archived members keep their history and are
+    /// restored when they are added again.
+    #[default]
+    Archive,
+    /// Permanently delete the member and its history
+    DeletePermanent,
+    /// Keep the member, but unsubscribe it and tag it with [`REMOVED_TAG`]
+    Unsubscribe,
+}
+
+impl RemovalPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Archive => "archive",
+            Self::DeletePermanent => "delete-permanent",
+            Self::Unsubscribe => "unsubscribe",
+        }
+    }
+
+    /// Add the operations removing a member to a batch
+    fn remove(&self, batch: &mut batches::Batch, list_id: &str, member_id: &str) -> Result<()> {
+        let path = format!("/lists/{list_id}/members/{member_id}");
+        match self {
+            Self::Archive => {
+                batch.delete(&path, &serde_json::json!({}))?.operation_id = member_id.to_owned();
+            }
+            Self::DeletePermanent => {
+                batch
+                    .post(
+                        &format!("{path}/actions/delete-permanent"),
+                        &serde_json::json!({}),
+                    )?
+                    .operation_id = member_id.to_owned();
+            }
+            Self::Unsubscribe => {
+                let unsubscribe = Member {
+                    status: Some(MemberStatus::Unsubscribed),
+                    ..Default::default()
+                };
+                batch.patch(&path, &unsubscribe)?.operation_id = member_id.to_owned();
+                let tag = MemberTagUpdate {
+                    name: REMOVED_TAG.to_string(),
+                    status: MemberTagStatus::Active,
+                };
+                tags::batch::update(batch, list_id, member_id, &[tag])?.operation_id =
+                    member_id.to_owned();
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a member still needs to be removed. Cleaned members are
+    /// never touched. Only subscribed members are unsubscribed, so contacts
+    /// who unsubscribed themselves are not tagged as removed.
+    fn applies_to(&self, member: &Member) -> bool {
+        match (self, &member.status) {
+            (_, Some(MemberStatus::Cleaned) | Some(MemberStatus::Archived)) => false,
+            (Self::Unsubscribe, status) => *status == Some(MemberStatus::Subscribed),
+            _ => true,
+        }
+    }
+}
+
+/// Whether [`RemovalPolicy::Unsubscribe`] removed the member, which is
+/// subscribed again when it comes back
+fn was_removed(member: &Member) -> bool {
+    member.status == Some(MemberStatus::Unsubscribed)
+        && member.tags.iter().any(|tag| tag.name == REMOVED_TAG)
+}
+
+impl std::fmt::Display for RemovalPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for RemovalPolicy {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(&format!("\"{s}\"")).map_err(|_| Error::value(s.into()))
+    }
+}
+
+impl TryFrom<String> for RemovalPolicy {
+    type Error = Error;
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// How many contacts a single [`retain`] may remove, absolute and as
+/// percentage of the list. Removing is aborted when either is exceeded, the
+/// default has no limits.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct DeleteLimit {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+}
+
+impl DeleteLimit {
+    pub fn exceeded(&self, removing: usize, total: usize) -> bool {
+        let over_rows = self.rows.is_some_and(|rows| removing as u64 > rows);
+        let over_percent = self
+            .percent
+            .is_some_and(|percent| total > 0 && removing as f64 * 100.0 / total as f64 > percent);
+        over_rows || over_percent
+    }
+}
+
+impl std::fmt::Display for DeleteLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.rows, self.percent) {
+            (Some(rows), Some(percent)) => write!(f, "{rows} contacts or {percent}%"),
+            (Some(rows), None) => write!(f, "{rows} contacts"),
+            (None, Some(percent)) => write!(f, "{percent}%"),
+            (None, None) => f.write_str("none"),
+        }
+    }
+}
+
+/// What [`retain`] changed in a list
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Retained {
+    /// The number of removed contacts
+    pub removed: usize,
+    /// The operations removing contacts that failed
+    pub failed_removals: Vec<batches::BatchOperationResult>,
+    /// The operations subscribing kept contacts again that failed
+    pub failed_restores: Vec<batches::BatchOperationResult>,
+}
+
+/// Remove all contacts of the list not in `keep_keys`. Fails with
+/// [`Error::MassDeletion`] without removing anything when that would be
+/// more contacts than `limit` allows. Kept contacts that were removed by
+/// unsubscribing them are subscribed again.
+pub async fn retain(
+    client: &Client,
+    list_id: &str,
+    keep_keys: &HashSet<String>,
+    policy: RemovalPolicy,
+    limit: DeleteLimit,
+    retries: RetryPolicy,
+) -> Result<Retained> {
     // Iterate through all mailchimp audience member. Collect all members that are not
     // the upserted set by set subtraction
     let audience = all_collect(
         client,
         list_id,
         MembersQuery {
-            fields: "members.id".to_string(),
+            fields: "members.id,members.status,members.tags".to_string(),
             ..Default::default()
         },
     )
     .await?;
-    let audience_ids: HashSet<String> = audience
+    let total = audience.len();
+    let to_restore = audience
+        .iter()
+        .filter(|member| keep_keys.contains(&member.id) && was_removed(member))
+        .map(|member| member.id.clone())
+        .collect::<Vec<_>>();
+    let to_remove: Vec<String> = audience
         .into_iter()
-        .filter_map(|member| (member.status != Some(MemberStatus::Cleaned)).then_some(member.id))
+        .filter(|member| !keep_keys.contains(&member.id) && policy.applies_to(member))
+        .map(|member| member.id)
         .collect();
+    if limit.exceeded(to_remove.len(), total) {
+        return Err(Error::MassDeletion {
+            list_id: list_id.to_string(),
+            removing: to_remove.len(),
+            total,
+            limit,
+        });
+    }
 
-    let to_delete = &audience_ids - keep_keys;
-    // Delete all to_delete entries
-    futures::stream::iter(to_delete.iter())
-        .map(|member_id| Ok::<_, crate::Error>((client.clone(), member_id)))
-        .try_for_each_concurrent(10, |(client, member_id)| async move {
-            delete(&client, list_id, member_id)
-                .await
-                .inspect_err(|err| tracing::error!(id = member_id, ?err, "failed to delete"))?;
-            Ok(())
+    let batches = to_remove
+        .chunks(batches::OPERATIONS_PER_BATCH)
+        .map(|member_ids| {
+            let mut batch = batches::Batch::default();
+            for member_id in member_ids {
+                policy.remove(&mut batch, list_id, member_id)?;
+            }
+            Ok(batch)
         })
-        .await?;
-    Ok(to_delete.len())
+        .collect::<Result<Vec<_>>>()?;
+    let failed_removals = batches::run_many(client, batches, retries).await?;
+
+    let mut failed_ids = HashSet::new();
+    for result in &failed_removals {
+        let detail = result.error().map(|err| err.detail).unwrap_or_default();
+        tracing::error!(
+            id = result.operation_id,
+            status = result.status_code,
+            detail,
+            %policy,
+            "failed to remove"
+        );
+        failed_ids.insert(result.operation_id.as_str());
+    }
+    let removed = to_remove.len() - failed_ids.len();
+    let failed_restores = restore(client, list_id, &to_restore, retries).await?;
+    Ok(Retained {
+        removed,
+        failed_removals,
+        failed_restores,
+    })
+}
+
+/// Subscribe members removed by [`RemovalPolicy::Unsubscribe`] again, and
+/// clear the [`REMOVED_TAG`] of those subscribed. Returns the operations
+/// that failed.
+async fn restore(
+    client: &Client,
+    list_id: &str,
+    member_ids: &[String],
+    retries: RetryPolicy,
+) -> Result<Vec<batches::BatchOperationResult>> {
+    let batches = member_ids
+        .chunks(batches::OPERATIONS_PER_BATCH)
+        .map(|member_ids| {
+            let mut batch = batches::Batch::default();
+            for member_id in member_ids {
+                let subscribe = Member {
+                    status: Some(MemberStatus::Subscribed),
+                    ..Default::default()
+                };
+                batch
+                    .patch(&format!("/lists/{list_id}/members/{member_id}"), &subscribe)?
+                    .operation_id = member_id.to_owned();
+            }
+            Ok(batch)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut failed = batches::run_many(client, batches, retries).await?;
+    for result in &failed {
+        let detail = result.error().map(|err| err.detail).unwrap_or_default();
+        tracing::error!(
+            id = result.operation_id,
+            status = result.status_code,
+            detail,
+            "failed to subscribe again"
+        );
+    }
+
+    // Members still unsubscribed stay tagged as removed
+    let failed_ids: HashSet<&str> = failed.iter().map(|r| r.operation_id.as_str()).collect();
+    let tag_updates = member_ids
+        .iter()
+        .filter(|member_id| !failed_ids.contains(member_id.as_str()))
+        .map(|member_id| {
+            let tag = MemberTagUpdate {
+                name: REMOVED_TAG.to_string(),
+                status: MemberTagStatus::Inactive,
+            };
+            (member_id.clone(), vec![tag])
+        })
+        .collect::<Vec<_>>();
+    failed.extend(tags::update_many(client, list_id, &tag_updates, retries).await?);
+    Ok(failed)
+}
+
+/// What upserting `members`, removing everyone else and applying the tag
+/// updates would change in a list, computed without writing anything
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MembersPlan {
+    /// Emails of contacts to create, including archived contacts that
+    /// would be restored
+    pub create: Vec<String>,
+    /// Existing contacts whose merge fields or interests change
+    pub update: Vec<MemberChanges>,
+    /// Emails of contacts to remove
+    pub remove: Vec<String>,
+    pub removal_policy: RemovalPolicy,
+    pub tags: Vec<TagChanges>,
+    /// Existing contacts the upsert leaves as they are
+    pub unchanged: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MemberChanges {
+    pub email: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// A changed merge field, by tag, or interest, by `interests.{id}`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: serde_json::Value,
+    pub to: serde_json::Value,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct TagChanges {
+    pub email: String,
+    pub add: Vec<String>,
+    pub remove: Vec<String>,
+}
+
+/// Compute what syncing would change. Only reads the list.
+pub async fn plan(
+    client: &Client,
+    list_id: &str,
+    members: &[Member],
+    tag_updates: &[(String, Vec<MemberTagUpdate>)],
+    policy: RemovalPolicy,
+) -> Result<MembersPlan> {
+    let audience = all_collect(
+        client,
+        list_id,
+        MembersQuery {
+            fields: "members.id,members.email_address,members.status,members.merge_fields,\
+                members.interests,members.tags"
+                .to_string(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    Ok(diff(&audience, members, tag_updates, policy))
+}
+
+/// Compare the current contacts of a list with the members to sync
+pub fn diff(
+    audience: &[Member],
+    members: &[Member],
+    tag_updates: &[(String, Vec<MemberTagUpdate>)],
+    policy: RemovalPolicy,
+) -> MembersPlan {
+    let current: HashMap<&str, &Member> = audience
+        .iter()
+        .map(|member| (member.id.as_str(), member))
+        .collect();
+    let keep: HashSet<&str> = members.iter().map(|member| member.id.as_str()).collect();
+    let emails: HashMap<&str, &str> = audience
+        .iter()
+        .chain(members)
+        .map(|member| (member.id.as_str(), member.email_address.as_str()))
+        .collect();
+
+    let mut plan = MembersPlan {
+        removal_policy: policy,
+        ..Default::default()
+    };
+    for member in members {
+        match current.get(member.id.as_str()) {
+            Some(existing) if existing.status != Some(MemberStatus::Archived) => {
+                let mut changes = member_changes(existing, member);
+                if was_removed(existing) {
+                    changes.push(FieldChange {
+                        field: "status".to_string(),
+                        from: serde_json::json!(MemberStatus::Unsubscribed),
+                        to: serde_json::json!(MemberStatus::Subscribed),
+                    });
+                }
+                if changes.is_empty() {
+                    plan.unchanged += 1;
+                } else {
+                    plan.update.push(MemberChanges {
+                        email: member.email_address.clone(),
+                        changes,
+                    });
+                }
+            }
+            _ => plan.create.push(member.email_address.clone()),
+        }
+    }
+    plan.remove = audience
+        .iter()
+        .filter(|member| !keep.contains(member.id.as_str()) && policy.applies_to(member))
+        .map(|member| member.email_address.clone())
+        .collect();
+
+    for (member_id, updates) in tag_updates {
+        let tags: HashSet<&str> = current
+            .get(member_id.as_str())
+            .filter(|member| member.status != Some(MemberStatus::Archived))
+            .map(|member| member.tags.iter().map(|tag| tag.name.as_str()).collect())
+            .unwrap_or_default();
+        let mut changes = TagChanges::default();
+        for update in updates {
+            match update.status {
+                MemberTagStatus::Active if !tags.contains(update.name.as_str()) => {
+                    changes.add.push(update.name.clone())
+                }
+                MemberTagStatus::Inactive if tags.contains(update.name.as_str()) => {
+                    changes.remove.push(update.name.clone())
+                }
+                _ => {}
+            }
+        }
+        if !changes.add.is_empty() || !changes.remove.is_empty() {
+            changes.email = emails
+                .get(member_id.as_str())
+                .map_or_else(|| member_id.clone(), |email| email.to_string());
+            plan.tags.push(changes);
+        }
+    }
+    plan.create.sort();
+    plan.remove.sort();
+    plan.update.sort_by(|a, b| a.email.cmp(&b.email));
+    plan.tags.sort_by(|a, b| a.email.cmp(&b.email));
+    plan
+}
+
+/// The merge fields and interests an upsert of `member` would change
+fn member_changes(existing: &Member, member: &Member) -> Vec<FieldChange> {
+    use serde_json::Value;
+    /// Mailchimp leaves out empty values and returns empty address parts, so
+    /// only what is sent is compared
+    fn same(current: &Value, target: &Value) -> bool {
+        match (current, target) {
+            (Value::Object(current), Value::Object(target)) => target
+                .iter()
+                .all(|(key, value)| same(current.get(key).unwrap_or(&Value::Null), value)),
+            (Value::Null, Value::String(target)) => target.is_empty(),
+            (current, target) => current == target,
+        }
+    }
+
+    let mut changes = vec![];
+    let no_fields = HashMap::new();
+    let fields = existing.merge_fields.as_ref().unwrap_or(&no_fields);
+    let mut targets: Vec<_> = member.merge_fields.iter().flatten().collect();
+    targets.sort_by(|a, b| a.0.cmp(b.0));
+    for (tag, value) in targets {
+        let current = fields.get(tag).cloned().unwrap_or_default();
+        if !same(&current, value) {
+            changes.push(FieldChange {
+                field: tag.clone(),
+                from: current,
+                to: value.clone(),
+            });
+        }
+    }
+
+    let no_interests = HashMap::new();
+    let interests = existing.interests.as_ref().unwrap_or(&no_interests);
+    let mut targets: Vec<_> = member.interests.iter().flatten().collect();
+    targets.sort_by(|a, b| a.0.cmp(b.0));
+    for (id, selected) in targets {
+        let current = interests.get(id).copied().unwrap_or_default();
+        if current != *selected {
+            changes.push(FieldChange {
+                field: format!("interests.{id}"),
+                from: current.into(),
+                to: (*selected).into(),
+            });
+        }
+    }
+    changes
 }
 
 pub async fn for_email(client: &Client, list_id: &str, email: &str) -> Result<Member> {
@@ -246,31 +683,39 @@ pub mod tags {
             .await
     }
 
+    /// Update the tags of many members using batches
+    ///
+    /// Returns the results of the operations that failed, keyed by member id
+    /// through their `operation_id`. Failures are logged as well.
     pub async fn update_many(
         client: &Client,
         list_id: &str,
         tag_updates: &[(String, Vec<MemberTagUpdate>)],
         retries: RetryPolicy,
-    ) -> Result {
-        futures::stream::iter(tag_updates)
-            .chunks(1000)
-            .map(Ok::<Vec<_>, Error>)
-            .map_ok(|updates| (client.clone(), updates, retries))
-            .try_for_each_concurrent(10, |(client, updates, retries)| async move {
+    ) -> Result<Vec<batches::BatchOperationResult>> {
+        let batches = tag_updates
+            .chunks(batches::OPERATIONS_PER_BATCH)
+            .map(|updates| {
                 let mut batch = batches::Batch::default();
                 for (member_id, updates) in updates {
                     let operation = batch::update(&mut batch, list_id, member_id, updates)?;
                     operation.operation_id = member_id.to_owned();
                 }
-                Retry::spawn_notify(
-                    retries,
-                    || batch.run(&client, true).map_err(Error::into_retry),
-                    |err, sleep| tracing::warn!(%err, sleep = sleep.as_secs(), "batch tag update"),
-                )
-                .await?;
-                Ok(())
+                Ok(batch)
             })
-            .await
+            .collect::<Result<Vec<_>>>()?;
+        let failed = batches::run_many(client, batches, retries).await?;
+
+        for result in &failed {
+            let detail = result.error().map(|err| err.detail).unwrap_or_default();
+            tracing::warn!(
+                member_id = result.operation_id,
+                status = result.status_code,
+                detail,
+                "tag update failed"
+            );
+        }
+        Ok(failed)
     }
 
     pub mod batch {
@@ -335,6 +780,9 @@ pub struct Member {
     pub status: Option<MemberStatus>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub merge_fields: Option<HashMap<String, serde_json::Value>>,
+    /// Group membership keyed by interest id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interests: Option<HashMap<String, bool>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tags_count: Option<u16>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -343,7 +791,7 @@ pub struct Member {
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct MemberTag {
-    name: String,
+    pub name: String,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -48,56 +48,97 @@ pub async fn update(
         .await
 }
 
+/// How the merge fields of a list differ from a target configuration
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeFieldsPlan {
+    /// Tags of fields to create
+    pub added: Vec<String>,
+    /// Tags of fields not in the target
+    pub deleted: Vec<String>,
+    /// Fields to patch, with the attributes that differ
+    pub updated: Vec<MergeFieldChanges>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct MergeFieldChanges {
+    pub tag: String,
+    pub changes: Vec<&'static str>,
+}
+
+impl MergeFieldsPlan {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.deleted.is_empty() && self.updated.is_empty()
+    }
+}
+
+/// Compare the current merge fields of a list with the target
+pub fn diff(current: &MergeFields, target: &MergeFields) -> MergeFieldsPlan {
+    fn sorted<'a>(tags: impl Iterator<Item = &'a String>) -> Vec<String> {
+        let mut tags: Vec<String> = tags.cloned().collect();
+        tags.sort();
+        tags
+    }
+    let mut updated: Vec<MergeFieldChanges> = target
+        .values()
+        .filter_map(|field| {
+            let changes = field.changes(current.get(&field.tag)?);
+            (!changes.is_empty()).then(|| MergeFieldChanges {
+                tag: field.tag.clone(),
+                changes,
+            })
+        })
+        .collect();
+    updated.sort_by(|a, b| a.tag.cmp(&b.tag));
+    MergeFieldsPlan {
+        added: sorted(target.keys().filter(|tag| !current.contains_key(*tag))),
+        deleted: sorted(current.keys().filter(|tag| !target.contains_key(*tag))),
+        updated,
+    }
+}
+
+/// What `sync` would change, without changing anything
+pub async fn plan(client: &Client, list_id: &str, target: &MergeFields) -> Result<MergeFieldsPlan> {
+    let current: MergeFields = all(client, list_id, Default::default())
+        .try_collect()
+        .await?;
+    Ok(diff(&current, target))
+}
+
 pub async fn sync(
     client: &Client,
     list_id: &str,
-    target: MergeFields,
+    mut target: MergeFields,
     process_deletes: bool,
 ) -> Result<(Vec<String>, Vec<String>, Vec<String>)> {
-    type TaggedMergeField = (String, MergeField);
-
     let current: MergeFields = all(client, list_id, Default::default())
         .try_collect()
         .await?;
+    let plan = diff(&current, &target);
 
-    fn collect_tags(fields: &[TaggedMergeField]) -> Vec<String> {
-        fields
-            .iter()
-            .map(|(_, field)| field.tag.clone())
-            .collect::<Vec<_>>()
-    }
-
-    let (to_delete, _): (Vec<TaggedMergeField>, Vec<TaggedMergeField>) = current
-        .clone()
-        .into_iter()
-        .partition(|(key, _)| !target.contains_key(key));
-
-    let (to_add, target_remaining): (Vec<TaggedMergeField>, Vec<TaggedMergeField>) = target
-        .into_iter()
-        .partition(|(key, _)| !current.contains_key(key));
-
-    let deleted = collect_tags(&to_delete);
     if process_deletes {
-        for (_, field) in to_delete {
-            delete(client, list_id, &field.merge_id.to_string()).await?;
+        for tag in &plan.deleted {
+            delete(client, list_id, &current[tag].merge_id.to_string()).await?;
         }
     }
 
-    let added = collect_tags(&to_add);
-    for (_, field) in to_add {
-        create(client, list_id, field).await?;
+    for tag in &plan.added {
+        if let Some(field) = target.remove(tag) {
+            create(client, list_id, field).await?;
+        }
     }
 
     let mut updated = vec![];
-    for (_, mut field) in target_remaining.into_iter() {
-        let current = current.get(&field.tag).unwrap();
-        field.merge_id = current.merge_id;
-        if field != *current {
-            updated.push(field.tag.clone());
-            update(client, list_id, &current.merge_id.to_string(), field).await?;
-        }
+    for MergeFieldChanges { tag, changes } in plan.updated {
+        let Some(mut field) = target.remove(&tag) else {
+            continue;
+        };
+        tracing::debug!(tag, ?changes, "updating merge field");
+        let merge_id = current[&tag].merge_id;
+        field.merge_id = merge_id;
+        update(client, list_id, &merge_id.to_string(), field).await?;
+        updated.push(tag);
     }
-    Ok((added, deleted, updated))
+    Ok((plan.added, plan.deleted, updated))
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
@@ -123,6 +164,20 @@ pub struct MergeField {
 
     #[serde(default)]
     pub r#type: MergeType,
+
+    /// Attributes left out of the configuration are not managed by `sync`,
+    /// whatever Mailchimp has for them is kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    /// Whether the field is shown on signup forms and profile pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_order: Option<i32>,
+    #[serde(default, skip_serializing_if = "MergeFieldOptions::is_empty")]
+    pub options: MergeFieldOptions,
 }
 
 impl Default for MergeField {
@@ -132,7 +187,80 @@ impl Default for MergeField {
             tag: "".to_string(),
             name: "".to_string(),
             r#type: MergeType::default(),
+            required: None,
+            public: None,
+            default_value: None,
+            display_order: None,
+            options: MergeFieldOptions::default(),
+        }
+    }
+}
+
+impl MergeField {
+    /// The attributes that differ between this field as configured and the
+    /// field as it is in Mailchimp. Only configured attributes are compared.
+    pub fn changes(&self, current: &MergeField) -> Vec<&'static str> {
+        fn differs<T: PartialEq>(target: &Option<T>, current: &Option<T>) -> bool {
+            target.is_some() && target != current
+        }
+        let mut changes = vec![];
+        if self.name != current.name {
+            changes.push("name");
+        }
+        if self.r#type != current.r#type {
+            changes.push("type");
+        }
+        if differs(&self.required, &current.required) {
+            changes.push("required");
+        }
+        if differs(&self.public, &current.public) {
+            changes.push("public");
+        }
+        if differs(&self.default_value, &current.default_value) {
+            changes.push("default_value");
+        }
+        if differs(&self.display_order, &current.display_order) {
+            changes.push("display_order");
+        }
+        if differs(&self.options.choices, &current.options.choices) {
+            changes.push("options.choices");
         }
+        if differs(&self.options.date_format, &current.options.date_format) {
+            changes.push("options.date_format");
+        }
+        if differs(&self.options.size, &current.options.size) {
+            changes.push("options.size");
+        }
+        if differs(&self.options.phone_format, &current.options.phone_format) {
+            changes.push("options.phone_format");
+        }
+        changes
+    }
+}
+
+/// Type specific settings of a merge field
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct MergeFieldOptions {
+    /// The choices of a `radio` or `dropdown` field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<String>>,
+    /// The format of a `date` or `birthday` field, like `MM/DD/YYYY`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_format: Option<String>,
+    /// The size of the input of a `text` field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<i32>,
+    /// `US` for US phone numbers or `none` for international ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phone_format: Option<String>,
+}
+
+impl MergeFieldOptions {
+    pub fn is_empty(&self) -> bool {
+        self.choices.is_none()
+            && self.date_format.is_none()
+            && self.size.is_none()
+            && self.phone_format.is_none()
     }
 }
 
@@ -187,6 +315,24 @@ impl MergeFields {
                     if field.tag.len() > 10 {
                         return Err(Error::merge_field(format!("tag too long: {}", field.tag)));
                     }
+                    let choices = field
+                        .options
+                        .choices
+                        .as_ref()
+                        .is_some_and(|c| !c.is_empty());
+                    match field.r#type {
+                        MergeType::Radio | MergeType::Dropdown if !choices => {
+                            return Err(Error::merge_field(format!("{} needs choices", field.tag)));
+                        }
+                        MergeType::Radio | MergeType::Dropdown => {}
+                        _ if field.options.choices.is_some() => {
+                            return Err(Error::merge_field(format!(
+                                "{} can not have choices",
+                                field.tag
+                            )));
+                        }
+                        _ => {}
+                    }
                 }
                 Ok(config.merge_fields.into_iter().collect())
             }
@@ -337,15 +483,165 @@ impl ToMergeFieldValue for i64 {
     }
 }
 
-impl ToMergeFieldValue for &str {
+impl ToMergeFieldValue for f64 {
     fn to_merge_field_value(self, field: &MergeField) -> Result<Option<MergeFieldValue>> {
+        if !self.is_finite() {
+            return Err(Error::number(&self.to_string()));
+        }
         match field.r#type {
+            MergeType::Number => Ok(Some((
+                field.tag.clone(),
+                serde_json::to_value(self).unwrap(),
+            ))),
             MergeType::Text => Ok(Some((field.tag.clone(), self.to_string().into()))),
             _ => Err(Error::InvalidMergeType(field.r#type.to_string())),
         }
     }
 }
 
+/// A yes/no value for a `radio` or `dropdown` field with exactly two
+/// choices. `true` selects the first choice and `false` the second.
+impl ToMergeFieldValue for bool {
+    fn to_merge_field_value(self, field: &MergeField) -> Result<Option<MergeFieldValue>> {
+        match field.r#type {
+            MergeType::Radio | MergeType::Dropdown => {
+                match field.options.choices.as_deref().unwrap_or_default() {
+                    [yes, no] => Ok(Some((
+                        field.tag.clone(),
+                        if self { yes } else { no }.as_str().into(),
+                    ))),
+                    _ => Err(Error::merge_field(format!(
+                        "{} needs exactly two choices for a yes/no value",
+                        field.tag
+                    ))),
+                }
+            }
+            _ => Err(Error::InvalidMergeType(field.r#type.to_string())),
+        }
+    }
+}
+
+/// The value of an `address` merge field. Mailchimp rejects addresses
+/// without a street, city, state or zip code, those are left out instead.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Address {
+    pub addr1: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub addr2: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    /// The two letter ISO country code
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub country: String,
+}
+
+impl Address {
+    pub fn is_complete(&self) -> bool {
+        [&self.addr1, &self.city, &self.state, &self.zip]
+            .iter()
+            .all(|part| !part.trim().is_empty())
+    }
+}
+
+impl ToMergeFieldValue for &Address {
+    fn to_merge_field_value(self, field: &MergeField) -> Result<Option<MergeFieldValue>> {
+        match field.r#type {
+            MergeType::Address if self.is_complete() => {
+                Ok(Some((field.tag.clone(), serde_json::to_value(self)?)))
+            }
+            MergeType::Address => Ok(None),
+            _ => Err(Error::InvalidMergeType(field.r#type.to_string())),
+        }
+    }
+}
+
+impl ToMergeFieldValue for Address {
+    fn to_merge_field_value(self, field: &MergeField) -> Result<Option<MergeFieldValue>> {
+        (&self).to_merge_field_value(field)
+    }
+}
+
+/// Format a phone number for a `phone` field. US formatted fields only take
+/// ten digit numbers, written as `555-555-0123`.
+fn format_phone(value: &str, field: &MergeField) -> Result<String> {
+    let us = field
+        .options
+        .phone_format
+        .as_deref()
+        .is_some_and(|format| format.eq_ignore_ascii_case("us"));
+    if !us {
+        return Ok(value.trim().to_string());
+    }
+    let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+    let digits = match digits.len() {
+        11 if digits.starts_with('1') => &digits[1..],
+        _ => digits.as_str(),
+    };
+    if digits.len() != 10 {
+        return Err(Error::merge_field(format!(
+            "{value} is not a US phone number for {}",
+            field.tag
+        )));
+    }
+    Ok(format!(
+        "{}-{}-{}",
+        &digits[0..3],
+        &digits[3..6],
+        &digits[6..]
+    ))
+}
+
+/// Url fields only take absolute http(s) urls
+fn validate_url(value: &str, field: &MergeField) -> Result<String> {
+    let url = url::Url::parse(value.trim())?;
+    match url.scheme() {
+        "http" | "https" => Ok(url.to_string()),
+        scheme => Err(Error::merge_field(format!(
+            "{scheme} urls are not supported for {}",
+            field.tag
+        ))),
+    }
+}
+
+/// Radio and dropdown fields only take one of their choices
+fn validate_choice(value: &str, field: &MergeField) -> Result<String> {
+    let choices = field.options.choices.as_deref().unwrap_or_default();
+    if choices.iter().any(|choice| choice == value) {
+        Ok(value.to_string())
+    } else {
+        Err(Error::merge_field(format!(
+            "{value} is not a choice of {}",
+            field.tag
+        )))
+    }
+}
+
+/// Values a field can't take are left out with a warning, so a single bad
+/// value doesn't fail the whole sync. Only fields of a type that takes no
+/// text are an error.
+impl ToMergeFieldValue for &str {
+    fn to_merge_field_value(self, field: &MergeField) -> Result<Option<MergeFieldValue>> {
+        let value = match field.r#type {
+            MergeType::Text => Ok(self.to_string()),
+            MergeType::Phone | MergeType::Url | MergeType::ImageUrl if self.trim().is_empty() => {
+                return Ok(None);
+            }
+            MergeType::Phone => format_phone(self, field),
+            MergeType::Url | MergeType::ImageUrl => validate_url(self, field),
+            MergeType::Radio | MergeType::Dropdown => validate_choice(self, field),
+            _ => return Err(Error::InvalidMergeType(field.r#type.to_string())),
+        };
+        match value {
+            Ok(value) => Ok(Some((field.tag.clone(), value.into()))),
+            Err(err) => {
+                tracing::warn!(tag = field.tag, %err, "leaving out invalid merge field value");
+                Ok(None)
+            }
+        }
+    }
+}
+
 impl ToMergeFieldValue for &String {
     fn to_merge_field_value(self, field: &MergeField) -> Result<Option<MergeFieldValue>> {
         self.as_str().to_merge_field_value(field)
@@ -383,7 +679,12 @@ paged_query_impl!(
         "merge_fields.merge_id",
         "merge_fields.tag",
         "merge_fields.name",
-        "merge_fields.type"
+        "merge_fields.type",
+        "merge_fields.required",
+        "merge_fields.public",
+        "merge_fields.default_value",
+        "merge_fields.display_order",
+        "merge_fields.options"
     ]
 );
 paged_response_impl!(MergeFieldsResponse, merge_fields, MergeField);
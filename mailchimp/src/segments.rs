@@ -0,0 +1,266 @@
+use crate::{
+    Client, NO_QUERY, Result, Stream, deserialize_null_string,
+    error::Error,
+    members::{Member, MembersQuery, MembersResponse},
+    paged_query_impl, paged_response_impl,
+};
+use serde::{Deserialize, Serialize};
+
+/// All segments of a list. Use the query's `type` to restrict the results to
+/// static or saved segments.
+pub fn all(client: &Client, list_id: &str, query: SegmentsQuery) -> Stream<Segment> {
+    client.fetch_stream::<SegmentsQuery, SegmentsResponse>(
+        &format!("/3.0/lists/{list_id}/segments"),
+        query,
+    )
+}
+
+pub async fn get(client: &Client, list_id: &str, segment_id: u64) -> Result<Segment> {
+    client
+        .fetch(
+            &format!("/3.0/lists/{list_id}/segments/{segment_id}"),
+            NO_QUERY,
+        )
+        .await
+}
+
+/// Create a segment. A segment with `static_segment` set creates a static
+/// segment (a tag) containing the given email addresses, one with `options`
+/// creates a saved segment matching the given conditions.
+pub async fn create(client: &Client, list_id: &str, segment: &NewSegment) -> Result<Segment> {
+    client
+        .post(&format!("/3.0/lists/{list_id}/segments"), segment)
+        .await
+}
+
+/// Update the name, static members or conditions of a segment
+pub async fn update(
+    client: &Client,
+    list_id: &str,
+    segment_id: u64,
+    segment: &NewSegment,
+) -> Result<Segment> {
+    client
+        .patch(
+            &format!("/3.0/lists/{list_id}/segments/{segment_id}"),
+            segment,
+        )
+        .await
+}
+
+/// Replace the conditions of a saved segment
+pub async fn update_conditions(
+    client: &Client,
+    list_id: &str,
+    segment_id: u64,
+    options: SegmentOptions,
+) -> Result<Segment> {
+    let current = get(client, list_id, segment_id).await?;
+    let segment = NewSegment {
+        name: current.name,
+        options: Some(options),
+        ..Default::default()
+    };
+    update(client, list_id, segment_id, &segment).await
+}
+
+pub async fn delete(client: &Client, list_id: &str, segment_id: u64) -> Result<()> {
+    client
+        .delete(&format!("/3.0/lists/{list_id}/segments/{segment_id}"))
+        .await
+}
+
+/// Add and remove members of a static segment in bulk, by email address
+pub async fn update_members(
+    client: &Client,
+    list_id: &str,
+    segment_id: u64,
+    add: &[String],
+    remove: &[String],
+) -> Result<SegmentMembersUpdateResponse> {
+    #[derive(Serialize)]
+    struct SegmentMembersUpdate<'a> {
+        members_to_add: &'a [String],
+        members_to_remove: &'a [String],
+    }
+    client
+        .post(
+            &format!("/3.0/lists/{list_id}/segments/{segment_id}"),
+            &SegmentMembersUpdate {
+                members_to_add: add,
+                members_to_remove: remove,
+            },
+        )
+        .await
+}
+
+/// All members of a segment
+pub fn members(
+    client: &Client,
+    list_id: &str,
+    segment_id: u64,
+    query: MembersQuery,
+) -> Stream<Member> {
+    client.fetch_stream::<MembersQuery, MembersResponse>(
+        &format!("/3.0/lists/{list_id}/segments/{segment_id}/members"),
+        query,
+    )
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentType {
+    Saved,
+    Static,
+    Fuzzy,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentMatch {
+    #[default]
+    All,
+    Any,
+}
+
+impl std::str::FromStr for SegmentType {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(&format!("\"{s}\"")).map_err(|_| Error::value(s.into()))
+    }
+}
+
+impl std::str::FromStr for SegmentMatch {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(&format!("\"{s}\"")).map_err(|_| Error::value(s.into()))
+    }
+}
+
+/// A single condition of a saved segment, like
+/// `{ "condition_type": "TextMerge", "field": "CLUB", "op": "is", "value": "..." }`
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SegmentCondition {
+    pub condition_type: String,
+    pub field: String,
+    pub op: String,
+    #[serde(default)]
+    pub value: serde_json::Value,
+}
+
+impl SegmentCondition {
+    /// A condition matching contacts whose merge field equals the given value
+    pub fn merge_field_is<V: Into<serde_json::Value>>(tag: &str, value: V) -> Self {
+        Self {
+            condition_type: "TextMerge".to_string(),
+            field: tag.to_string(),
+            op: "is".to_string(),
+            value: value.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct SegmentOptions {
+    #[serde(default, rename = "match")]
+    pub r#match: SegmentMatch,
+    #[serde(default)]
+    pub conditions: Vec<SegmentCondition>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Segment {
+    pub id: u64,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub name: String,
+    #[serde(default)]
+    pub member_count: u64,
+    pub r#type: SegmentType,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub created_at: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub updated_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<SegmentOptions>,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub list_id: String,
+}
+
+/// The request body to create or update a segment
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct NewSegment {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub static_segment: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<SegmentOptions>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SegmentMembersUpdateResponse {
+    #[serde(default)]
+    pub members_added: Vec<Member>,
+    #[serde(default)]
+    pub members_removed: Vec<Member>,
+    #[serde(default)]
+    pub errors: Vec<SegmentMembersUpdateError>,
+    #[serde(default)]
+    pub total_added: u64,
+    #[serde(default)]
+    pub total_removed: u64,
+    #[serde(default)]
+    pub error_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SegmentMembersUpdateError {
+    #[serde(default)]
+    pub email_addresses: Vec<String>,
+    #[serde(default)]
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SegmentsQuery {
+    pub fields: String,
+    pub count: usize,
+    pub offset: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<SegmentType>,
+}
+
+impl Default for SegmentsQuery {
+    fn default() -> Self {
+        use crate::PagedQuery;
+        Self {
+            fields: Self::default_fields().join(","),
+            count: crate::DEFAULT_QUERY_COUNT,
+            offset: 0,
+            r#type: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SegmentsResponse {
+    pub segments: Vec<Segment>,
+}
+
+paged_query_impl!(SegmentsQuery, &[]);
+paged_response_impl!(SegmentsResponse, segments, Segment);
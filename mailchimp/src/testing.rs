@@ -0,0 +1,1720 @@
+//! An in-process stand-in for the Mailchimp marketing API.
+//!
+//! The server keeps lists, members, merge fields, tags and batches in memory
+//! and implements enough of the API used by this crate to run syncs
+//! end-to-end without talking to Mailchimp. Resources are stored as JSON and
+//! updates are merged into them the way the real API merges partial updates.
+//!
+//! Batches are executed as soon as they are submitted and always report as
+//! `finished`. Campaigns are never delivered: sending one records the
+//! addresses it would have gone to, see [`Server::deliveries`].
+
+use crate::{
+    Client, batches::BatchInfo, campaigns::Campaign, members::Member, merge_fields::MergeField,
+    webhooks::Webhook,
+};
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{
+        HeaderMap, Method, StatusCode, Uri,
+        header::{AUTHORIZATION, RETRY_AFTER},
+    },
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    net::SocketAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+/// The API key accepted by the stand-in server. The `test` suffix takes the
+/// place of the datacenter.
+pub const API_KEY: &str = "00000000000000000000000000000000-test";
+
+/// Merge fields Mailchimp creates for every new audience
+const DEFAULT_MERGE_FIELDS: &[(&str, &str, &str)] = &[
+    ("FNAME", "First Name", "text"),
+    ("LNAME", "Last Name", "text"),
+    ("ADDRESS", "Address", "address"),
+    ("PHONE", "Phone Number", "phone"),
+    ("BIRTHDAY", "Birthday", "birthday"),
+];
+
+/// Default page size for collection endpoints when no `count` is given
+const DEFAULT_COUNT: usize = 10;
+
+type SharedState = Arc<Shared>;
+type Reply = (StatusCode, Option<Value>);
+
+/// A running stand-in server bound to a local port.
+///
+/// The server is shut down when dropped.
+pub struct Server {
+    addr: SocketAddr,
+    state: SharedState,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Server {
+    /// Start a server on a random local port
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = SharedState::default();
+        state.inner.lock().unwrap().endpoint = format!("http://{addr}");
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let task = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Ok(Self { addr, state, task })
+    }
+
+    /// The base URL of the server
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A client that talks to this server using [`API_KEY`]
+    pub fn client(&self) -> Client {
+        crate::client::from_api_key_with_endpoint(API_KEY, &self.endpoint())
+            .expect("valid stand-in endpoint")
+    }
+
+    /// Create an audience with the given name and return its id
+    pub fn create_list(&self, name: &str) -> String {
+        self.state
+            .inner
+            .lock()
+            .unwrap()
+            .create_list(json!({ "name": name }))
+    }
+
+    /// All members of the given list, including unsubscribed, cleaned and
+    /// archived contacts
+    pub fn members(&self, list_id: &str) -> Vec<Member> {
+        self.with_list(list_id, |list| {
+            list.members.values().cloned().map(from_value).collect()
+        })
+    }
+
+    /// A single member of the given list
+    pub fn member(&self, list_id: &str, member_id: &str) -> Option<Member> {
+        self.with_list(list_id, |list| {
+            list.members.get(member_id).cloned().map(from_value)
+        })
+    }
+
+    /// Insert a member as-is, bypassing validation. Useful to seed an
+    /// audience with contacts in a given state, like `cleaned`.
+    pub fn insert_member(&self, list_id: &str, member: &Member) {
+        let mut value = serde_json::to_value(member).expect("serializable member");
+        let id = crate::members::member_id(&member.email_address);
+        value["id"] = id.clone().into();
+        self.with_list(list_id, |list| list.members.insert(id, value));
+    }
+
+    /// Unsubscribe a member as if it opted out itself. Like Mailchimp, the
+    /// API refuses to subscribe such members again.
+    pub fn opt_out(&self, list_id: &str, member_id: &str) {
+        self.with_list(list_id, |list| {
+            if let Some(member) = list.members.get_mut(member_id) {
+                member["status"] = "unsubscribed".into();
+                list.opted_out.insert(member_id.to_string());
+            }
+        })
+    }
+
+    /// The names of the tags currently active on a member
+    pub fn tags(&self, list_id: &str, member_id: &str) -> Vec<String> {
+        self.with_list(list_id, |list| {
+            list.members
+                .get(member_id)
+                .map(member_tags)
+                .unwrap_or_default()
+        })
+    }
+
+    /// The merge fields of the given list
+    pub fn merge_fields(&self, list_id: &str) -> Vec<MergeField> {
+        self.with_list(list_id, |list| {
+            list.merge_fields
+                .values()
+                .cloned()
+                .map(from_value)
+                .collect()
+        })
+    }
+
+    /// The webhooks registered on the given list
+    pub fn webhooks(&self, list_id: &str) -> Vec<Webhook> {
+        self.with_list(list_id, |list| {
+            list.webhooks.values().cloned().map(from_value).collect()
+        })
+    }
+
+    /// All submitted batches
+    pub fn batches(&self) -> Vec<BatchInfo> {
+        let inner = self.state.inner.lock().unwrap();
+        inner.batches.values().cloned().map(from_value).collect()
+    }
+
+    /// A campaign in its current state
+    pub fn campaign(&self, campaign_id: &str) -> Option<Campaign> {
+        let inner = self.state.inner.lock().unwrap();
+        inner
+            .campaigns
+            .get(campaign_id)
+            .map(|campaign| from_value(campaign.campaign.clone()))
+    }
+
+    /// The addresses test emails of a campaign were sent to, in order
+    pub fn test_emails(&self, campaign_id: &str) -> Vec<String> {
+        let inner = self.state.inner.lock().unwrap();
+        inner
+            .campaigns
+            .get(campaign_id)
+            .map(|campaign| campaign.test_emails.clone())
+            .unwrap_or_default()
+    }
+
+    /// The addresses a sent campaign was delivered to
+    pub fn deliveries(&self, campaign_id: &str) -> Vec<String> {
+        let inner = self.state.inner.lock().unwrap();
+        inner
+            .campaigns
+            .get(campaign_id)
+            .map(|campaign| campaign.deliveries.clone())
+            .unwrap_or_default()
+    }
+
+    /// Answer the next `requests` requests with `429 Too Many Requests`,
+    /// asking the client to retry after `retry_after` seconds
+    pub fn rate_limit(&self, requests: usize, retry_after: u64) {
+        *self.state.rate_limit.lock().unwrap() = (requests, retry_after);
+    }
+
+    /// Delay every response by the given duration, to keep requests in
+    /// flight long enough to observe concurrency
+    pub fn set_latency(&self, latency: Duration) {
+        *self.state.latency.lock().unwrap() = latency;
+    }
+
+    /// The highest number of requests that were handled at the same time
+    pub fn peak_connections(&self) -> usize {
+        self.state.peak_connections.load(Ordering::SeqCst)
+    }
+
+    fn with_list<R>(&self, list_id: &str, f: impl FnOnce(&mut FakeList) -> R) -> R {
+        let mut inner = self.state.inner.lock().unwrap();
+        let list = inner
+            .lists
+            .get_mut(list_id)
+            .unwrap_or_else(|| panic!("no such list {list_id}"));
+        f(list)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn from_value<T: serde::de::DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).expect("stored resource matches api type")
+}
+
+#[derive(Default)]
+struct Shared {
+    inner: Mutex<Inner>,
+    rate_limit: Mutex<(usize, u64)>,
+    latency: Mutex<Duration>,
+    connections: AtomicUsize,
+    peak_connections: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Inner {
+    endpoint: String,
+    lists: BTreeMap<String, FakeList>,
+    batches: BTreeMap<String, Value>,
+    batch_results: BTreeMap<String, Vec<Value>>,
+    campaigns: BTreeMap<String, FakeCampaign>,
+    next_id: u64,
+}
+
+struct FakeList {
+    list: Value,
+    members: BTreeMap<String, Value>,
+    merge_fields: BTreeMap<i64, Value>,
+    next_merge_id: i64,
+    segments: BTreeMap<u64, FakeSegment>,
+    next_segment_id: u64,
+    /// Interest categories keyed by id, each with its interests keyed by id
+    interest_categories: BTreeMap<String, (Value, BTreeMap<String, Value>)>,
+    next_interest_id: u64,
+    webhooks: BTreeMap<String, Value>,
+    /// Ids of permanently deleted members, which can't be added again
+    forgotten: BTreeSet<String>,
+    /// Ids of members who unsubscribed themselves, which can't be subscribed
+    /// again
+    opted_out: BTreeSet<String>,
+}
+
+struct FakeCampaign {
+    campaign: Value,
+    content: Value,
+    test_emails: Vec<String>,
+    /// Addresses of the recipients once the campaign was sent
+    deliveries: Vec<String>,
+}
+
+struct FakeSegment {
+    segment: Value,
+    /// Member ids of a static segment
+    members: BTreeSet<String>,
+}
+
+async fn handle(
+    State(state): State<SharedState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let connections = state.connections.fetch_add(1, Ordering::SeqCst) + 1;
+    state
+        .peak_connections
+        .fetch_max(connections, Ordering::SeqCst);
+    let latency = *state.latency.lock().unwrap();
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
+    let response = respond(&state, &method, &uri, &headers, &body);
+    state.connections.fetch_sub(1, Ordering::SeqCst);
+    response
+}
+
+fn respond(
+    state: &Shared,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Response {
+    {
+        let mut rate_limit = state.rate_limit.lock().unwrap();
+        if rate_limit.0 > 0 {
+            rate_limit.0 -= 1;
+            let (status, value) = error(
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too Many Requests",
+                "You have exceeded the limit of 10 simultaneous connections.",
+            );
+            return (
+                status,
+                [(RETRY_AFTER, rate_limit.1.to_string())],
+                axum::Json(value),
+            )
+                .into_response();
+        }
+    }
+
+    // Result archives are served from storage outside the API and don't
+    // require credentials
+    if let Some(file) = uri.path().strip_prefix("/batch-results/") {
+        let inner = state.inner.lock().unwrap();
+        let results = file
+            .strip_suffix(".tar.gz")
+            .and_then(|id| inner.batch_results.get(id));
+        return match results {
+            Some(results) => (StatusCode::OK, results_archive(results)).into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        };
+    }
+
+    let (status, value) = if is_authorized(headers) {
+        let mut inner = state.inner.lock().unwrap();
+        match uri.path().strip_prefix("/3.0") {
+            Some(path) => inner.dispatch(method, path, uri.query().unwrap_or_default(), body),
+            None => not_found(uri.path()),
+        }
+    } else {
+        error(
+            StatusCode::UNAUTHORIZED,
+            "API Key Invalid",
+            "Your API key may be invalid, or you've attempted to access the wrong datacenter.",
+        )
+    };
+    match value {
+        Some(value) => (status, axum::Json(value)).into_response(),
+        None => status.into_response(),
+    }
+}
+
+/// Pack operation results into a gzipped tarball the way Mailchimp does
+fn results_archive(results: &[Value]) -> Vec<u8> {
+    let json = serde_json::to_vec(results).expect("serializable results");
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    let encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+    archive
+        .append_data(&mut header, "results.json", json.as_slice())
+        .and_then(|_| archive.into_inner())
+        .and_then(|encoder| encoder.finish())
+        .expect("in-memory archive")
+}
+
+fn is_authorized(headers: &HeaderMap) -> bool {
+    use base64::Engine;
+    let expected = format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD.encode(format!("username:{API_KEY}"))
+    );
+    headers
+        .get(AUTHORIZATION)
+        .is_some_and(|value| value.as_bytes() == expected.as_bytes())
+}
+
+fn error(status: StatusCode, title: &str, detail: &str) -> Reply {
+    (
+        status,
+        Some(json!({
+            "type": "https://mailchimp.com/developer/marketing/docs/errors/",
+            "title": title,
+            "status": status.as_u16(),
+            "detail": detail,
+            "instance": "00000000-0000-0000-0000-000000000000",
+        })),
+    )
+}
+
+fn not_found(path: &str) -> Reply {
+    error(
+        StatusCode::NOT_FOUND,
+        "Resource Not Found",
+        &format!("The requested resource could not be found: {path}"),
+    )
+}
+
+fn invalid(detail: &str) -> Reply {
+    error(StatusCode::BAD_REQUEST, "Invalid Resource", detail)
+}
+
+fn ok(value: Value) -> Reply {
+    (StatusCode::OK, Some(value))
+}
+
+fn no_content() -> Reply {
+    (StatusCode::NO_CONTENT, None)
+}
+
+/// Merge `patch` into `target`. Objects are merged key by key, everything
+/// else is replaced.
+fn merge(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect()
+}
+
+fn page(items: Vec<Value>, query: &HashMap<String, String>) -> Vec<Value> {
+    let offset = query
+        .get("offset")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let count = query
+        .get("count")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_COUNT);
+    items.into_iter().skip(offset).take(count).collect()
+}
+
+fn parse_body(body: &[u8]) -> std::result::Result<Value, Reply> {
+    if body.is_empty() {
+        return Ok(Value::Object(Map::new()));
+    }
+    serde_json::from_slice(body).map_err(|err| invalid(&format!("invalid json: {err}")))
+}
+
+fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && domain.contains('.'),
+        None => false,
+    }
+}
+
+fn member_tags(member: &Value) -> Vec<String> {
+    member["tags"]
+        .as_array()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|tag| tag["name"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+macro_rules! try_reply {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(reply) => return reply,
+        }
+    };
+}
+
+impl Inner {
+    fn dispatch(&mut self, method: &Method, path: &str, query: &str, body: &[u8]) -> Reply {
+        let query = parse_query(query);
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (method.clone(), segments.as_slice()) {
+            (Method::GET, ["ping"]) => ok(json!({ "health_status": "Everything's Chimpy!" })),
+
+            (Method::GET, ["lists"]) => {
+                let lists = self.lists.values().map(FakeList::info).collect();
+                let total = self.lists.len();
+                ok(json!({ "lists": page(lists, &query), "total_items": total }))
+            }
+            (Method::POST, ["lists"]) => {
+                let body = try_reply!(parse_body(body));
+                if body["name"].as_str().unwrap_or_default().is_empty() {
+                    return invalid("list name is required");
+                }
+                let id = self.create_list(body);
+                ok(self.lists[&id].info())
+            }
+            (Method::GET, ["lists", list_id]) => {
+                let list = try_reply!(self.list(list_id));
+                ok(list.info())
+            }
+            (Method::PATCH, ["lists", list_id]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                merge(&mut list.list, body);
+                ok(list.info())
+            }
+            (Method::DELETE, ["lists", list_id]) => match self.lists.remove(*list_id) {
+                Some(_) => no_content(),
+                None => not_found(path),
+            },
+            (Method::POST, ["lists", list_id]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                list.batch_upsert(body)
+            }
+
+            (Method::GET, ["lists", list_id, "members"]) => {
+                let list = try_reply!(self.list(list_id));
+                let members: Vec<Value> = list
+                    .members
+                    .values()
+                    .filter(|member| match query.get("status") {
+                        Some(status) => member["status"] == status.as_str(),
+                        None => member["status"] != "archived",
+                    })
+                    .cloned()
+                    .collect();
+                let total = members.len();
+                ok(json!({
+                    "members": page(members, &query),
+                    "list_id": list_id,
+                    "total_items": total,
+                }))
+            }
+            (Method::GET, ["lists", list_id, "members", member_id]) => {
+                let list = try_reply!(self.list(list_id));
+                let member = try_reply!(list.member(member_id, path));
+                ok(member.clone())
+            }
+            (Method::PUT, ["lists", list_id, "members", member_id]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                let (member, _) = try_reply!(list.upsert_member(member_id, body));
+                ok(member)
+            }
+            (Method::PATCH, ["lists", list_id, "members", member_id]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                try_reply!(list.member(member_id, path));
+                let (member, _) = try_reply!(list.upsert_member(member_id, body));
+                ok(member)
+            }
+            // Deleting a member only archives it
+            (Method::DELETE, ["lists", list_id, "members", member_id]) => {
+                let list = try_reply!(self.list(list_id));
+                let member = try_reply!(list.member(member_id, path));
+                member["status"] = "archived".into();
+                no_content()
+            }
+            (
+                Method::POST,
+                [
+                    "lists",
+                    list_id,
+                    "members",
+                    member_id,
+                    "actions",
+                    "delete-permanent",
+                ],
+            ) => {
+                let list = try_reply!(self.list(list_id));
+                match list.members.remove(*member_id) {
+                    Some(_) => {
+                        list.forgotten.insert(member_id.to_string());
+                        no_content()
+                    }
+                    None => not_found(path),
+                }
+            }
+            (Method::GET, ["lists", list_id, "members", member_id, "tags"]) => {
+                let list = try_reply!(self.list(list_id));
+                let member = try_reply!(list.member(member_id, path));
+                let tags = member_tags(member)
+                    .into_iter()
+                    .map(|name| json!({ "name": name }))
+                    .collect::<Vec<_>>();
+                let total = tags.len();
+                ok(json!({ "tags": tags, "total_items": total }))
+            }
+            (Method::POST, ["lists", list_id, "members", member_id, "tags"]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                let member = try_reply!(list.member(member_id, path));
+                try_reply!(update_tags(member, body));
+                no_content()
+            }
+
+            (Method::GET, ["lists", list_id, "merge-fields"]) => {
+                let list = try_reply!(self.list(list_id));
+                let fields = list.merge_fields.values().cloned().collect();
+                let total = list.merge_fields.len();
+                ok(json!({
+                    "merge_fields": page(fields, &query),
+                    "list_id": list_id,
+                    "total_items": total,
+                }))
+            }
+            (Method::POST, ["lists", list_id, "merge-fields"]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                list.create_merge_field(body)
+            }
+            (Method::GET, ["lists", list_id, "merge-fields", merge_id]) => {
+                let list = try_reply!(self.list(list_id));
+                let field = try_reply!(list.merge_field(merge_id, path));
+                ok(field.clone())
+            }
+            (Method::PATCH, ["lists", list_id, "merge-fields", merge_id]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                list.update_merge_field(merge_id, body, path)
+            }
+            (Method::DELETE, ["lists", list_id, "merge-fields", merge_id]) => {
+                let list = try_reply!(self.list(list_id));
+                let merge_id = merge_id.parse::<i64>().unwrap_or_default();
+                match list.merge_fields.remove(&merge_id) {
+                    Some(_) => no_content(),
+                    None => not_found(path),
+                }
+            }
+
+            (Method::GET, ["lists", list_id, "segments"]) => {
+                let list = try_reply!(self.list(list_id));
+                let segments: Vec<Value> = list
+                    .segments
+                    .values()
+                    .filter(|segment| match query.get("type") {
+                        Some(r#type) => segment.segment["type"] == r#type.as_str(),
+                        None => true,
+                    })
+                    .map(|segment| list.segment_info(segment))
+                    .collect();
+                let total = segments.len();
+                ok(json!({
+                    "segments": page(segments, &query),
+                    "list_id": list_id,
+                    "total_items": total,
+                }))
+            }
+            (Method::POST, ["lists", list_id, "segments"]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                list.create_segment(list_id, body)
+            }
+            (Method::GET, ["lists", list_id, "segments", segment_id]) => {
+                let list = try_reply!(self.list(list_id));
+                let segment = try_reply!(list.segment(segment_id, path));
+                ok(list.segment_info(segment))
+            }
+            (Method::PATCH, ["lists", list_id, "segments", segment_id]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                list.update_segment(segment_id, body, path)
+            }
+            (Method::DELETE, ["lists", list_id, "segments", segment_id]) => {
+                let list = try_reply!(self.list(list_id));
+                let removed = segment_id
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|id| list.segments.remove(&id));
+                match removed {
+                    Some(_) => no_content(),
+                    None => not_found(path),
+                }
+            }
+            (Method::POST, ["lists", list_id, "segments", segment_id]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                list.update_segment_members(segment_id, body, path)
+            }
+            (Method::GET, ["lists", list_id, "segments", segment_id, "members"]) => {
+                let list = try_reply!(self.list(list_id));
+                let segment = try_reply!(list.segment(segment_id, path));
+                let members: Vec<Value> = list.segment_members(segment).cloned().collect();
+                let total = members.len();
+                ok(json!({ "members": page(members, &query), "total_items": total }))
+            }
+
+            (Method::GET, ["lists", list_id, "interest-categories"]) => {
+                let list = try_reply!(self.list(list_id));
+                let categories: Vec<Value> = list
+                    .interest_categories
+                    .values()
+                    .map(|(category, _)| category.clone())
+                    .collect();
+                let total = categories.len();
+                ok(json!({
+                    "categories": page(categories, &query),
+                    "list_id": list_id,
+                    "total_items": total,
+                }))
+            }
+            (Method::POST, ["lists", list_id, "interest-categories"]) => {
+                let mut body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                let title = body["title"].as_str().unwrap_or_default();
+                if title.is_empty() {
+                    return invalid("interest category title is required");
+                }
+                let taken = list
+                    .interest_categories
+                    .values()
+                    .any(|(category, _)| category["title"] == title);
+                if taken {
+                    return invalid(&format!(
+                        "an interest category titled {title} already exists"
+                    ));
+                }
+                let id = list.next_interest_id();
+                body["id"] = id.clone().into();
+                body["list_id"] = (*list_id).into();
+                if body["type"].is_null() {
+                    body["type"] = "checkboxes".into();
+                }
+                list.interest_categories
+                    .insert(id, (body.clone(), BTreeMap::new()));
+                ok(body)
+            }
+            (Method::GET, ["lists", list_id, "interest-categories", category_id]) => {
+                let list = try_reply!(self.list(list_id));
+                let (category, _) = try_reply!(list.interest_category(category_id, path));
+                ok(category.clone())
+            }
+            (Method::PATCH, ["lists", list_id, "interest-categories", category_id]) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                let (category, _) = try_reply!(list.interest_category(category_id, path));
+                merge(category, body);
+                ok(category.clone())
+            }
+            (Method::DELETE, ["lists", list_id, "interest-categories", category_id]) => {
+                let list = try_reply!(self.list(list_id));
+                match list.interest_categories.remove(*category_id) {
+                    Some(_) => no_content(),
+                    None => not_found(path),
+                }
+            }
+            (
+                Method::GET,
+                [
+                    "lists",
+                    list_id,
+                    "interest-categories",
+                    category_id,
+                    "interests",
+                ],
+            ) => {
+                let list = try_reply!(self.list(list_id));
+                let (_, interests) = try_reply!(list.interest_category(category_id, path));
+                let interests: Vec<Value> = interests.values().cloned().collect();
+                let total = interests.len();
+                ok(json!({ "interests": page(interests, &query), "total_items": total }))
+            }
+            (
+                Method::POST,
+                [
+                    "lists",
+                    list_id,
+                    "interest-categories",
+                    category_id,
+                    "interests",
+                ],
+            ) => {
+                let mut body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                let id = list.next_interest_id();
+                let (_, interests) = try_reply!(list.interest_category(category_id, path));
+                let name = body["name"].as_str().unwrap_or_default();
+                if name.is_empty() {
+                    return invalid("interest name is required");
+                }
+                if interests.values().any(|interest| interest["name"] == name) {
+                    return invalid(&format!("an interest named {name} already exists"));
+                }
+                body["id"] = id.clone().into();
+                body["category_id"] = (*category_id).into();
+                body["list_id"] = (*list_id).into();
+                interests.insert(id, body.clone());
+                ok(body)
+            }
+            (
+                method,
+                [
+                    "lists",
+                    list_id,
+                    "interest-categories",
+                    category_id,
+                    "interests",
+                    interest_id,
+                ],
+            ) => {
+                let body = try_reply!(parse_body(body));
+                let list = try_reply!(self.list(list_id));
+                let (_, interests) = try_reply!(list.interest_category(category_id, path));
+                match method {
+                    Method::GET => match interests.get(*interest_id) {
+                        Some(interest) => ok(interest.clone()),
+                        None => not_found(path),
+                    },
+                    Method::PATCH => match interests.get_mut(*interest_id) {
+                        Some(interest) => {
+                            merge(interest, body);
+                            ok(interest.clone())
+                        }
+                        None => not_found(path),
+                    },
+                    Method::DELETE => match interests.remove(*interest_id) {
+                        Some(_) => no_content(),
+                        None => not_found(path),
+                    },
+                    _ => not_found(path),
+                }
+            }
+
+            (Method::GET, ["lists", list_id, "webhooks"]) => {
+                let list = try_reply!(self.list(list_id));
+                let webhooks: Vec<Value> = list.webhooks.values().cloned().collect();
+                let total = webhooks.len();
+                ok(json!({
+                    "webhooks": webhooks,
+                    "list_id": list_id,
+                    "total_items": total,
+                }))
+            }
+            (Method::POST, ["lists", list_id, "webhooks"]) => {
+                let mut body = try_reply!(parse_body(body));
+                let id = self.next_id();
+                let list = try_reply!(self.list(list_id));
+                let url = body["url"].as_str().unwrap_or_default();
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    return invalid("The URL must be a valid http or https URL.");
+                }
+                if list.webhooks.values().any(|webhook| webhook["url"] == url) {
+                    return invalid("Sorry, you can't use the same webhook URL more than once.");
+                }
+                body["id"] = id.clone().into();
+                body["list_id"] = (*list_id).into();
+                list.webhooks.insert(id, body.clone());
+                ok(body)
+            }
+            (Method::GET, ["lists", list_id, "webhooks", webhook_id]) => {
+                let list = try_reply!(self.list(list_id));
+                match list.webhooks.get(*webhook_id) {
+                    Some(webhook) => ok(webhook.clone()),
+                    None => not_found(path),
+                }
+            }
+            (Method::DELETE, ["lists", list_id, "webhooks", webhook_id]) => {
+                let list = try_reply!(self.list(list_id));
+                match list.webhooks.remove(*webhook_id) {
+                    Some(_) => no_content(),
+                    None => not_found(path),
+                }
+            }
+
+            (method, ["campaigns", rest @ ..]) => {
+                let body = try_reply!(parse_body(body));
+                self.dispatch_campaign(&method, rest, &query, body, path)
+            }
+
+            (Method::GET, ["batches"]) => {
+                let batches = self.batches.values().cloned().collect();
+                let total = self.batches.len();
+                ok(json!({ "batches": page(batches, &query), "total_items": total }))
+            }
+            (Method::POST, ["batches"]) => {
+                let body = try_reply!(parse_body(body));
+                self.run_batch(body)
+            }
+            (Method::GET, ["batches", batch_id]) => match self.batches.get(*batch_id) {
+                Some(batch) => ok(batch.clone()),
+                None => not_found(path),
+            },
+
+            _ => not_found(path),
+        }
+    }
+
+    fn next_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("{:010x}", self.next_id)
+    }
+
+    fn list(&mut self, list_id: &str) -> std::result::Result<&mut FakeList, Reply> {
+        self.lists
+            .get_mut(list_id)
+            .ok_or_else(|| not_found(&format!("/lists/{list_id}")))
+    }
+
+    fn create_list(&mut self, mut list: Value) -> String {
+        let id = self.next_id();
+        list["id"] = id.clone().into();
+        list["date_created"] = chrono::Utc::now().to_rfc3339().into();
+        let mut fake = FakeList {
+            list,
+            members: BTreeMap::new(),
+            merge_fields: BTreeMap::new(),
+            next_merge_id: 1,
+            segments: BTreeMap::new(),
+            next_segment_id: 1,
+            interest_categories: BTreeMap::new(),
+            next_interest_id: 1,
+            webhooks: BTreeMap::new(),
+            forgotten: BTreeSet::new(),
+            opted_out: BTreeSet::new(),
+        };
+        for (tag, name, r#type) in DEFAULT_MERGE_FIELDS {
+            let _ = fake.create_merge_field(json!({ "tag": tag, "name": name, "type": r#type }));
+        }
+        self.lists.insert(id.clone(), fake);
+        id
+    }
+
+    fn run_batch(&mut self, body: Value) -> Reply {
+        let operations = body["operations"].as_array().cloned().unwrap_or_default();
+        let mut errored = 0;
+        let mut results = vec![];
+        for operation in &operations {
+            let method = operation["method"]
+                .as_str()
+                .and_then(|method| method.parse::<Method>().ok())
+                .unwrap_or(Method::GET);
+            let path = operation["path"].as_str().unwrap_or_default();
+            let query = operation["params"]
+                .as_object()
+                .map(|params| {
+                    let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+                    for (key, value) in params {
+                        match value {
+                            Value::String(value) => serializer.append_pair(key, value),
+                            other => serializer.append_pair(key, &other.to_string()),
+                        };
+                    }
+                    serializer.finish()
+                })
+                .unwrap_or_default();
+            let body = operation["body"].as_str().unwrap_or_default();
+            let (status, response) = self.dispatch(&method, path, &query, body.as_bytes());
+            if status.is_client_error() || status.is_server_error() {
+                errored += 1;
+            }
+            results.push(json!({
+                "status_code": status.as_u16(),
+                "operation_id": operation["operation_id"],
+                "response": response.map(|response| response.to_string()).unwrap_or_default(),
+            }));
+        }
+
+        let id = self.next_id();
+        let now = chrono::Utc::now().to_rfc3339();
+        let batch = json!({
+            "id": id,
+            "status": "finished",
+            "total_operations": operations.len(),
+            "finished_operations": operations.len(),
+            "errored_operations": errored,
+            "submitted_at": now,
+            "completed_at": now,
+            "response_body_url": format!("{}/batch-results/{id}.tar.gz", self.endpoint),
+        });
+        self.batches.insert(id.clone(), batch.clone());
+        self.batch_results.insert(id, results);
+        ok(batch)
+    }
+}
+
+impl Inner {
+    fn dispatch_campaign(
+        &mut self,
+        method: &Method,
+        segments: &[&str],
+        query: &HashMap<String, String>,
+        body: Value,
+        path: &str,
+    ) -> Reply {
+        match (method.clone(), segments) {
+            (Method::GET, []) => {
+                let campaigns: Vec<Value> = self
+                    .campaigns
+                    .values()
+                    .map(|campaign| &campaign.campaign)
+                    .filter(|campaign| match query.get("list_id") {
+                        Some(list_id) => campaign["recipients"]["list_id"] == list_id.as_str(),
+                        None => true,
+                    })
+                    .filter(|campaign| match query.get("status") {
+                        Some(status) => campaign["status"] == status.as_str(),
+                        None => true,
+                    })
+                    .cloned()
+                    .collect();
+                let total = campaigns.len();
+                ok(json!({ "campaigns": page(campaigns, query), "total_items": total }))
+            }
+            (Method::POST, []) => self.create_campaign(body),
+            (Method::GET, [campaign_id]) => {
+                let campaign = try_reply!(self.campaign(campaign_id, path));
+                ok(campaign.campaign.clone())
+            }
+            (Method::PATCH, [campaign_id]) => {
+                let recipients = body["recipients"].clone();
+                if !recipients.is_null() {
+                    try_reply!(self.recipients(&recipients));
+                }
+                let campaign = try_reply!(self.editable_campaign(campaign_id, path));
+                merge(&mut campaign.campaign["settings"], body["settings"].clone());
+                if !recipients.is_null() {
+                    campaign.campaign["recipients"] = recipients;
+                }
+                let campaign_id = campaign_id.to_string();
+                self.update_recipient_count(&campaign_id);
+                ok(self.campaigns[&campaign_id].campaign.clone())
+            }
+            (Method::DELETE, [campaign_id]) => {
+                try_reply!(self.campaign(campaign_id, path));
+                self.campaigns.remove(*campaign_id);
+                no_content()
+            }
+            (Method::GET, [campaign_id, "content"]) => {
+                let campaign = try_reply!(self.campaign(campaign_id, path));
+                ok(campaign.content.clone())
+            }
+            (Method::PUT, [campaign_id, "content"]) => {
+                let html = if let Some(html) = body["html"].as_str() {
+                    html.to_string()
+                } else if let Some(template_id) = body["template"]["id"].as_u64() {
+                    let sections = body["template"]["sections"]
+                        .as_object()
+                        .map(|sections| {
+                            sections
+                                .values()
+                                .filter_map(Value::as_str)
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        })
+                        .unwrap_or_default();
+                    format!("<!-- template {template_id} -->\n{sections}")
+                } else {
+                    return invalid("either html or template content is required");
+                };
+                let campaign = try_reply!(self.editable_campaign(campaign_id, path));
+                campaign.content = json!({
+                    "html": html,
+                    "plain_text": html,
+                });
+                ok(campaign.content.clone())
+            }
+            (Method::GET, [campaign_id, "send-checklist"]) => {
+                let campaign = try_reply!(self.campaign(campaign_id, path));
+                ok(campaign.checklist())
+            }
+            (Method::POST, [campaign_id, "actions", "test"]) => {
+                let emails: Vec<String> = body["test_emails"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect();
+                if emails.is_empty() || !emails.iter().all(|email| is_valid_email(email)) {
+                    return invalid("test_emails must contain valid email addresses");
+                }
+                let campaign = try_reply!(self.campaign(campaign_id, path));
+                if campaign.content["html"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .is_empty()
+                {
+                    return invalid("Your Campaign is not ready to send.");
+                }
+                campaign.test_emails.extend(emails);
+                no_content()
+            }
+            (Method::POST, [campaign_id, "actions", "schedule"]) => {
+                let time = body["schedule_time"]
+                    .as_str()
+                    .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok());
+                let Some(time) = time else {
+                    return invalid("schedule_time must be an ISO 8601 time");
+                };
+                if time.timestamp() % (15 * 60) != 0 {
+                    return invalid(
+                        "Schedule time must be on the quarter-hour (:00, :15, :30, :45).",
+                    );
+                }
+                if time < chrono::Utc::now() {
+                    return invalid("Schedule time must be in the future.");
+                }
+                let campaign = try_reply!(self.ready_campaign(campaign_id, path));
+                campaign.campaign["status"] = "schedule".into();
+                campaign.campaign["send_time"] = time.to_utc().to_rfc3339().into();
+                no_content()
+            }
+            (Method::POST, [campaign_id, "actions", "unschedule"]) => {
+                let campaign = try_reply!(self.campaign(campaign_id, path));
+                if campaign.campaign["status"] != "schedule" {
+                    return invalid("This campaign is not scheduled.");
+                }
+                campaign.campaign["status"] = "save".into();
+                campaign.campaign["send_time"] = "".into();
+                no_content()
+            }
+            (Method::POST, [campaign_id, "actions", "send"]) => {
+                try_reply!(self.ready_campaign(campaign_id, path));
+                let recipients = self.campaigns[*campaign_id].campaign["recipients"].clone();
+                let deliveries = try_reply!(self.recipients(&recipients));
+                let campaign = self.campaigns.get_mut(*campaign_id).unwrap();
+                campaign.campaign["status"] = "sent".into();
+                campaign.campaign["send_time"] = chrono::Utc::now().to_rfc3339().into();
+                campaign.campaign["emails_sent"] = deliveries.len().into();
+                campaign.deliveries = deliveries;
+                no_content()
+            }
+            _ => not_found(path),
+        }
+    }
+
+    fn create_campaign(&mut self, body: Value) -> Reply {
+        let r#type = body["type"].as_str().unwrap_or("regular");
+        if r#type != "regular" {
+            return invalid(&format!("unsupported campaign type {}", r#type));
+        }
+        try_reply!(self.recipients(&body["recipients"]));
+        let id = self.next_id();
+        let campaign = json!({
+            "id": id,
+            "web_id": self.next_id,
+            "type": r#type,
+            "status": "save",
+            "emails_sent": 0,
+            "create_time": chrono::Utc::now().to_rfc3339(),
+            "send_time": "",
+            "recipients": body["recipients"],
+            "settings": body["settings"],
+        });
+        self.campaigns.insert(
+            id.clone(),
+            FakeCampaign {
+                campaign,
+                content: json!({ "html": "", "plain_text": "" }),
+                test_emails: vec![],
+                deliveries: vec![],
+            },
+        );
+        self.update_recipient_count(&id);
+        ok(self.campaigns[&id].campaign.clone())
+    }
+
+    fn campaign(
+        &mut self,
+        campaign_id: &str,
+        path: &str,
+    ) -> std::result::Result<&mut FakeCampaign, Reply> {
+        self.campaigns
+            .get_mut(campaign_id)
+            .ok_or_else(|| not_found(path))
+    }
+
+    /// A campaign that can still be changed, as sent and scheduled campaigns
+    /// can't
+    fn editable_campaign(
+        &mut self,
+        campaign_id: &str,
+        path: &str,
+    ) -> std::result::Result<&mut FakeCampaign, Reply> {
+        let campaign = self.campaign(campaign_id, path)?;
+        match campaign.campaign["status"].as_str() {
+            Some("save") | Some("paused") => Ok(campaign),
+            _ => Err(invalid("This campaign can no longer be edited.")),
+        }
+    }
+
+    /// An editable campaign that passes its send checklist
+    fn ready_campaign(
+        &mut self,
+        campaign_id: &str,
+        path: &str,
+    ) -> std::result::Result<&mut FakeCampaign, Reply> {
+        let campaign = self.editable_campaign(campaign_id, path)?;
+        if campaign.checklist()["is_ready"] != true {
+            return Err(invalid("Your Campaign is not ready to send."));
+        }
+        Ok(campaign)
+    }
+
+    /// The email addresses of the subscribed members the given recipients
+    /// resolve to
+    fn recipients(&mut self, recipients: &Value) -> std::result::Result<Vec<String>, Reply> {
+        let list_id = recipients["list_id"].as_str().unwrap_or_default();
+        if list_id.is_empty() {
+            return Err(invalid("recipients.list_id is required"));
+        }
+        let list = self.list(list_id)?;
+        let members: Vec<&Value> = match recipients["segment_opts"]["saved_segment_id"].as_u64() {
+            Some(segment_id) => {
+                let segment = list.segment(
+                    &segment_id.to_string(),
+                    &format!("/lists/{list_id}/segments/{segment_id}"),
+                )?;
+                list.segment_members(segment).collect()
+            }
+            None => list.members.values().collect(),
+        };
+        Ok(members
+            .into_iter()
+            .filter(|member| member["status"] == "subscribed")
+            .filter_map(|member| member["email_address"].as_str().map(str::to_string))
+            .collect())
+    }
+
+    fn update_recipient_count(&mut self, campaign_id: &str) {
+        let recipients = self.campaigns[campaign_id].campaign["recipients"].clone();
+        let count = self
+            .recipients(&recipients)
+            .map(|r| r.len())
+            .unwrap_or_default();
+        let campaign = self.campaigns.get_mut(campaign_id).unwrap();
+        campaign.campaign["recipients"]["recipient_count"] = count.into();
+    }
+}
+
+impl FakeCampaign {
+    fn checklist(&self) -> Value {
+        let mut items = vec![];
+        let settings = &self.campaign["settings"];
+        for (field, heading) in [
+            ("subject_line", "Subject line"),
+            ("from_name", "From name"),
+            ("reply_to", "Reply-to address"),
+        ] {
+            if settings[field].as_str().unwrap_or_default().is_empty() {
+                items.push(json!({
+                    "type": "error",
+                    "heading": heading,
+                    "details": format!("You need to set a {}.", heading.to_lowercase()),
+                }));
+            }
+        }
+        if self.content["html"].as_str().unwrap_or_default().is_empty() {
+            items.push(json!({
+                "type": "error",
+                "heading": "Content",
+                "details": "You need to add content to the campaign.",
+            }));
+        }
+        json!({ "is_ready": items.is_empty(), "items": items })
+    }
+}
+
+impl FakeList {
+    fn info(&self) -> Value {
+        let count = |status: &str| {
+            self.members
+                .values()
+                .filter(|member| member["status"] == status)
+                .count()
+        };
+        let mut info = self.list.clone();
+        info["stats"] = json!({
+            "member_count": count("subscribed"),
+            "total_contacts": self.members.len() - count("archived"),
+            "unsubscribe_count": count("unsubscribed"),
+            "cleaned_count": count("cleaned"),
+            "member_count_since_send": 0,
+            "unsubscribe_count_since_send": 0,
+            "cleaned_count_since_send": 0,
+            "campaign_count": 0,
+            "campaign_last_sent": "",
+            "merge_field_count": self.merge_fields.len(),
+            "last_sub_date": "",
+            "last_unsub_date": "",
+        });
+        info
+    }
+
+    fn member(&mut self, member_id: &str, path: &str) -> std::result::Result<&mut Value, Reply> {
+        self.members
+            .get_mut(member_id)
+            .ok_or_else(|| not_found(path))
+    }
+
+    /// Create or update the member with the given id. Returns the stored
+    /// member and whether it was newly created.
+    fn upsert_member(
+        &mut self,
+        member_id: &str,
+        mut body: Value,
+    ) -> std::result::Result<(Value, bool), Reply> {
+        let Some(fields) = body.as_object_mut() else {
+            return Err(invalid("member must be an object"));
+        };
+        let status_if_new = fields.remove("status_if_new");
+        fields.remove("id");
+        if let Some(email) = fields.get("email_address").and_then(Value::as_str)
+            && !is_valid_email(email)
+        {
+            return Err(invalid(&format!(
+                "{email} looks fake or invalid, please enter a real email address."
+            )));
+        }
+        if fields.get("status").is_some_and(|status| status == "noop") {
+            fields.remove("status");
+        }
+
+        if self.forgotten.contains(member_id) {
+            return Err(invalid(
+                "This contact was permanently deleted and cannot be re-imported.",
+            ));
+        }
+        if self.opted_out.contains(member_id)
+            && fields
+                .get("status")
+                .is_some_and(|status| status == "subscribed")
+        {
+            return Err(invalid(
+                "This contact is in a compliance state due to unsubscribe and cannot be subscribed.",
+            ));
+        }
+        // Archived members are restored as if they were new
+        let archived = self
+            .members
+            .get(member_id)
+            .is_some_and(|member| member["status"] == "archived");
+        if archived && !fields.contains_key("status") {
+            let status = status_if_new.clone().unwrap_or_else(|| "subscribed".into());
+            fields.insert("status".into(), status);
+        }
+        let created = archived || !self.members.contains_key(member_id);
+        if !self.members.contains_key(member_id) {
+            let Some(email) = fields.get("email_address").and_then(Value::as_str) else {
+                return Err(invalid("email_address is required for new members"));
+            };
+            if crate::members::member_id(email) != member_id {
+                return Err(invalid("member id does not match email address"));
+            }
+            if !fields.contains_key("status") {
+                let status = status_if_new.unwrap_or_else(|| "subscribed".into());
+                fields.insert("status".into(), status);
+            }
+            self.members.insert(
+                member_id.to_string(),
+                json!({ "id": member_id, "tags": [], "tags_count": 0 }),
+            );
+        }
+
+        let member = self.members.get_mut(member_id).unwrap();
+        merge(member, body);
+        let name = |tag: &str| member["merge_fields"][tag].as_str().unwrap_or_default();
+        let full_name = format!("{} {}", name("FNAME"), name("LNAME"));
+        member["full_name"] = full_name.trim().into();
+        Ok((member.clone(), created))
+    }
+
+    fn batch_upsert(&mut self, body: Value) -> Reply {
+        let update_existing = body["update_existing"].as_bool().unwrap_or(false);
+        let members = body["members"].as_array().cloned().unwrap_or_default();
+        let mut new_members = vec![];
+        let mut updated_members = vec![];
+        let mut errors = vec![];
+        for member in members {
+            let email = member["email_address"].as_str().unwrap_or_default();
+            let member_id = crate::members::member_id(email);
+            if self.members.contains_key(&member_id) && !update_existing {
+                errors.push(json!({
+                    "email_address": email,
+                    "error": format!("{email} is already a list member"),
+                    "error_code": "ERROR_CONTACT_EXISTS",
+                }));
+                continue;
+            }
+            match self.upsert_member(&member_id, member.clone()) {
+                Ok((member, true)) => new_members.push(member),
+                Ok((member, false)) => updated_members.push(member),
+                Err((_, detail)) => errors.push(json!({
+                    "email_address": email,
+                    "error": detail.map(|detail| detail["detail"].clone()).unwrap_or_default(),
+                    "error_code": "ERROR_GENERIC",
+                })),
+            }
+        }
+        ok(json!({
+            "total_created": new_members.len(),
+            "total_updated": updated_members.len(),
+            "error_count": errors.len(),
+            "new_members": new_members,
+            "updated_members": updated_members,
+            "errors": errors,
+        }))
+    }
+
+    fn merge_field(
+        &mut self,
+        merge_id: &str,
+        path: &str,
+    ) -> std::result::Result<&mut Value, Reply> {
+        merge_id
+            .parse::<i64>()
+            .ok()
+            .and_then(|merge_id| self.merge_fields.get_mut(&merge_id))
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn validate_merge_tag(&self, tag: &str, merge_id: i64) -> std::result::Result<(), Reply> {
+        if tag.is_empty() || tag.len() > 10 {
+            return Err(invalid(&format!("invalid merge field tag: {tag}")));
+        }
+        let taken = self
+            .merge_fields
+            .iter()
+            .any(|(id, field)| *id != merge_id && field["tag"] == tag);
+        if taken {
+            return Err(invalid(&format!(
+                "a merge field with tag {tag} already exists"
+            )));
+        }
+        Ok(())
+    }
+
+    fn create_merge_field(&mut self, mut body: Value) -> Reply {
+        let tag = body["tag"].as_str().unwrap_or_default().to_uppercase();
+        try_reply!(self.validate_merge_tag(&tag, 0));
+        if body["name"].as_str().unwrap_or_default().is_empty() {
+            return invalid("merge field name is required");
+        }
+        let merge_id = self.next_merge_id;
+        self.next_merge_id += 1;
+        if body["type"].is_null() {
+            body["type"] = "text".into();
+        }
+        let r#type = body["type"].as_str().unwrap_or_default().to_string();
+        let choices = body["options"]["choices"].as_array().map(Vec::len);
+        if matches!(r#type.as_str(), "radio" | "dropdown") && choices.unwrap_or_default() == 0 {
+            return invalid("choices are required for radio and dropdown fields");
+        }
+        body["tag"] = tag.into();
+        body["merge_id"] = merge_id.into();
+        // Mailchimp fills in defaults for everything left out
+        let display_order = self.merge_fields.len() + 2;
+        let defaults = [
+            ("required", json!(false)),
+            ("public", json!(false)),
+            ("default_value", json!("")),
+            ("display_order", json!(display_order)),
+        ];
+        for (key, value) in defaults {
+            if body[key].is_null() {
+                body[key] = value;
+            }
+        }
+        let mut options = match r#type.as_str() {
+            "text" => json!({ "size": 25 }),
+            "date" => json!({ "date_format": "MM/DD/YYYY" }),
+            "birthday" => json!({ "date_format": "MM/DD" }),
+            "address" => json!({ "default_country": 164 }),
+            _ => json!({}),
+        };
+        if body["options"].is_object() {
+            merge(&mut options, body["options"].take());
+        }
+        body["options"] = options;
+        self.merge_fields.insert(merge_id, body.clone());
+        ok(body)
+    }
+
+    fn update_merge_field(&mut self, merge_id: &str, mut body: Value, path: &str) -> Reply {
+        let id = try_reply!(self.merge_field(merge_id, path))["merge_id"]
+            .as_i64()
+            .unwrap_or_default();
+        if let Some(tag) = body["tag"].as_str() {
+            let tag = tag.to_uppercase();
+            try_reply!(self.validate_merge_tag(&tag, id));
+            body["tag"] = tag.into();
+        }
+        // The merge id and type of a field can not be changed
+        if let Some(fields) = body.as_object_mut() {
+            fields.remove("merge_id");
+            fields.remove("type");
+        }
+        let field = self.merge_fields.get_mut(&id).unwrap();
+        merge(field, body);
+        ok(field.clone())
+    }
+}
+
+impl FakeList {
+    fn next_interest_id(&mut self) -> String {
+        self.next_interest_id += 1;
+        format!("{:010x}", self.next_interest_id)
+    }
+
+    fn interest_category(
+        &mut self,
+        category_id: &str,
+        path: &str,
+    ) -> std::result::Result<&mut (Value, BTreeMap<String, Value>), Reply> {
+        self.interest_categories
+            .get_mut(category_id)
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn segment(&self, segment_id: &str, path: &str) -> std::result::Result<&FakeSegment, Reply> {
+        segment_id
+            .parse::<u64>()
+            .ok()
+            .and_then(|segment_id| self.segments.get(&segment_id))
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn segment_info(&self, segment: &FakeSegment) -> Value {
+        let mut info = segment.segment.clone();
+        info["member_count"] = self.segment_members(segment).count().into();
+        info
+    }
+
+    /// The members of a static segment, or the members matching the
+    /// conditions of a saved segment
+    fn segment_members<'a>(&'a self, segment: &'a FakeSegment) -> impl Iterator<Item = &'a Value> {
+        let options = &segment.segment["options"];
+        let is_static = segment.segment["type"] == "static";
+        self.members.iter().filter_map(move |(id, member)| {
+            let included = if is_static {
+                segment.members.contains(id)
+            } else {
+                matches_options(member, options)
+            };
+            included.then_some(member)
+        })
+    }
+
+    /// Member ids for the given email addresses, split into known ids and
+    /// unknown addresses
+    fn member_ids(&self, emails: &Value) -> (Vec<String>, Vec<String>) {
+        let mut known = vec![];
+        let mut unknown = vec![];
+        for email in emails.as_array().into_iter().flatten() {
+            let email = email.as_str().unwrap_or_default();
+            let id = crate::members::member_id(email);
+            if self.members.contains_key(&id) {
+                known.push(id);
+            } else {
+                unknown.push(email.to_string());
+            }
+        }
+        (known, unknown)
+    }
+
+    fn create_segment(&mut self, list_id: &str, body: Value) -> Reply {
+        if body["name"].as_str().unwrap_or_default().is_empty() {
+            return invalid("segment name is required");
+        }
+        let id = self.next_segment_id;
+        self.next_segment_id += 1;
+        let now = chrono::Utc::now().to_rfc3339();
+        let r#type = if body["options"].is_object() {
+            "saved"
+        } else {
+            "static"
+        };
+        let (members, _) = self.member_ids(&body["static_segment"]);
+        let mut segment = json!({
+            "id": id,
+            "name": body["name"],
+            "type": r#type,
+            "created_at": now,
+            "updated_at": now,
+            "list_id": list_id,
+        });
+        if r#type == "saved" {
+            segment["options"] = body["options"].clone();
+        }
+        let segment = FakeSegment {
+            segment,
+            members: members.into_iter().collect(),
+        };
+        let info = self.segment_info(&segment);
+        self.segments.insert(id, segment);
+        ok(info)
+    }
+
+    fn update_segment(&mut self, segment_id: &str, body: Value, path: &str) -> Reply {
+        let id = try_reply!(self.segment(segment_id, path)).segment["id"]
+            .as_u64()
+            .unwrap_or_default();
+        let (members, _) = self.member_ids(&body["static_segment"]);
+        let segment = self.segments.get_mut(&id).unwrap();
+        if let Some(name) = body["name"].as_str() {
+            segment.segment["name"] = name.into();
+        }
+        if body["static_segment"].is_array() {
+            segment.members = members.into_iter().collect();
+        }
+        if body["options"].is_object() {
+            segment.segment["options"] = body["options"].clone();
+        }
+        segment.segment["updated_at"] = chrono::Utc::now().to_rfc3339().into();
+        let segment = &self.segments[&id];
+        ok(self.segment_info(segment))
+    }
+
+    fn update_segment_members(&mut self, segment_id: &str, body: Value, path: &str) -> Reply {
+        let segment = try_reply!(self.segment(segment_id, path));
+        if segment.segment["type"] != "static" {
+            return invalid("members can only be added to or removed from static segments");
+        }
+        let id = segment.segment["id"].as_u64().unwrap_or_default();
+        let (to_add, unknown_add) = self.member_ids(&body["members_to_add"]);
+        let (to_remove, unknown_remove) = self.member_ids(&body["members_to_remove"]);
+        let segment = self.segments.get_mut(&id).unwrap();
+        let added: Vec<String> = to_add
+            .into_iter()
+            .filter(|id| segment.members.insert(id.clone()))
+            .collect();
+        let removed: Vec<String> = to_remove
+            .into_iter()
+            .filter(|id| segment.members.remove(id))
+            .collect();
+
+        let mut errors = vec![];
+        if !unknown_add.is_empty() {
+            errors.push(json!({
+                "email_addresses": unknown_add,
+                "error": "Email addresses are not subscribed to the list",
+            }));
+        }
+        if !unknown_remove.is_empty() {
+            errors.push(json!({
+                "email_addresses": unknown_remove,
+                "error": "Email addresses are not subscribed to the list",
+            }));
+        }
+        let lookup = |ids: &[String]| -> Vec<Value> {
+            ids.iter().map(|id| self.members[id].clone()).collect()
+        };
+        ok(json!({
+            "members_added": lookup(&added),
+            "members_removed": lookup(&removed),
+            "errors": errors,
+            "total_added": added.len(),
+            "total_removed": removed.len(),
+            "error_count": errors.len(),
+        }))
+    }
+}
+
+/// Evaluate the merge field conditions of a saved segment against a member
+fn matches_options(member: &Value, options: &Value) -> bool {
+    let conditions = options["conditions"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let matches = |condition: &Value| {
+        let field = condition["field"].as_str().unwrap_or_default();
+        let actual = match &member["merge_fields"][field] {
+            Value::String(value) => value.to_lowercase(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        let expected = match &condition["value"] {
+            Value::String(value) => value.to_lowercase(),
+            other => other.to_string(),
+        };
+        match condition["op"].as_str().unwrap_or_default() {
+            "is" => actual == expected,
+            "not" => actual != expected,
+            "contains" => actual.contains(&expected),
+            "notcontain" => !actual.contains(&expected),
+            "blank" => actual.is_empty(),
+            "blank_not" => !actual.is_empty(),
+            _ => false,
+        }
+    };
+    match options["match"].as_str() {
+        Some("any") => conditions.iter().any(matches),
+        _ => conditions.iter().all(matches),
+    }
+}
+
+fn update_tags(member: &mut Value, body: Value) -> std::result::Result<(), Reply> {
+    let Some(updates) = body["tags"].as_array() else {
+        return Err(invalid("tags are required"));
+    };
+    let mut tags = member_tags(member);
+    for update in updates {
+        let Some(name) = update["name"].as_str() else {
+            return Err(invalid("tag name is required"));
+        };
+        match update["status"].as_str() {
+            Some("active") if !tags.iter().any(|tag| tag == name) => tags.push(name.to_string()),
+            Some("inactive") => tags.retain(|tag| tag != name),
+            Some("active") => {}
+            _ => return Err(invalid("tag status must be active or inactive")),
+        }
+    }
+    member["tags_count"] = tags.len().into();
+    member["tags"] = tags
+        .into_iter()
+        .map(|name| json!({ "name": name }))
+        .collect();
+    Ok(())
+}
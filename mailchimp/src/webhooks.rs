@@ -0,0 +1,246 @@
+use crate::{Client, NO_QUERY, Result, deserialize_null_string, error::Error, members::member_id};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// All webhooks of a list. Mailchimp doesn't page webhooks.
+pub async fn all(client: &Client, list_id: &str) -> Result<Vec<Webhook>> {
+    #[derive(Deserialize)]
+    struct WebhooksResponse {
+        webhooks: Vec<Webhook>,
+    }
+    let response: WebhooksResponse = client
+        .fetch(&format!("/3.0/lists/{list_id}/webhooks"), NO_QUERY)
+        .await?;
+    Ok(response.webhooks)
+}
+
+pub async fn get(client: &Client, list_id: &str, webhook_id: &str) -> Result<Webhook> {
+    client
+        .fetch(
+            &format!("/3.0/lists/{list_id}/webhooks/{webhook_id}"),
+            NO_QUERY,
+        )
+        .await
+}
+
+/// Register a webhook. Mailchimp validates the url with a `GET` request
+/// before accepting it, so the receiver has to be reachable already.
+pub async fn create(client: &Client, list_id: &str, webhook: &NewWebhook) -> Result<Webhook> {
+    client
+        .post(&format!("/3.0/lists/{list_id}/webhooks"), webhook)
+        .await
+}
+
+pub async fn delete(client: &Client, list_id: &str, webhook_id: &str) -> Result<()> {
+    client
+        .delete(&format!("/3.0/lists/{list_id}/webhooks/{webhook_id}"))
+        .await
+}
+
+/// The list events a webhook is triggered by
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct WebhookEvents {
+    #[serde(default)]
+    pub subscribe: bool,
+    #[serde(default)]
+    pub unsubscribe: bool,
+    #[serde(default)]
+    pub profile: bool,
+    #[serde(default)]
+    pub cleaned: bool,
+    #[serde(default)]
+    pub upemail: bool,
+    #[serde(default)]
+    pub campaign: bool,
+}
+
+impl WebhookEvents {
+    /// All member events, leaving out campaign sends
+    pub fn members() -> Self {
+        Self {
+            subscribe: true,
+            unsubscribe: true,
+            profile: true,
+            cleaned: true,
+            upemail: true,
+            campaign: false,
+        }
+    }
+}
+
+/// Who has to trigger a change for the webhook to fire
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct WebhookSources {
+    #[serde(default)]
+    pub user: bool,
+    #[serde(default)]
+    pub admin: bool,
+    #[serde(default)]
+    pub api: bool,
+}
+
+impl WebhookSources {
+    /// Changes by contacts and by account admins in the Mailchimp app.
+    /// Changes made through the API are our own syncs and are left out.
+    pub fn external() -> Self {
+        Self {
+            user: true,
+            admin: true,
+            api: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    #[serde(default)]
+    pub events: WebhookEvents,
+    #[serde(default)]
+    pub sources: WebhookSources,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        deserialize_with = "deserialize_null_string::deserialize"
+    )]
+    pub list_id: String,
+}
+
+/// The request body to register a webhook
+#[derive(Serialize, Debug, Clone)]
+pub struct NewWebhook {
+    pub url: String,
+    pub events: WebhookEvents,
+    pub sources: WebhookSources,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEventType {
+    Subscribe,
+    Unsubscribe,
+    Profile,
+    Cleaned,
+    Upemail,
+    Campaign,
+}
+
+impl WebhookEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Subscribe => "subscribe",
+            Self::Unsubscribe => "unsubscribe",
+            Self::Profile => "profile",
+            Self::Cleaned => "cleaned",
+            Self::Upemail => "upemail",
+            Self::Campaign => "campaign",
+        }
+    }
+}
+
+impl std::str::FromStr for WebhookEventType {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(&format!("\"{s}\"")).map_err(|_| Error::value(s.into()))
+    }
+}
+
+/// A list event as posted by Mailchimp to a webhook.
+///
+/// Mailchimp posts events form encoded with nested keys, like
+/// `type=unsubscribe&data[email]=...&data[merges][FNAME]=...`. The `data`
+/// keys are kept flattened, e.g. `merges[FNAME]`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WebhookEvent {
+    pub r#type: WebhookEventType,
+    pub fired_at: Option<DateTime<Utc>>,
+    pub data: HashMap<String, String>,
+}
+
+impl WebhookEvent {
+    /// Parse a form encoded webhook request body
+    pub fn parse(body: &[u8]) -> Result<Self> {
+        let mut r#type = None;
+        let mut fired_at = None;
+        let mut data = HashMap::new();
+        for (key, value) in url::form_urlencoded::parse(body) {
+            match key.as_ref() {
+                "type" => r#type = Some(value.parse::<WebhookEventType>()?),
+                // Mailchimp sends the time in UTC without an offset
+                "fired_at" => {
+                    fired_at = NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S")
+                        .ok()
+                        .map(|time| time.and_utc())
+                }
+                key => {
+                    if let Some(key) = key.strip_prefix("data[") {
+                        let key = key.replacen(']', "", 1);
+                        data.insert(key, value.into_owned());
+                    }
+                }
+            }
+        }
+        let r#type = r#type.ok_or_else(|| Error::value("webhook event without type".into()))?;
+        Ok(Self {
+            r#type,
+            fired_at,
+            data,
+        })
+    }
+
+    pub fn list_id(&self) -> &str {
+        self.field("list_id").unwrap_or_default()
+    }
+
+    /// The current email address of the contact. For `upemail` events this
+    /// is the new address.
+    pub fn email(&self) -> Option<&str> {
+        match self.r#type {
+            WebhookEventType::Upemail => self.field("new_email"),
+            _ => self.field("email"),
+        }
+    }
+
+    /// The previous address of the contact of an `upemail` event
+    pub fn old_email(&self) -> Option<&str> {
+        match self.r#type {
+            WebhookEventType::Upemail => self.field("old_email"),
+            _ => None,
+        }
+    }
+
+    /// The id of the contact as used by the API, which is derived from its
+    /// email address. The `id` Mailchimp includes in webhook events is a
+    /// different, legacy identifier.
+    pub fn member_id(&self) -> Option<String> {
+        self.email().map(member_id)
+    }
+
+    /// Why a contact unsubscribed or was cleaned, like `manual`, `abuse` or
+    /// `hard`
+    pub fn reason(&self) -> Option<&str> {
+        self.field("reason")
+    }
+
+    /// The merge fields of the contact, keyed by tag
+    pub fn merges(&self) -> HashMap<&str, &str> {
+        self.data
+            .iter()
+            .filter_map(|(key, value)| {
+                key.strip_prefix("merges[")
+                    .and_then(|key| key.strip_suffix(']'))
+                    .filter(|key| !key.contains('['))
+                    .map(|key| (key, value.as_str()))
+            })
+            .collect()
+    }
+
+    fn field(&self, key: &str) -> Option<&str> {
+        self.data
+            .get(key)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+}
@@ -0,0 +1,199 @@
+use chrono::{Duration, DurationRound, Utc};
+use futures::TryStreamExt;
+use mailchimp::{
+    RetryPolicy,
+    campaigns::{
+        self, CampaignSettings, CampaignStatus, CampaignsQuery, Content, NewCampaign, Recipients,
+        TemplateContent,
+    },
+    members::{self, Member, MemberStatus},
+    segments::{self, NewSegment},
+    testing::Server,
+};
+use std::collections::HashMap;
+
+fn member(email: &str, status: MemberStatus) -> Member {
+    Member {
+        id: members::member_id(email),
+        email_address: email.to_string(),
+        status: Some(status),
+        ..Default::default()
+    }
+}
+
+fn newsletter(recipients: Recipients) -> NewCampaign {
+    NewCampaign {
+        recipients,
+        settings: CampaignSettings {
+            subject_line: "Club newsletter".to_string(),
+            title: "Newsletter".to_string(),
+            from_name: "Silver Bullets".to_string(),
+            reply_to: "secretary@airstream.test".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+async fn seeded_list(server: &Server) -> String {
+    let list_id = server.create_list("club");
+    members::upsert_many(
+        &server.client(),
+        &list_id,
+        futures::stream::iter(vec![
+            member("ada@airstream.test", MemberStatus::Subscribed),
+            member("grace@airstream.test", MemberStatus::Subscribed),
+            member("linus@airstream.test", MemberStatus::Unsubscribed),
+        ]),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap();
+    list_id
+}
+
+#[tokio::test]
+async fn draft_test_schedule_and_send() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = seeded_list(&server).await;
+
+    let campaign = campaigns::create(&client, &newsletter(Recipients::list(&list_id)))
+        .await
+        .unwrap();
+    assert_eq!(campaign.status, CampaignStatus::Save);
+    assert_eq!(campaign.recipients.recipient_count, 2);
+
+    // Without content the campaign can neither be tested nor sent
+    let checklist = campaigns::send_checklist(&client, &campaign.id)
+        .await
+        .unwrap();
+    assert!(!checklist.is_ready);
+    assert!(
+        campaigns::send_test(&client, &campaign.id, &["me@airstream.test".to_string()])
+            .await
+            .is_err()
+    );
+
+    let content = campaigns::set_content(
+        &client,
+        &campaign.id,
+        &Content::Html("<p>Rally next week</p>".to_string()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(content.html, "<p>Rally next week</p>");
+
+    campaigns::send_test(&client, &campaign.id, &["me@airstream.test".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(server.test_emails(&campaign.id), vec!["me@airstream.test"]);
+    assert!(server.deliveries(&campaign.id).is_empty());
+
+    let time = (Utc::now() + Duration::days(1))
+        .duration_trunc(Duration::minutes(15))
+        .unwrap();
+    campaigns::schedule(&client, &campaign.id, time)
+        .await
+        .unwrap();
+    let scheduled = campaigns::get(&client, &campaign.id).await.unwrap();
+    assert_eq!(scheduled.status, CampaignStatus::Schedule);
+
+    // Scheduled campaigns can't be changed until unscheduled
+    assert!(
+        campaigns::set_content(&client, &campaign.id, &Content::Html("<p>x</p>".into()))
+            .await
+            .is_err()
+    );
+    campaigns::unschedule(&client, &campaign.id).await.unwrap();
+
+    campaigns::send(&client, &campaign.id).await.unwrap();
+    let sent = campaigns::get(&client, &campaign.id).await.unwrap();
+    assert_eq!(sent.status, CampaignStatus::Sent);
+    assert_eq!(sent.emails_sent, 2);
+    let mut deliveries = server.deliveries(&campaign.id);
+    deliveries.sort();
+    assert_eq!(
+        deliveries,
+        vec!["ada@airstream.test", "grace@airstream.test"]
+    );
+
+    let sent: Vec<_> = campaigns::all(
+        &client,
+        CampaignsQuery {
+            list_id: Some(list_id),
+            status: Some(CampaignStatus::Sent),
+            ..Default::default()
+        },
+    )
+    .try_collect()
+    .await
+    .unwrap();
+    assert_eq!(sent.len(), 1);
+}
+
+#[tokio::test]
+async fn segment_campaign_from_template() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = seeded_list(&server).await;
+    let segment = segments::create(
+        &client,
+        &list_id,
+        &NewSegment {
+            name: "officers".to_string(),
+            static_segment: Some(vec![
+                "grace@airstream.test".to_string(),
+                "linus@airstream.test".to_string(),
+            ]),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let campaign = campaigns::create(
+        &client,
+        &newsletter(Recipients::segment(&list_id, segment.id)),
+    )
+    .await
+    .unwrap();
+    let content = campaigns::set_content(
+        &client,
+        &campaign.id,
+        &Content::Template(TemplateContent {
+            id: 42,
+            sections: HashMap::from([("body".to_string(), "Officers meeting".to_string())]),
+        }),
+    )
+    .await
+    .unwrap();
+    assert!(content.html.contains("Officers meeting"));
+
+    campaigns::send(&client, &campaign.id).await.unwrap();
+    assert_eq!(
+        server.deliveries(&campaign.id),
+        vec!["grace@airstream.test"]
+    );
+
+    // Sent campaigns can't be sent again
+    assert!(campaigns::send(&client, &campaign.id).await.is_err());
+}
+
+#[tokio::test]
+async fn rejects_unknown_recipients() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("club");
+
+    assert!(
+        campaigns::create(&client, &newsletter(Recipients::list("missing")))
+            .await
+            .is_err()
+    );
+    assert!(
+        campaigns::create(&client, &newsletter(Recipients::segment(&list_id, 7)))
+            .await
+            .is_err()
+    );
+}
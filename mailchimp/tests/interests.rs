@@ -0,0 +1,36 @@
+use futures::TryStreamExt;
+use mailchimp::{
+    interests::{self, InterestGroups, InterestType},
+    testing::Server,
+};
+
+#[tokio::test]
+async fn sync_creates_missing_groups_once() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("audience");
+    let groups = InterestGroups::preferences().unwrap();
+
+    let created = interests::sync(&client, &list_id, &groups).await.unwrap();
+    let names: usize = groups.iter().map(|group| group.interests.len()).sum();
+    assert_eq!(created.len(), names);
+
+    let synced = interests::sync(&client, &list_id, &groups).await.unwrap();
+    assert_eq!(synced["Email only"], created["Email only"]);
+
+    let categories: Vec<_> = interests::categories(&client, &list_id, Default::default())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(categories.len(), groups.len());
+    let communication = categories
+        .iter()
+        .find(|category| category.title == "Communication Preference")
+        .unwrap();
+    assert_eq!(communication.r#type, InterestType::Radio);
+
+    let email_only = interests::get(&client, &list_id, &communication.id, &created["Email only"])
+        .await
+        .unwrap();
+    assert_eq!(email_only.category_id, communication.id);
+}
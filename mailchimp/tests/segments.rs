@@ -0,0 +1,136 @@
+use futures::TryStreamExt;
+use mailchimp::{
+    RetryPolicy,
+    members::{self, Member, MemberStatus},
+    segments::{self, NewSegment, SegmentCondition, SegmentOptions, SegmentType, SegmentsQuery},
+    testing::Server,
+};
+use std::collections::HashMap;
+
+fn member(email: &str, club: &str) -> Member {
+    Member {
+        id: members::member_id(email),
+        email_address: email.to_string(),
+        status_if_new: Some(MemberStatus::Subscribed),
+        merge_fields: Some(HashMap::from([("CLUB".to_string(), club.into())])),
+        ..Default::default()
+    }
+}
+
+async fn segment_emails(client: &mailchimp::Client, list_id: &str, segment_id: u64) -> Vec<String> {
+    let mut emails: Vec<String> =
+        segments::members(client, list_id, segment_id, Default::default())
+            .map_ok(|member| member.email_address)
+            .try_collect()
+            .await
+            .unwrap();
+    emails.sort();
+    emails
+}
+
+#[tokio::test]
+async fn static_and_saved_segments() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("region");
+    members::upsert_many(
+        &client,
+        &list_id,
+        futures::stream::iter(vec![
+            member("ada@example.com", "Silver Bullets"),
+            member("grace@example.com", "Silver Bullets"),
+            member("linus@example.com", "Wally Byam Caravan"),
+        ]),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap();
+
+    let leaders = segments::create(
+        &client,
+        &list_id,
+        &NewSegment {
+            name: "leaders".to_string(),
+            static_segment: Some(vec!["ada@example.com".to_string()]),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(leaders.r#type, SegmentType::Static);
+    assert_eq!(leaders.member_count, 1);
+
+    let response = segments::update_members(
+        &client,
+        &list_id,
+        leaders.id,
+        &[
+            "linus@example.com".to_string(),
+            "nobody@example.com".to_string(),
+        ],
+        &["ada@example.com".to_string()],
+    )
+    .await
+    .unwrap();
+    assert_eq!((response.total_added, response.total_removed), (1, 1));
+    assert_eq!(response.errors[0].email_addresses, ["nobody@example.com"]);
+    assert_eq!(
+        segment_emails(&client, &list_id, leaders.id).await,
+        ["linus@example.com"]
+    );
+
+    let club = segments::create(
+        &client,
+        &list_id,
+        &NewSegment {
+            name: "Silver Bullets".to_string(),
+            options: Some(SegmentOptions {
+                conditions: vec![SegmentCondition::merge_field_is("CLUB", "Silver Bullets")],
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(club.r#type, SegmentType::Saved);
+    assert_eq!(
+        segment_emails(&client, &list_id, club.id).await,
+        ["ada@example.com", "grace@example.com"]
+    );
+
+    let club = segments::update_conditions(
+        &client,
+        &list_id,
+        club.id,
+        SegmentOptions {
+            conditions: vec![SegmentCondition::merge_field_is(
+                "CLUB",
+                "Wally Byam Caravan",
+            )],
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(club.name, "Silver Bullets");
+    assert_eq!(club.member_count, 1);
+
+    let saved: Vec<_> = segments::all(
+        &client,
+        &list_id,
+        SegmentsQuery {
+            r#type: Some(SegmentType::Saved),
+            ..Default::default()
+        },
+    )
+    .try_collect()
+    .await
+    .unwrap();
+    assert_eq!(saved.len(), 1);
+
+    segments::delete(&client, &list_id, leaders.id)
+        .await
+        .unwrap();
+    assert!(segments::get(&client, &list_id, leaders.id).await.is_err());
+}
@@ -0,0 +1,637 @@
+use futures::TryStreamExt;
+use mailchimp::{
+    RetryPolicy,
+    batches::Batch,
+    members::{
+        self, DeleteLimit, Member, MemberStatus, MemberTagStatus, MemberTagUpdate, RemovalPolicy,
+    },
+    merge_fields::{self, MergeFields},
+    testing::Server,
+};
+use std::collections::{HashMap, HashSet};
+
+fn member(email: &str, first_name: &str) -> Member {
+    Member {
+        id: members::member_id(email),
+        email_address: email.to_string(),
+        status_if_new: Some(MemberStatus::Subscribed),
+        merge_fields: Some(HashMap::from([("FNAME".to_string(), first_name.into())])),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn rejects_unknown_api_key() {
+    let server = Server::start().await.unwrap();
+    let client = mailchimp::client::from_api_key_with_endpoint(
+        "11111111111111111111111111111111-test",
+        &server.endpoint(),
+    )
+    .unwrap();
+    let list_id = server.create_list("audience");
+    let err = mailchimp::lists::get(&client, &list_id).await.unwrap_err();
+    assert!(matches!(err, mailchimp::Error::Mailchimp(e) if e.status == 401));
+}
+
+#[tokio::test]
+async fn upsert_many_and_retain() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("audience");
+
+    let upserted = members::upsert_many(
+        &client,
+        &list_id,
+        futures::stream::iter(vec![
+            member("ada@example.com", "Ada"),
+            member("grace@example.com", "Grace"),
+            member("not-an-email", "Nobody"),
+        ]),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(upserted.len(), 2);
+    assert!(upserted.contains(&members::member_id("ada@example.com")));
+
+    let ada = members::get(&client, &list_id, &members::member_id("ada@example.com"))
+        .await
+        .unwrap();
+    assert_eq!(ada.status, Some(MemberStatus::Subscribed));
+    assert_eq!(ada.full_name, "Ada");
+
+    // cleaned contacts are left alone by retain
+    server.insert_member(
+        &list_id,
+        &Member {
+            email_address: "bounced@example.com".into(),
+            status: Some(MemberStatus::Cleaned),
+            ..Default::default()
+        },
+    );
+
+    let keep = [members::member_id("grace@example.com")].into();
+    let deleted = members::retain(
+        &client,
+        &list_id,
+        &keep,
+        RemovalPolicy::Archive,
+        DeleteLimit::default(),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap()
+    .removed;
+    assert_eq!(deleted, 1);
+
+    let mut remaining: Vec<_> = server
+        .members(&list_id)
+        .into_iter()
+        .filter(|member| member.status != Some(MemberStatus::Archived))
+        .map(|member| member.email_address)
+        .collect();
+    remaining.sort();
+    assert_eq!(remaining, ["bounced@example.com", "grace@example.com"]);
+    assert_eq!(server.batches().len(), 1);
+}
+
+#[tokio::test]
+async fn retain_removal_policies() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let seed = |list_id: String| {
+        let client = client.clone();
+        async move {
+            members::upsert_many(
+                &client,
+                &list_id,
+                futures::stream::iter(vec![
+                    member("ada@example.com", "Ada"),
+                    member("grace@example.com", "Grace"),
+                ]),
+                RetryPolicy::None,
+            )
+            .await
+            .unwrap();
+            list_id
+        }
+    };
+    let keep: HashSet<String> = [members::member_id("grace@example.com")].into();
+    let ada = members::member_id("ada@example.com");
+
+    // Archived members are restored when added again
+    let list_id = seed(server.create_list("archive")).await;
+    let removed = members::retain(
+        &client,
+        &list_id,
+        &keep,
+        RemovalPolicy::Archive,
+        DeleteLimit::default(),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap()
+    .removed;
+    assert_eq!(removed, 1);
+    let archived = server.member(&list_id, &ada).unwrap();
+    assert_eq!(archived.status, Some(MemberStatus::Archived));
+    let list_id = seed(list_id).await;
+    let restored = server.member(&list_id, &ada).unwrap();
+    assert_eq!(restored.status, Some(MemberStatus::Subscribed));
+
+    // Permanently deleted members are gone for good
+    let list_id = seed(server.create_list("delete")).await;
+    let policy = RemovalPolicy::DeletePermanent;
+    let removed = members::retain(
+        &client,
+        &list_id,
+        &keep,
+        policy,
+        DeleteLimit::default(),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap()
+    .removed;
+    assert_eq!(removed, 1);
+    assert!(server.member(&list_id, &ada).is_none());
+
+    // Unsubscribed members are kept and tagged, and only removed once
+    let list_id = seed(server.create_list("unsubscribe")).await;
+    let policy = RemovalPolicy::Unsubscribe;
+    let removed = members::retain(
+        &client,
+        &list_id,
+        &keep,
+        policy,
+        DeleteLimit::default(),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap()
+    .removed;
+    assert_eq!(removed, 1);
+    let unsubscribed = server.member(&list_id, &ada).unwrap();
+    assert_eq!(unsubscribed.status, Some(MemberStatus::Unsubscribed));
+    assert_eq!(server.tags(&list_id, &ada), [members::REMOVED_TAG]);
+    let removed = members::retain(
+        &client,
+        &list_id,
+        &keep,
+        policy,
+        DeleteLimit::default(),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap()
+    .removed;
+    assert_eq!(removed, 0);
+
+    // Removed members that come back are subscribed again
+    let all = [ada.clone(), members::member_id("grace@example.com")].into();
+    members::retain(
+        &client,
+        &list_id,
+        &all,
+        policy,
+        DeleteLimit::default(),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap();
+    let restored = server.member(&list_id, &ada).unwrap();
+    assert_eq!(restored.status, Some(MemberStatus::Subscribed));
+    assert!(server.tags(&list_id, &ada).is_empty());
+
+    // Contacts who unsubscribed themselves are not tagged as removed
+    server.insert_member(
+        &list_id,
+        &Member {
+            email_address: "optout@example.com".into(),
+            status: Some(MemberStatus::Unsubscribed),
+            ..Default::default()
+        },
+    );
+    let removed = members::retain(
+        &client,
+        &list_id,
+        &all,
+        policy,
+        DeleteLimit::default(),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap()
+    .removed;
+    assert_eq!(removed, 0);
+    let optout = members::member_id("optout@example.com");
+    assert!(server.tags(&list_id, &optout).is_empty());
+}
+
+#[tokio::test]
+async fn restore_keeps_removed_tag_when_subscribing_fails() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("unsubscribe");
+    members::upsert_many(
+        &client,
+        &list_id,
+        futures::stream::iter(vec![
+            member("ada@example.com", "Ada"),
+            member("grace@example.com", "Grace"),
+        ]),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap();
+    let ada = members::member_id("ada@example.com");
+    let grace = members::member_id("grace@example.com");
+    let retain = |keep: HashSet<String>| {
+        let client = client.clone();
+        let list_id = list_id.clone();
+        async move {
+            members::retain(
+                &client,
+                &list_id,
+                &keep,
+                RemovalPolicy::Unsubscribe,
+                DeleteLimit::default(),
+                RetryPolicy::None,
+            )
+            .await
+            .unwrap()
+        }
+    };
+    retain([grace.clone()].into()).await;
+    server.opt_out(&list_id, &ada);
+
+    let retained = retain([ada.clone(), grace].into()).await;
+    assert_eq!(retained.removed, 0);
+    assert_eq!(retained.failed_restores.len(), 1);
+    assert_eq!(retained.failed_restores[0].operation_id, ada);
+    let unsubscribed = server.member(&list_id, &ada).unwrap();
+    assert_eq!(unsubscribed.status, Some(MemberStatus::Unsubscribed));
+    assert_eq!(server.tags(&list_id, &ada), [members::REMOVED_TAG]);
+}
+
+#[tokio::test]
+async fn tag_updates_run_as_batch() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("audience");
+    let id = members::member_id("ada@example.com");
+    members::upsert(&client, &list_id, &id, &member("ada@example.com", "Ada"))
+        .await
+        .unwrap();
+
+    let update = |name: &str, status| MemberTagUpdate {
+        name: name.to_string(),
+        status,
+    };
+    members::tags::update_many(
+        &client,
+        &list_id,
+        &[(
+            id.clone(),
+            vec![
+                update("member", MemberTagStatus::Active),
+                update("lapsed", MemberTagStatus::Inactive),
+            ],
+        )],
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(server.tags(&list_id, &id), ["member"]);
+
+    let mut batch = Batch::default();
+    members::tags::batch::update(
+        &mut batch,
+        &list_id,
+        &id,
+        &[update("member", MemberTagStatus::Inactive)],
+    )
+    .unwrap();
+    batch.run(&client, true).await.unwrap();
+    assert!(server.tags(&list_id, &id).is_empty());
+    assert_eq!(server.batches().len(), 2);
+}
+
+#[tokio::test]
+async fn retain_refuses_mass_deletion() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("audience");
+    members::upsert_many(
+        &client,
+        &list_id,
+        futures::stream::iter(vec![
+            member("ada@example.com", "Ada"),
+            member("grace@example.com", "Grace"),
+            member("hedy@example.com", "Hedy"),
+        ]),
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap();
+    let keep: HashSet<String> = [members::member_id("grace@example.com")].into();
+    let retain = |limit| {
+        members::retain(
+            &client,
+            &list_id,
+            &keep,
+            RemovalPolicy::Archive,
+            limit,
+            RetryPolicy::None,
+        )
+    };
+
+    let err = retain(DeleteLimit {
+        percent: Some(50.0),
+        ..Default::default()
+    })
+    .await
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        mailchimp::Error::MassDeletion {
+            removing: 2,
+            total: 3,
+            ..
+        }
+    ));
+    assert!(
+        retain(DeleteLimit {
+            rows: Some(1),
+            ..Default::default()
+        })
+        .await
+        .is_err()
+    );
+    assert!(server.batches().is_empty());
+
+    let removed = retain(DeleteLimit {
+        rows: Some(2),
+        percent: Some(70.0),
+    })
+    .await
+    .unwrap()
+    .removed;
+    assert_eq!(removed, 2);
+}
+
+#[tokio::test]
+async fn merge_fields_sync() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("audience");
+    let target = MergeFields::club().unwrap();
+
+    let (added, deleted, _) = merge_fields::sync(&client, &list_id, target.clone(), true)
+        .await
+        .unwrap();
+    assert!(added.contains(&"UID".to_string()));
+    assert!(deleted.contains(&"PHONE".to_string()));
+
+    let current: MergeFields = merge_fields::all(&client, &list_id, Default::default())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(current.len(), target.len());
+
+    let (added, deleted, updated) = merge_fields::sync(&client, &list_id, target, true)
+        .await
+        .unwrap();
+    assert!(added.is_empty() && deleted.is_empty() && updated.is_empty());
+}
+
+#[tokio::test]
+async fn merge_fields_sync_attributes() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("audience");
+    let mut target = MergeFields::club().unwrap();
+    merge_fields::sync(&client, &list_id, target.clone(), true)
+        .await
+        .unwrap();
+
+    let field = |tag: &str| {
+        server
+            .merge_fields(&list_id)
+            .into_iter()
+            .find(|field| field.tag == tag)
+            .unwrap()
+    };
+    let class = field("MCLASS");
+    assert_eq!(class.r#type, merge_fields::MergeType::Dropdown);
+    assert_eq!(
+        class.options.choices.as_deref(),
+        Some(&["Regular", "Lifetime", "Complimentary"].map(String::from)[..])
+    );
+    assert_eq!(class.public, Some(false));
+
+    let class = target.get_mut("MCLASS").unwrap();
+    class.required = Some(true);
+    class.default_value = Some("Regular".to_string());
+    class.options.choices = Some(vec!["Regular".to_string(), "Lifetime".to_string()]);
+    target.get_mut("JOIN").unwrap().options.date_format = Some("DD/MM/YYYY".to_string());
+
+    let (_, _, mut updated) = merge_fields::sync(&client, &list_id, target.clone(), true)
+        .await
+        .unwrap();
+    updated.sort();
+    assert_eq!(updated, vec!["JOIN", "MCLASS"]);
+    let class = field("MCLASS");
+    assert_eq!(class.required, Some(true));
+    assert_eq!(class.default_value.as_deref(), Some("Regular"));
+    assert_eq!(class.options.choices.unwrap().len(), 2);
+    assert_eq!(
+        field("JOIN").options.date_format.as_deref(),
+        Some("DD/MM/YYYY")
+    );
+
+    let (_, _, updated) = merge_fields::sync(&client, &list_id, target, true)
+        .await
+        .unwrap();
+    assert!(updated.is_empty());
+}
+
+#[test]
+fn typed_merge_field_values() {
+    use merge_fields::{Address, MergeField, MergeFieldOptions, MergeType, ToMergeFieldValue};
+    let field = |r#type: MergeType, options: MergeFieldOptions| MergeField {
+        tag: "F".to_string(),
+        name: "Field".to_string(),
+        r#type,
+        options,
+        ..Default::default()
+    };
+    let value = |result: mailchimp::Result<Option<merge_fields::MergeFieldValue>>| {
+        result.unwrap().map(|(_, value)| value)
+    };
+
+    let us_phone = field(
+        MergeType::Phone,
+        MergeFieldOptions {
+            phone_format: Some("US".to_string()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        value("+1 (937) 596-6111".to_merge_field_value(&us_phone)),
+        Some("937-596-6111".into())
+    );
+    assert_eq!(value("596-6111".to_merge_field_value(&us_phone)), None);
+    let phone = field(MergeType::Phone, Default::default());
+    assert_eq!(
+        value(" +44 20 7946 0000 ".to_merge_field_value(&phone)),
+        Some("+44 20 7946 0000".into())
+    );
+
+    let url = field(MergeType::Url, Default::default());
+    assert_eq!(
+        value("https://airstream.test/club".to_merge_field_value(&url)),
+        Some("https://airstream.test/club".into())
+    );
+    assert_eq!(value("not a url".to_merge_field_value(&url)), None);
+    assert_eq!(
+        value("ftp://airstream.test".to_merge_field_value(&url)),
+        None
+    );
+
+    let number = field(MergeType::Number, Default::default());
+    assert_eq!(
+        value(2.5f64.to_merge_field_value(&number)),
+        Some(2.5.into())
+    );
+    assert!(f64::NAN.to_merge_field_value(&number).is_err());
+    assert!("2.5".to_merge_field_value(&number).is_err());
+
+    let yes_no = field(
+        MergeType::Radio,
+        MergeFieldOptions {
+            choices: Some(vec!["Yes".to_string(), "No".to_string()]),
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        value(false.to_merge_field_value(&yes_no)),
+        Some("No".into())
+    );
+    assert!(
+        true.to_merge_field_value(&field(MergeType::Text, Default::default()))
+            .is_err()
+    );
+
+    let address = field(MergeType::Address, Default::default());
+    let mut home = Address {
+        addr1: "1 Riveted Way".to_string(),
+        city: "Jackson Center".to_string(),
+        state: "OH".to_string(),
+        zip: "45334".to_string(),
+        country: "US".to_string(),
+        ..Default::default()
+    };
+    let json = value((&home).to_merge_field_value(&address)).unwrap();
+    assert_eq!(json["addr1"], "1 Riveted Way");
+    assert!(json.get("addr2").is_none());
+    home.city.clear();
+    assert_eq!(value((&home).to_merge_field_value(&address)), None);
+}
+
+#[test]
+fn dropdown_values_other_than_choices_are_left_out() {
+    let fields = MergeFields::club().unwrap();
+    assert!(fields.to_value("MCLASS", "Lifetime").unwrap().is_some());
+    assert!(fields.to_value("MCLASS", "Honorary").unwrap().is_none());
+    assert!(fields.to_value("MCLASS", true).is_err());
+}
+
+#[tokio::test]
+async fn retries_rate_limited_requests() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("audience");
+
+    server.rate_limit(2, 0);
+    let list = mailchimp::lists::get(&client, &list_id).await.unwrap();
+    assert_eq!(list.id, list_id);
+
+    server.rate_limit(mailchimp::RATE_LIMIT_RETRIES + 1, 0);
+    let err = mailchimp::lists::get(&client, &list_id).await.unwrap_err();
+    assert!(matches!(&err, mailchimp::Error::Mailchimp(e) if e.status == 429));
+    assert!(err.is_retryable());
+}
+
+#[tokio::test]
+async fn validation_errors_fail_fast() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("audience");
+
+    let err = members::upsert(
+        &client,
+        &list_id,
+        &members::member_id("not-an-email"),
+        &member("not-an-email", "Nobody"),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(&err, mailchimp::Error::Mailchimp(e) if e.status == 400));
+    assert!(!err.is_retryable());
+}
+
+#[tokio::test]
+async fn caps_connections_per_api_key() {
+    let server = Server::start().await.unwrap();
+    let list_id = server.create_list("audience");
+    server.set_latency(std::time::Duration::from_millis(20));
+
+    // separate clients for the same key share one budget
+    let requests = (0..30).map(|_| {
+        let client = server.client();
+        let list_id = list_id.clone();
+        async move { mailchimp::lists::get(&client, &list_id).await }
+    });
+    futures::future::try_join_all(requests).await.unwrap();
+    assert!(server.peak_connections() <= mailchimp::MAX_CONNECTIONS_PER_KEY);
+    assert!(server.peak_connections() > 1);
+}
+
+#[tokio::test]
+async fn batch_outcome_reports_failed_operations() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("audience");
+    let id = members::member_id("ada@example.com");
+    members::upsert(&client, &list_id, &id, &member("ada@example.com", "Ada"))
+        .await
+        .unwrap();
+
+    let updates = [MemberTagUpdate {
+        name: "member".to_string(),
+        status: MemberTagStatus::Active,
+    }];
+    let failed = members::tags::update_many(
+        &client,
+        &list_id,
+        &[
+            (id.clone(), updates.to_vec()),
+            ("missing".to_string(), updates.to_vec()),
+        ],
+        RetryPolicy::None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].operation_id, "missing");
+    assert_eq!(failed[0].error().unwrap().status, 404);
+
+    let mut batch = Batch::default();
+    members::tags::batch::update(&mut batch, &list_id, &id, &updates)
+        .unwrap()
+        .operation_id = id.clone();
+    let outcome = batch.run_with_outcome(&client).await.unwrap();
+    assert!(outcome.is_success());
+    assert_eq!(outcome.succeeded[0].operation_id, id);
+}
@@ -0,0 +1,64 @@
+use mailchimp::{
+    members::member_id,
+    testing::Server,
+    webhooks::{self, NewWebhook, WebhookEvent, WebhookEventType, WebhookEvents, WebhookSources},
+};
+
+#[tokio::test]
+async fn register_list_and_delete() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let list_id = server.create_list("club");
+
+    let webhook = NewWebhook {
+        url: "https://sync.airstream.test/webhooks/1?secret=s3cret".to_string(),
+        events: WebhookEvents::members(),
+        sources: WebhookSources::external(),
+    };
+    let created = webhooks::create(&client, &list_id, &webhook).await.unwrap();
+    assert_eq!(created.url, webhook.url);
+    assert!(created.events.cleaned);
+    assert!(!created.sources.api);
+
+    // The same url can only be registered once
+    assert!(webhooks::create(&client, &list_id, &webhook).await.is_err());
+
+    let all = webhooks::all(&client, &list_id).await.unwrap();
+    assert_eq!(all, vec![created.clone()]);
+    assert_eq!(server.webhooks(&list_id), all);
+
+    webhooks::delete(&client, &list_id, &created.id)
+        .await
+        .unwrap();
+    assert!(webhooks::all(&client, &list_id).await.unwrap().is_empty());
+}
+
+#[test]
+fn parse_events() {
+    let body = "type=unsubscribe&fired_at=2009-03-26+21%3A40%3A57&data%5Baction%5D=unsub\
+        &data%5Breason%5D=manual&data%5Bid%5D=8a25ff1d98&data%5Blist_id%5D=a6b5da1054\
+        &data%5Bemail%5D=Wally%40airstream.test&data%5Bmerges%5D%5BFNAME%5D=Wally\
+        &data%5Bmerges%5D%5BINTERESTS%5D%5B0%5D=x";
+    let event = WebhookEvent::parse(body.as_bytes()).unwrap();
+    assert_eq!(event.r#type, WebhookEventType::Unsubscribe);
+    assert_eq!(
+        event.fired_at.unwrap().to_rfc3339(),
+        "2009-03-26T21:40:57+00:00"
+    );
+    assert_eq!(event.list_id(), "a6b5da1054");
+    assert_eq!(event.reason(), Some("manual"));
+    assert_eq!(event.member_id(), Some(member_id("wally@airstream.test")));
+    assert_eq!(event.merges().get("FNAME"), Some(&"Wally"));
+    assert_eq!(event.merges().len(), 1);
+
+    let body = "type=upemail&fired_at=2009-03-26+22%3A15%3A09&data%5Blist_id%5D=a6b5da1054\
+        &data%5Bnew_id%5D=51da8c3259&data%5Bnew_email%5D=new%40airstream.test\
+        &data%5Bold_email%5D=old%40airstream.test";
+    let event = WebhookEvent::parse(body.as_bytes()).unwrap();
+    assert_eq!(event.r#type, WebhookEventType::Upemail);
+    assert_eq!(event.email(), Some("new@airstream.test"));
+    assert_eq!(event.old_email(), Some("old@airstream.test"));
+
+    assert!(WebhookEvent::parse(b"data%5Bemail%5D=a%40b.test").is_err());
+    assert!(WebhookEvent::parse(b"type=bogus").is_err());
+}
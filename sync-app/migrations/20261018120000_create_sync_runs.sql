@@ -0,0 +1,26 @@
+-- History of portal sync runs
+create table sync_runs (
+    id bigserial primary key,
+    started_at timestamptz not null default now(),
+    finished_at timestamptz,
+    status text not null default 'running'
+        check (status in ('running', 'succeeded', 'failed')),
+    warnings jsonb not null default '[]'::jsonb,
+    error text
+);
+
+create index idx_sync_runs_started_at on sync_runs(started_at desc);
+alter table sync_runs enable row level security;
+
+-- Stats per synced entity of a run, durations in seconds
+create table sync_run_entities (
+    run_id bigint not null references sync_runs(id) on delete cascade,
+    entity text not null,
+    upserted bigint not null,
+    deleted bigint not null,
+    duration bigint not null,
+    primary key (run_id, entity)
+);
+
+create index idx_sync_run_entities_entity on sync_run_entities(entity);
+alter table sync_run_entities enable row level security;
@@ -0,0 +1,6 @@
+-- Break upserts down into inserted, updated and unchanged rows. Runs recorded
+-- before have no breakdown, upserted stays the rows written.
+alter table sync_run_entities
+    add column inserted bigint,
+    add column updated bigint,
+    add column unchanged bigint;
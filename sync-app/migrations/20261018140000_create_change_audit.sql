@@ -0,0 +1,47 @@
+-- Field level changes made by the portal sync. Rows that appear are recorded
+-- as changes from null, rows that disappear as changes to null.
+create table member_changes (
+    id bigserial primary key,
+    run_id bigint references sync_runs(id) on delete set null,
+    changed_at timestamptz not null default now(),
+    user_id text not null,
+    field text not null,
+    old_value text,
+    new_value text
+);
+
+create index idx_member_changes_user_id on member_changes(user_id, changed_at);
+alter table member_changes enable row level security;
+
+create table user_changes (
+    id bigserial primary key,
+    run_id bigint references sync_runs(id) on delete set null,
+    changed_at timestamptz not null default now(),
+    user_id text not null,
+    field text not null,
+    old_value text,
+    new_value text
+);
+
+create index idx_user_changes_user_id on user_changes(user_id, changed_at);
+alter table user_changes enable row level security;
+
+-- Leadership records are identified by kind, entity (club, region or
+-- standing committee, null for international), user, role and start date
+create table leadership_changes (
+    id bigserial primary key,
+    run_id bigint references sync_runs(id) on delete set null,
+    changed_at timestamptz not null default now(),
+    kind text not null
+        check (kind in ('club', 'region', 'international', 'standing_committee')),
+    entity bigint,
+    user_id text not null,
+    role bigint not null,
+    start_date date not null,
+    field text not null,
+    old_value text,
+    new_value text
+);
+
+create index idx_leadership_changes_user_id on leadership_changes(user_id, changed_at);
+alter table leadership_changes enable row level security;
@@ -0,0 +1,14 @@
+-- Rows removed by a sync in soft delete mode are marked deleted instead of
+-- deleted, and revived by the next upsert that brings them back
+alter table users add column deleted_at timestamptz;
+alter table members add column deleted_at timestamptz;
+alter table addresses add column deleted_at timestamptz;
+alter table brns add column deleted_at timestamptz;
+alter table regions add column deleted_at timestamptz;
+alter table clubs add column deleted_at timestamptz;
+alter table standing_committees add column deleted_at timestamptz;
+alter table leadership_role add column deleted_at timestamptz;
+alter table leadership_club add column deleted_at timestamptz;
+alter table leadership_region add column deleted_at timestamptz;
+alter table leadership_international add column deleted_at timestamptz;
+alter table leadership_standing_committee add column deleted_at timestamptz;
@@ -0,0 +1,2 @@
+-- Milliseconds each membership database query of a run took
+alter table sync_runs add column extract jsonb not null default '{}';
@@ -0,0 +1,19 @@
+-- Every membership period of every user, current and lapsed, one row per
+-- Drupal membership paragraph. International memberships have no club and
+-- no member type. Users and clubs are referenced by their Drupal uid without
+-- foreign keys, so the history outlives users and clubs that are gone.
+create table membership_history (
+    paragraph_id bigint primary key,
+    user_uid bigint not null,
+    partner_uid bigint,
+    club bigint,
+    member_class member_class not null,
+    member_type member_type,
+    join_date date not null,
+    leave_date date,
+    deleted_at timestamptz
+);
+
+create index idx_membership_history_user on membership_history(user_uid);
+create index idx_membership_history_club on membership_history(club);
+alter table membership_history enable row level security;
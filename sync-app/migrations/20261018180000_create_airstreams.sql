@@ -0,0 +1,27 @@
+-- Airstreams and who owned them when, one row per Drupal ownership
+-- paragraph. Owners are referenced by their Drupal uid without a foreign
+-- key, like the membership history.
+create table airstreams (
+    airstream_id bigint primary key,
+    vin text,
+    model text,
+    airstream_type text,
+    year integer,
+    length double precision,
+    deleted_at timestamptz
+);
+
+create table airstream_ownership (
+    paragraph_id bigint primary key,
+    airstream_id bigint not null references airstreams(airstream_id),
+    user_uid bigint not null,
+    include_partner boolean not null default false,
+    join_date date not null,
+    leave_date date,
+    deleted_at timestamptz
+);
+
+create index idx_airstream_ownership_airstream on airstream_ownership(airstream_id);
+create index idx_airstream_ownership_user on airstream_ownership(user_uid);
+alter table airstreams enable row level security;
+alter table airstream_ownership enable row level security;
@@ -0,0 +1,76 @@
+-- Drupal roles of users, and the users administering a club or region
+-- microsite. Users are referenced by their Drupal uid, clubs and regions by
+-- their uid.
+create table user_roles (
+    user_uid bigint not null,
+    role text not null,
+    deleted_at timestamptz,
+    primary key (user_uid, role)
+);
+
+create table entity_admins (
+    user_uid bigint not null,
+    entity_uid bigint not null,
+    is_region boolean not null default false,
+    deleted_at timestamptz,
+    primary key (user_uid, entity_uid)
+);
+
+create index idx_entity_admins_entity on entity_admins(entity_uid);
+alter table user_roles enable row level security;
+alter table entity_admins enable row level security;
+
+-- The Drupal uid of the signed in user. Portal user ids are the url safe
+-- base64 of the sha256 of the lowercased email.
+create function public.current_user_uid() returns bigint
+language sql stable security definer set search_path = ''
+as $$
+    select u.uid
+    from public.users u
+    where u.deleted_at is null
+      and u.id = rtrim(translate(encode(sha256(convert_to(
+            lower(trim(auth.jwt() ->> 'email')), 'UTF8')), 'base64'), '+/', '-_'), '=')
+$$;
+
+-- The numbers of the clubs the signed in user administers, directly or
+-- through their region
+create function public.administered_club_numbers() returns setof bigint
+language sql stable security definer set search_path = ''
+as $$
+    select c.number
+    from public.entity_admins a
+    join public.clubs c
+      on (not a.is_region and c.uid = a.entity_uid)
+      or (a.is_region and c.region = a.entity_uid)
+    where a.user_uid = public.current_user_uid()
+      and a.deleted_at is null
+      and c.deleted_at is null
+      and c.number is not null
+$$;
+
+create policy "Users can read their own roles" on user_roles
+  for select to authenticated
+  using (deleted_at is null and user_uid = public.current_user_uid());
+
+create policy "Users can read their own admin assignments" on entity_admins
+  for select to authenticated
+  using (deleted_at is null and user_uid = public.current_user_uid());
+
+create policy "Admins can read the members of their club or region" on members
+  for select to authenticated
+  using (
+    deleted_at is null
+    and local_club in (select public.administered_club_numbers())
+  );
+
+create policy "Admins can read the users of their club or region" on users
+  for select to authenticated
+  using (deleted_at is null and id in (
+    select m.primary_user from members m
+    where m.deleted_at is null
+      and m.local_club in (select public.administered_club_numbers())
+    union all
+    select m.partner_user from members m
+    where m.deleted_at is null
+      and m.local_club in (select public.administered_club_numbers())
+  ));
@@ -0,0 +1,27 @@
+-- Every address of every user with its flags, one row per Drupal address
+-- paragraph. Full addresses are personal data, so they live in a private
+-- schema that is not exposed through the API and only the service role can
+-- read.
+create schema if not exists private;
+revoke all on schema private from public;
+grant usage on schema private to service_role;
+
+create table private.user_addresses (
+    paragraph_id bigint primary key,
+    user_uid bigint not null,
+    delta integer not null default 0,
+    street_address text,
+    street_address_2 text,
+    city text,
+    state text,
+    zip_code text,
+    country text,
+    is_primary boolean not null default false,
+    is_mailing_address boolean not null default false,
+    deleted_at timestamptz
+);
+
+create index idx_user_addresses_user on private.user_addresses(user_uid);
+alter table private.user_addresses enable row level security;
+revoke all on private.user_addresses from public;
+grant select, insert, update, delete on private.user_addresses to service_role;
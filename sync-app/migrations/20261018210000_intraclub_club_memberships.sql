@@ -0,0 +1,33 @@
+-- Intraclub memberships are memberships of a club besides the home club.
+alter type member_type add value if not exists 'intraclub';
+
+-- The current memberships of members, their home club and any intraclub and
+-- affiliate clubs, where members only holds the home club. One row per user,
+-- club and member type.
+create table club_memberships (
+    user_id text not null references users(id),
+    club bigint not null references clubs(uid),
+    member_type member_type not null,
+    member_class member_class not null,
+    join_date date not null,
+    expiration_date date,
+    deleted_at timestamptz,
+    primary key (user_id, club, member_type)
+);
+
+create index idx_club_memberships_club on club_memberships(club);
+alter table club_memberships enable row level security;
+
+create policy "Users can read their own club memberships" on club_memberships
+  for select to authenticated
+  using (
+    deleted_at is null
+    and user_id in (select u.id from users u where u.uid = public.current_user_uid())
+  );
+
+create policy "Admins can read the club memberships of their club or region" on club_memberships
+  for select to authenticated
+  using (deleted_at is null and club in (
+    select c.uid from clubs c
+    where c.number in (select public.administered_club_numbers())
+  ));
@@ -0,0 +1,118 @@
+-- Record the changes to members, users and leadership as the sync writes
+-- them, instead of comparing snapshots of the tables before and after. Only
+-- transactions that enable `aci.audit` are recorded, under the run id in
+-- `aci.audit_run_id`. Rows that appear, also by clearing their deleted_at,
+-- are recorded as changes from null, rows that disappear as changes to null.
+
+-- The fields that differ between the old and new audited fields of a row,
+-- either of which is null for rows that appear or disappear
+create function audit_field_changes(old_fields jsonb, new_fields jsonb)
+returns table (field text, old_value text, new_value text)
+language sql immutable as $$
+    select key, old_fields ->> key, new_fields ->> key
+    from jsonb_object_keys(coalesce(new_fields, old_fields)) as key
+    where (old_fields ->> key) is distinct from (new_fields ->> key)
+$$;
+
+create function audit_enabled() returns boolean language sql stable as $$
+    select coalesce(current_setting('aci.audit', true), '') = 'on'
+$$;
+
+create function audit_run_id() returns bigint language sql stable as $$
+    select nullif(current_setting('aci.audit_run_id', true), '')::bigint
+$$;
+
+create function audit_members() returns trigger language plpgsql as $$
+declare
+    old_fields jsonb;
+    new_fields jsonb;
+    changed members;
+begin
+    if tg_op <> 'INSERT' and old.deleted_at is null then
+        old_fields := jsonb_build_object(
+            'member_class', old.member_class::text,
+            'member_type', old.member_type::text,
+            'local_club', old.local_club::text,
+            'expiration_date', old.expiration_date::text
+        );
+    end if;
+    if tg_op <> 'DELETE' and new.deleted_at is null then
+        new_fields := jsonb_build_object(
+            'member_class', new.member_class::text,
+            'member_type', new.member_type::text,
+            'local_club', new.local_club::text,
+            'expiration_date', new.expiration_date::text
+        );
+    end if;
+    changed := case when tg_op = 'DELETE' then old else new end;
+    insert into member_changes (run_id, user_id, field, old_value, new_value)
+    select audit_run_id(), changed.primary_user, c.field, c.old_value, c.new_value
+    from audit_field_changes(old_fields, new_fields) c;
+    return null;
+end $$;
+
+create function audit_users() returns trigger language plpgsql as $$
+declare
+    old_fields jsonb;
+    new_fields jsonb;
+    changed users;
+begin
+    if tg_op <> 'INSERT' and old.deleted_at is null then
+        old_fields := jsonb_build_object('email', old.email);
+    end if;
+    if tg_op <> 'DELETE' and new.deleted_at is null then
+        new_fields := jsonb_build_object('email', new.email);
+    end if;
+    changed := case when tg_op = 'DELETE' then old else new end;
+    insert into user_changes (run_id, user_id, field, old_value, new_value)
+    select audit_run_id(), changed.id, c.field, c.old_value, c.new_value
+    from audit_field_changes(old_fields, new_fields) c;
+    return null;
+end $$;
+
+-- The arguments are the kind of leadership and the column of its club,
+-- region or committee, none for international leadership
+create function audit_leadership() returns trigger language plpgsql as $$
+declare
+    old_fields jsonb;
+    new_fields jsonb;
+    changed jsonb;
+begin
+    if tg_op <> 'INSERT' and old.deleted_at is null then
+        old_fields := jsonb_build_object(
+            'role', (select title from leadership_role where uid = old.role),
+            'end_date', old.end_date::text
+        );
+    end if;
+    if tg_op <> 'DELETE' and new.deleted_at is null then
+        new_fields := jsonb_build_object(
+            'role', (select title from leadership_role where uid = new.role),
+            'end_date', new.end_date::text
+        );
+    end if;
+    changed := to_jsonb(case when tg_op = 'DELETE' then old else new end);
+    insert into leadership_changes (
+        run_id, kind, entity, user_id, role, start_date, field, old_value, new_value
+    )
+    select audit_run_id(), tg_argv[0], (changed ->> tg_argv[1])::bigint, changed ->> 'user_id',
+        (changed ->> 'role')::bigint, (changed ->> 'start_date')::date, c.field, c.old_value,
+        c.new_value
+    from audit_field_changes(old_fields, new_fields) c;
+    return null;
+end $$;
+
+create trigger audit_members after insert or update or delete on members
+    for each row when (audit_enabled()) execute function audit_members();
+create trigger audit_users after insert or update or delete on users
+    for each row when (audit_enabled()) execute function audit_users();
+create trigger audit_leadership_club after insert or update or delete on leadership_club
+    for each row when (audit_enabled()) execute function audit_leadership('club', 'club');
+create trigger audit_leadership_region after insert or update or delete on leadership_region
+    for each row when (audit_enabled()) execute function audit_leadership('region', 'region');
+create trigger audit_leadership_international
+    after insert or update or delete on leadership_international
+    for each row when (audit_enabled()) execute function audit_leadership('international');
+create trigger audit_leadership_standing_committee
+    after insert or update or delete on leadership_standing_committee
+    for each row when (audit_enabled())
+    execute function audit_leadership('standing_committee', 'standing_committee');
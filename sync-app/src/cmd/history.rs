@@ -0,0 +1,23 @@
+use crate::{Result, cmd::print_json, history, settings::Settings};
+
+/// Show recent sync runs with their stats, warnings and errors
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// How many runs to show
+    #[arg(long, default_value_t = 20)]
+    limit: i64,
+    /// Only show failed runs
+    #[arg(long)]
+    failed: bool,
+    /// Only show the stats of one entity, like `members`
+    #[arg(long)]
+    entity: Option<String>,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let db = settings.app.db.connect().await?;
+        let runs = history::runs(&db, self.limit, self.failed, self.entity.as_deref()).await?;
+        print_json(&runs)
+    }
+}
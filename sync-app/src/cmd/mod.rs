@@ -1,6 +1,8 @@
 use crate::{Result, settings::Settings};
 
+pub mod history;
 pub mod migrate;
+pub mod purge;
 pub mod run;
 
 pub fn print_json<T: ?Sized + serde::Serialize>(value: &T) -> Result {
@@ -23,14 +25,18 @@ impl Cmd {
 #[derive(Debug, clap::Subcommand)]
 pub enum SyncCmd {
     Run(run::Cmd),
+    History(history::Cmd),
     Migrate(migrate::Cmd),
+    Purge(purge::Cmd),
 }
 
 impl SyncCmd {
     async fn run(&self, settings: Settings) -> Result {
         match self {
             Self::Run(cmd) => cmd.run(settings).await,
+            Self::History(cmd) => cmd.run(settings).await,
             Self::Migrate(cmd) => cmd.run(settings).await,
+            Self::Purge(cmd) => cmd.run(settings).await,
         }
     }
 }
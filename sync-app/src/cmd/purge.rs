@@ -0,0 +1,26 @@
+use crate::{Result, cmd::print_json, settings::Settings};
+use std::collections::BTreeMap;
+
+/// Hard delete rows that were soft deleted more than some days ago
+///
+/// Without the confirm flag this just counts the rows that would be purged
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Purge rows soft deleted more than this many days ago
+    #[arg(long, default_value_t = 30)]
+    days: u32,
+    #[arg(long)]
+    confirm: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let db = settings.app.db.connect().await?;
+        let counts = if self.confirm {
+            db::purge_deleted(&db, self.days).await?
+        } else {
+            db::count_deleted(&db, self.days).await?
+        };
+        print_json(&counts.into_iter().collect::<BTreeMap<_, _>>())
+    }
+}
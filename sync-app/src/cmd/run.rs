@@ -1,12 +1,59 @@
-use crate::{Result, cmd::print_json, settings::Settings, sync};
+use crate::{
+    Result,
+    cmd::print_json,
+    history,
+    settings::Settings,
+    sync::{self, Entity, SyncOptions},
+};
+use db::guard::Scope;
 
 /// Run the app database sync from the membership database
+///
+/// Each run is recorded in the sync history, failed runs with their error,
+/// and the changes it made to members, users and leadership in the audit log.
+/// A run can be limited to some entities, and to one club or region, without
+/// deleting anything outside of it.
 #[derive(Debug, clap::Args)]
-pub struct Cmd {}
+pub struct Cmd {
+    /// Delete rows even when more are missing than the delete guard allows
+    #[arg(long)]
+    force: bool,
+    /// Only sync these entities, writing what they reference as well
+    #[arg(long, value_enum, value_delimiter = ',')]
+    only: Vec<Entity>,
+    /// Only sync the club with this uid
+    #[arg(long, conflicts_with = "region")]
+    club: Option<i64>,
+    /// Only sync the region with this uid and its clubs
+    #[arg(long)]
+    region: Option<i64>,
+}
 
 impl Cmd {
-    pub async fn run(&self, settings: Settings) -> Result {
-        let stats = sync::run(&settings.app, &settings.ddb).await?;
-        print_json(&stats)
+    pub async fn run(&self, mut settings: Settings) -> Result {
+        settings.app.guard.force = self.force;
+        let scope = match (self.club, self.region) {
+            (Some(uid), _) => Scope::Club(uid),
+            (_, Some(uid)) => Scope::Region(uid),
+            _ => Scope::All,
+        };
+        let options = SyncOptions::new(self.only.iter().copied(), scope)?;
+        let db = settings.app.db.connect().await?;
+        let run_id = history::start(&db).await?;
+        match sync::run(&settings.app, &settings.ddb, &options, Some(run_id)).await {
+            Ok(report) => {
+                for warning in &report.warnings {
+                    tracing::warn!(run_id, "{warning}");
+                }
+                history::finish(&db, run_id, &report).await?;
+                print_json(&report.stats)
+            }
+            Err(e) => {
+                if let Err(record_err) = history::fail(&db, run_id, &e).await {
+                    tracing::error!(run_id, "recording failed run: {record_err}");
+                }
+                Err(e)
+            }
+        }
     }
 }
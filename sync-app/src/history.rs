@@ -0,0 +1,147 @@
+//! Sync run history.
+//!
+//! Every run of the portal sync is recorded in `sync_runs` with its outcome,
+//! and the stats of each synced entity in `sync_run_entities`.
+
+use crate::{Result, sync::SyncReport};
+use chrono::{DateTime, Utc};
+use futures::TryFutureExt;
+use sqlx::{PgPool, types::Json};
+use std::collections::BTreeMap;
+
+/// Record the start of a run, returning its id
+pub async fn start(db: &PgPool) -> Result<i64> {
+    let (id,): (i64,) = sqlx::query_as("insert into sync_runs default values returning id")
+        .fetch_one(db)
+        .await?;
+    Ok(id)
+}
+
+/// Record a successful run and its stats
+pub async fn finish(db: &PgPool, run_id: i64, report: &SyncReport) -> Result<()> {
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        r#"
+        update sync_runs
+        set status = 'succeeded', finished_at = now(), warnings = $2, extract = $3
+        where id = $1
+        "#,
+    )
+    .bind(run_id)
+    .bind(Json(&report.warnings))
+    .bind(Json(&report.extract))
+    .execute(&mut *tx)
+    .await?;
+
+    if !report.stats.is_empty() {
+        let mut builder = sqlx::QueryBuilder::new(
+            r#"insert into sync_run_entities
+                (run_id, entity, upserted, inserted, updated, unchanged, deleted, duration) "#,
+        );
+        builder.push_values(&report.stats, |mut b, (entity, stats)| {
+            b.push_bind(run_id)
+                .push_bind(entity)
+                .push_bind((stats.inserted + stats.updated) as i64)
+                .push_bind(stats.inserted as i64)
+                .push_bind(stats.updated as i64)
+                .push_bind(stats.unchanged as i64)
+                .push_bind(stats.deleted as i64)
+                .push_bind(stats.duration as i64);
+        });
+        builder.build().execute(&mut *tx).await?;
+    }
+    tx.commit().map_err(Into::into).await
+}
+
+/// Record a failed run
+pub async fn fail(db: &PgPool, run_id: i64, error: &anyhow::Error) -> Result<()> {
+    sqlx::query(
+        r#"
+        update sync_runs set status = 'failed', finished_at = now(), error = $2
+        where id = $1
+        "#,
+    )
+    .bind(run_id)
+    .bind(format!("{error:#}"))
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, sqlx::FromRow, serde::Serialize)]
+pub struct SyncRun {
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Json<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Milliseconds each membership database query took
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub extract: Json<BTreeMap<String, u64>>,
+    #[sqlx(skip)]
+    pub entities: Vec<EntityStats>,
+}
+
+#[derive(Debug, sqlx::FromRow, serde::Serialize)]
+pub struct EntityStats {
+    #[serde(skip)]
+    pub run_id: i64,
+    pub entity: String,
+    /// Rows written, inserted or updated
+    pub upserted: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inserted: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unchanged: Option<i64>,
+    pub deleted: i64,
+    /// Seconds
+    pub duration: i64,
+}
+
+/// The most recent runs with their stats, optionally only failed runs or
+/// only the stats of one entity
+pub async fn runs(
+    db: &PgPool,
+    limit: i64,
+    failed: bool,
+    entity: Option<&str>,
+) -> Result<Vec<SyncRun>> {
+    let mut runs: Vec<SyncRun> = sqlx::query_as(
+        r#"
+        select id, started_at, finished_at, status, warnings, error, extract
+        from sync_runs
+        where not $1 or status = 'failed'
+        order by started_at desc, id desc
+        limit $2
+        "#,
+    )
+    .bind(failed)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    let ids: Vec<i64> = runs.iter().map(|run| run.id).collect();
+    let entities: Vec<EntityStats> = sqlx::query_as(
+        r#"
+        select run_id, entity, upserted, inserted, updated, unchanged, deleted, duration
+        from sync_run_entities
+        where run_id = any($1) and ($2::text is null or entity = $2)
+        order by entity
+        "#,
+    )
+    .bind(&ids)
+    .bind(entity)
+    .fetch_all(db)
+    .await?;
+    for stats in entities {
+        if let Some(run) = runs.iter_mut().find(|run| run.id == stats.run_id) {
+            run.entities.push(stats);
+        }
+    }
+    Ok(runs)
+}
@@ -3,5 +3,6 @@ pub type Error = anyhow::Error;
 pub use anyhow::Context;
 
 pub mod cmd;
+pub mod history;
 pub mod settings;
 pub mod sync;
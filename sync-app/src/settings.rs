@@ -1,5 +1,6 @@
 use crate::{Context, Result};
 use config::{Config, Environment};
+use db::guard::{DeleteGuard, DeleteMode};
 use serde::Deserialize;
 use sqlx::{MySqlPool, PgPool};
 
@@ -34,6 +35,52 @@ fn default_log() -> String {
 pub struct AppSettings {
     #[serde(default)]
     pub db: DatabaseSettings,
+    #[serde(default)]
+    pub guard: GuardSettings,
+    /// Run the sync transaction with serializable isolation
+    #[serde(default)]
+    pub serializable: bool,
+}
+
+/// Limits on how many rows a sync may delete from a table, see
+/// [`db::guard::DeleteGuard::parse`] for the format, and whether rows are
+/// deleted or only marked deleted
+#[derive(Debug, Deserialize, Clone)]
+pub struct GuardSettings {
+    #[serde(default = "default_guard_limit")]
+    pub limit: String,
+    #[serde(default)]
+    pub tables: String,
+    #[serde(default)]
+    pub mode: DeleteMode,
+    /// Set by `--force`, never from the environment
+    #[serde(skip)]
+    pub force: bool,
+}
+
+impl Default for GuardSettings {
+    fn default() -> Self {
+        Self {
+            limit: default_guard_limit(),
+            tables: String::new(),
+            mode: DeleteMode::default(),
+            force: false,
+        }
+    }
+}
+
+fn default_guard_limit() -> String {
+    "20%".to_string()
+}
+
+impl GuardSettings {
+    pub fn guard(&self) -> Result<DeleteGuard> {
+        let mut guard =
+            DeleteGuard::parse(&self.limit, &self.tables).context("parsing delete guard")?;
+        guard.force = self.force;
+        guard.mode = self.mode;
+        Ok(guard)
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -63,18 +110,44 @@ impl DatabaseSettings {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct AciDatabaseSettings {
     #[serde(default = "default_ddb_url")]
     pub url: String,
+    /// Connections used to extract concurrently, each reading a snapshot of
+    /// the same state of the database. More than one, the default being one,
+    /// briefly takes a global read lock to begin the snapshots, which needs
+    /// the `RELOAD` privilege and one more connection.
+    #[serde(default = "default_ddb_connections")]
+    pub connections: u32,
+}
+
+impl Default for AciDatabaseSettings {
+    fn default() -> Self {
+        Self {
+            url: default_ddb_url(),
+            connections: default_ddb_connections(),
+        }
+    }
 }
 
 fn default_ddb_url() -> String {
     "".to_string()
 }
 
+fn default_ddb_connections() -> u32 {
+    1
+}
+
 impl AciDatabaseSettings {
     pub async fn connect(&self) -> Result<MySqlPool> {
-        ddb::connect(&self.url).await.context("opening database")
+        let connections = match self.connections {
+            0 | 1 => 1,
+            // One more to hold the lock while the snapshots begin
+            connections => connections + 1,
+        };
+        ddb::connect_with(&self.url, connections)
+            .await
+            .context("opening database")
     }
 }
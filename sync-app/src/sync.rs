@@ -2,23 +2,37 @@ use crate::{
     Result,
     settings::{AciDatabaseSettings, AppSettings},
 };
-use db::{address, brn, club, leadership, member, region, standing_committee, user};
+use db::{
+    UpsertStats, address, airstream, audit, brn, club, club_membership, entity_admin,
+    guard::{DeleteGuard, Scope},
+    leadership, member, membership_history, region, standing_committee, user, user_role,
+};
+use futures::TryFutureExt;
 use itertools::Itertools;
 use serde::Serialize;
-use sqlx::PgPool;
-use std::{collections::HashMap, time::Instant};
+use sqlx::{MySqlConnection, MySqlPool, PgConnection};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    sync::Mutex,
+    time::Instant,
+};
 
 #[derive(Debug, Serialize)]
 pub struct SyncStats {
-    pub upserted: u64,
+    pub inserted: u64,
+    pub updated: u64,
+    pub unchanged: u64,
     pub deleted: u64,
     pub duration: u64,
 }
 
 impl SyncStats {
-    fn new(upserted: u64, duration: u64) -> Self {
+    fn new(upserts: UpsertStats, duration: u64) -> Self {
         Self {
-            upserted,
+            inserted: upserts.inserted,
+            updated: upserts.updated,
+            unchanged: upserts.unchanged,
             deleted: 0,
             duration,
         }
@@ -27,8 +41,477 @@ impl SyncStats {
 
 pub type SyncStatsMap = std::collections::HashMap<String, SyncStats>;
 
+/// The outcome of a sync run: stats per entity, and anything that was
+/// skipped along the way
+#[derive(Debug, Serialize, Default)]
+pub struct SyncReport {
+    pub stats: SyncStatsMap,
+    pub warnings: Vec<String>,
+    /// Milliseconds each membership database query took
+    pub extract: ExtractTimings,
+}
+
+pub type ExtractTimings = BTreeMap<String, u64>;
+
+/// The entities the sync writes, named as in the stats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum Entity {
+    Regions,
+    Clubs,
+    StandingCommittees,
+    Users,
+    Members,
+    Addresses,
+    Brns,
+    LeadershipRoles,
+    LeadershipClub,
+    LeadershipRegion,
+    LeadershipInternational,
+    LeadershipStandingCommittee,
+    MembershipHistory,
+    Airstreams,
+    AirstreamOwnership,
+    UserRoles,
+    EntityAdmins,
+    UserAddresses,
+    ClubMemberships,
+}
+
+impl Entity {
+    pub const ALL: [Self; 19] = [
+        Self::Regions,
+        Self::Clubs,
+        Self::StandingCommittees,
+        Self::Users,
+        Self::Members,
+        Self::Addresses,
+        Self::Brns,
+        Self::LeadershipRoles,
+        Self::LeadershipClub,
+        Self::LeadershipRegion,
+        Self::LeadershipInternational,
+        Self::LeadershipStandingCommittee,
+        Self::MembershipHistory,
+        Self::Airstreams,
+        Self::AirstreamOwnership,
+        Self::UserRoles,
+        Self::EntityAdmins,
+        Self::UserAddresses,
+        Self::ClubMemberships,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Regions => "regions",
+            Self::Clubs => "clubs",
+            Self::StandingCommittees => "standing_committees",
+            Self::Users => "users",
+            Self::Members => "members",
+            Self::Addresses => "addresses",
+            Self::Brns => "brns",
+            Self::LeadershipRoles => "leadership_roles",
+            Self::LeadershipClub => "leadership_club",
+            Self::LeadershipRegion => "leadership_region",
+            Self::LeadershipInternational => "leadership_international",
+            Self::LeadershipStandingCommittee => "leadership_standing_committee",
+            Self::MembershipHistory => "membership_history",
+            Self::Airstreams => "airstreams",
+            Self::AirstreamOwnership => "airstream_ownership",
+            Self::UserRoles => "user_roles",
+            Self::EntityAdmins => "entity_admins",
+            Self::UserAddresses => "user_addresses",
+            Self::ClubMemberships => "club_memberships",
+        }
+    }
+
+    /// The app database table
+    pub fn table(&self) -> &'static str {
+        match self {
+            Self::LeadershipRoles => "leadership_role",
+            Self::UserAddresses => "private.user_addresses",
+            entity => entity.name(),
+        }
+    }
+
+    /// Everything the rows reference, directly or not, which has to be
+    /// written with them
+    fn references(&self) -> &'static [Self] {
+        match self {
+            Self::Clubs => &[Self::Regions],
+            Self::Members => &[Self::Users, Self::Clubs, Self::Regions],
+            Self::Addresses => &[Self::Users],
+            Self::Brns => &[Self::Members, Self::Users, Self::Clubs, Self::Regions],
+            Self::LeadershipClub => &[
+                Self::Clubs,
+                Self::Regions,
+                Self::Users,
+                Self::LeadershipRoles,
+            ],
+            Self::LeadershipRegion => &[Self::Regions, Self::Users, Self::LeadershipRoles],
+            Self::LeadershipInternational => &[Self::Users, Self::LeadershipRoles],
+            Self::LeadershipStandingCommittee => {
+                &[Self::StandingCommittees, Self::Users, Self::LeadershipRoles]
+            }
+            Self::Regions
+            | Self::StandingCommittees
+            | Self::Users
+            | Self::LeadershipRoles
+            | Self::MembershipHistory
+            | Self::Airstreams
+            | Self::UserRoles
+            | Self::EntityAdmins
+            | Self::UserAddresses => &[],
+            Self::AirstreamOwnership => &[Self::Airstreams],
+            Self::ClubMemberships => &[Self::Users, Self::Clubs, Self::Regions],
+        }
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a sync run covers. By default it syncs everything, it can be
+/// limited to some entities and to one club or region.
+///
+/// The selected entities are synced, rows missing from the membership
+/// database are deleted within the scope. What they reference is written as
+/// well, so it exists, but nothing is deleted from it.
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    only: BTreeSet<Entity>,
+    scope: Scope,
+}
+
+impl SyncOptions {
+    /// Sync `only` these entities, all when empty, within the `scope`
+    pub fn new(only: impl IntoIterator<Item = Entity>, scope: Scope) -> Result<Self> {
+        let options = Self {
+            only: only.into_iter().collect(),
+            scope,
+        };
+        for entity in &options.only {
+            if matches!(entity, Entity::Users | Entity::LeadershipRoles) {
+                anyhow::bail!(
+                    "{entity} are synced with the members and leadership referencing them"
+                );
+            }
+            if scope.condition(entity.table()).is_none() {
+                anyhow::bail!("{entity} can not be limited to {scope}");
+            }
+        }
+        Ok(options)
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    /// Whether the entity is synced, deleting rows that are gone
+    pub fn retains(&self, entity: Entity) -> bool {
+        (self.only.is_empty() || self.only.contains(&entity))
+            && self.scope.condition(entity.table()).is_some()
+    }
+
+    /// Whether rows of the entity are written, because it is synced or
+    /// referenced by a synced entity
+    pub fn writes(&self, entity: Entity) -> bool {
+        Entity::ALL.iter().any(|synced| {
+            self.retains(*synced) && (*synced == entity || synced.references().contains(&entity))
+        })
+    }
+}
+
+/// Reads the extract from snapshots of the same state of the database, one
+/// per connection. Every query takes a free snapshot, so with more than one
+/// connection queries run concurrently, and they all see the same data.
+struct Extractor {
+    snapshots: Mutex<Vec<ddb::Snapshot>>,
+    available: tokio::sync::Semaphore,
+    timings: Mutex<ExtractTimings>,
+}
+
+impl Extractor {
+    async fn new(ddb: &MySqlPool, connections: u32) -> Result<Self> {
+        let snapshots = ddb::Snapshot::begin_many(ddb, connections.max(1) as usize).await?;
+        Ok(Self {
+            available: tokio::sync::Semaphore::new(snapshots.len()),
+            snapshots: Mutex::new(snapshots),
+            timings: Mutex::default(),
+        })
+    }
+
+    /// Run a query if its rows are needed, or return nothing
+    async fn read_if<T, F>(&self, needed: bool, name: &str, query: F) -> Result<T>
+    where
+        T: Default,
+        F: for<'c> FnOnce(&'c mut MySqlConnection) -> ddb::Future<'c, T>,
+    {
+        if !needed {
+            return Ok(T::default());
+        }
+        self.read(name, query).await
+    }
+
+    /// Run a query, timing it from when it gets its snapshot
+    async fn read<T, F>(&self, name: &str, query: F) -> Result<T>
+    where
+        F: for<'c> FnOnce(&'c mut MySqlConnection) -> ddb::Future<'c, T>,
+    {
+        let _permit = self
+            .available
+            .acquire()
+            .await
+            .expect("extract snapshots closed");
+        let mut snapshot = self
+            .snapshots
+            .lock()
+            .expect("extract snapshots poisoned")
+            .pop()
+            .expect("a snapshot for every permit");
+        let start = Instant::now();
+        let result = query(&mut snapshot).await;
+        self.snapshots
+            .lock()
+            .expect("extract snapshots poisoned")
+            .push(snapshot);
+        let result = result?;
+        self.time(name, start);
+        Ok(result)
+    }
+
+    fn time(&self, name: &str, start: Instant) {
+        let duration = start.elapsed().as_millis() as u64;
+        tracing::info!(duration, "extracted {name}");
+        self.timings
+            .lock()
+            .expect("extract timings poisoned")
+            .insert(name.to_string(), duration);
+    }
+
+    async fn finish(self) -> Result<ExtractTimings> {
+        let snapshots = self
+            .snapshots
+            .into_inner()
+            .expect("extract snapshots poisoned");
+        for snapshot in snapshots {
+            snapshot.finish().await?;
+        }
+        Ok(self.timings.into_inner().expect("extract timings poisoned"))
+    }
+}
+
+/// Everything the sync reads from the membership database
+struct Extract {
+    regions: Vec<ddb::regions::Region>,
+    clubs: Vec<ddb::clubs::Club>,
+    standing_committees: Vec<ddb::standing_committees::StandingCommittee>,
+    members: Vec<ddb::members::Member>,
+    addresses: HashMap<u64, ddb::members::Address>,
+    club_leadership: Vec<ddb::leadership::Leadership>,
+    region_leadership: Vec<ddb::leadership::Leadership>,
+    international_leadership: Vec<ddb::leadership::Leadership>,
+    standing_committee_leadership: Vec<ddb::leadership::Leadership>,
+    membership_history: Vec<ddb::members::MembershipPeriod>,
+    international_history: Vec<ddb::members::InternationalMembershipPeriod>,
+    airstreams: Vec<ddb::airstreams::Airstream>,
+    user_roles: Vec<ddb::roles::UserRole>,
+    entity_admins: Vec<ddb::roles::MicrositeAdmin>,
+    user_addresses: Vec<ddb::addresses::Address>,
+}
+
+/// Read everything the sync writes from the membership database, running
+/// independent queries concurrently when there is more than one connection.
+/// All queries see the same state of the database either way.
+async fn extract(
+    ddb: &MySqlPool,
+    connections: u32,
+    options: &SyncOptions,
+) -> Result<(Extract, ExtractTimings)> {
+    use ddb::leadership::DateFilter;
+    let writes = |entity| options.writes(entity);
+    let scope = options.scope();
+    let extractor = Extractor::new(ddb, connections).await?;
+    let regions = extractor.read_if(writes(Entity::Regions), "regions", |c| {
+        Box::pin(async move {
+            match scope {
+                Scope::All => ddb::regions::all(c).await,
+                Scope::Region(uid) => {
+                    ddb::regions::by_uid(c, uid as u64)
+                        .map_ok(Vec::from_iter)
+                        .await
+                }
+                // Only the region of the club, which the club references
+                Scope::Club(uid) => {
+                    let club = ddb::clubs::by_uid(&mut *c, uid as u64).await?;
+                    match club.and_then(|club| club.region) {
+                        Some(region) => {
+                            ddb::regions::by_uid(c, region).map_ok(Vec::from_iter).await
+                        }
+                        None => Ok(vec![]),
+                    }
+                }
+            }
+        })
+    });
+    let clubs = extractor.read_if(writes(Entity::Clubs), "clubs", |c| match scope {
+        Scope::All => Box::pin(ddb::clubs::all(c)),
+        Scope::Club(uid) => Box::pin(ddb::clubs::by_uid(c, uid as u64).map_ok(Vec::from_iter)),
+        Scope::Region(uid) => Box::pin(ddb::clubs::all(c).map_ok(move |clubs| {
+            clubs
+                .into_iter()
+                .filter(|club| club.region == Some(uid as u64))
+                .collect_vec()
+        })),
+    });
+    let members_with_addresses = async {
+        let needed = [
+            Entity::Members,
+            Entity::Addresses,
+            Entity::Brns,
+            Entity::ClubMemberships,
+        ]
+        .into_iter()
+        .any(writes);
+        // Every member, also in a scoped run, which only writes the members
+        // of its clubs but must not delete members who moved or lapsed
+        let members = extractor
+            .read_if(needed, "members", |c| Box::pin(ddb::members::all(c)))
+            .await?;
+        let uids = members
+            .iter()
+            .map(|member| member.primary.uid)
+            .collect_vec();
+        let addresses = extractor
+            .read_if(writes(Entity::Addresses), "addresses", |c| {
+                Box::pin(ddb::members::mailing_address::by_uids(c, uids))
+            })
+            .await?;
+        Ok::<_, anyhow::Error>((members, addresses))
+    };
+    let (
+        regions,
+        clubs,
+        standing_committees,
+        (members, addresses),
+        mut club_leadership,
+        region_leadership,
+        international_leadership,
+        standing_committee_leadership,
+        membership_history,
+        international_history,
+        airstreams,
+        user_roles,
+        entity_admins,
+        user_addresses,
+    ) = futures::try_join!(
+        regions,
+        clubs,
+        extractor.read_if(
+            writes(Entity::StandingCommittees),
+            "standing_committees",
+            |c| Box::pin(ddb::standing_committees::all(c)),
+        ),
+        members_with_addresses,
+        extractor.read_if(
+            writes(Entity::LeadershipClub),
+            "leadership_club",
+            |c| match scope {
+                Scope::Club(uid) =>
+                    Box::pin(ddb::leadership::for_club(c, uid as u64, DateFilter::All,)),
+                _ => Box::pin(ddb::leadership::for_all_clubs(c, DateFilter::All)),
+            },
+        ),
+        extractor.read_if(
+            writes(Entity::LeadershipRegion),
+            "leadership_region",
+            |c| match scope {
+                Scope::Region(uid) =>
+                    Box::pin(ddb::leadership::for_region(c, uid as u64, DateFilter::All,)),
+                _ => Box::pin(ddb::leadership::for_all_regions(c, DateFilter::All)),
+            },
+        ),
+        extractor.read_if(
+            writes(Entity::LeadershipInternational),
+            "leadership_international",
+            |c| Box::pin(ddb::leadership::for_international(c, DateFilter::All)),
+        ),
+        extractor.read_if(
+            writes(Entity::LeadershipStandingCommittee),
+            "leadership_standing_committee",
+            |c| {
+                Box::pin(ddb::leadership::for_all_standing_committees(
+                    c,
+                    DateFilter::All,
+                ))
+            },
+        ),
+        extractor.read_if(
+            writes(Entity::MembershipHistory) || writes(Entity::ClubMemberships),
+            "membership_history",
+            |c| Box::pin(ddb::members::history_all(c)),
+        ),
+        extractor.read_if(
+            writes(Entity::MembershipHistory),
+            "international_membership_history",
+            |c| Box::pin(ddb::members::international_history_all(c)),
+        ),
+        extractor.read_if(writes(Entity::Airstreams), "airstreams", |c| {
+            Box::pin(ddb::airstreams::all(c))
+        }),
+        extractor.read_if(writes(Entity::UserRoles), "user_roles", |c| {
+            Box::pin(ddb::roles::all(c))
+        }),
+        extractor.read_if(writes(Entity::EntityAdmins), "entity_admins", |c| {
+            Box::pin(ddb::roles::microsite_admins(c))
+        }),
+        extractor.read_if(writes(Entity::UserAddresses), "user_addresses", |c| {
+            Box::pin(ddb::addresses::all(c))
+        }),
+    )?;
+    let timings = extractor.finish().await?;
+
+    match scope {
+        Scope::Club(uid) if writes(Entity::Clubs) && clubs.is_empty() => {
+            anyhow::bail!("club {uid} not found")
+        }
+        Scope::Region(uid) if writes(Entity::Regions) && regions.is_empty() => {
+            anyhow::bail!("region {uid} not found")
+        }
+        // There is no query for the leadership of all clubs of a region
+        Scope::Region(_) => {
+            club_leadership.retain(|lead| clubs.iter().any(|club| club.uid == lead.entity_uid))
+        }
+        _ => {}
+    }
+
+    Ok((
+        Extract {
+            regions,
+            clubs,
+            standing_committees,
+            members,
+            addresses,
+            club_leadership,
+            region_leadership,
+            international_leadership,
+            standing_committee_leadership,
+            membership_history,
+            international_history,
+            airstreams,
+            user_roles,
+            entity_admins,
+            user_addresses,
+        },
+        timings,
+    ))
+}
+
 pub async fn upsert_regions<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     regions: I,
 ) -> Result<((String, SyncStats), Vec<region::Region>)>
 where
@@ -36,22 +519,23 @@ where
 {
     let start = Instant::now();
     let db_regions = regions.into_iter().map(region::Region::from).collect_vec();
-    let upserted = region::upsert_many(db, &db_regions).await?;
+    let upserts = region::upsert_many(&mut *db, &db_regions).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted regions");
+    tracing::info!(?upserts, duration, "upserted regions");
     Ok((
-        ("regions".to_string(), SyncStats::new(upserted, duration)),
+        ("regions".to_string(), SyncStats::new(upserts, duration)),
         db_regions,
     ))
 }
 
 pub async fn retain_regions(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_regions: &[region::Region],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = region::retain(db, db_regions).await?;
+    let deleted = region::retain(&mut *db, guard, db_regions).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc regions");
     stats.1.deleted = deleted;
@@ -60,7 +544,7 @@ pub async fn retain_regions(
 }
 
 pub async fn upsert_clubs<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     clubs: I,
 ) -> Result<((String, SyncStats), Vec<club::Club>)>
 where
@@ -68,22 +552,23 @@ where
 {
     let start = Instant::now();
     let db_clubs = clubs.into_iter().map(club::Club::from).collect_vec();
-    let upserted = club::upsert_many(db, &db_clubs).await?;
+    let upserts = club::upsert_many(&mut *db, &db_clubs).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted clubs");
+    tracing::info!(?upserts, duration, "upserted clubs");
     Ok((
-        ("clubs".into(), SyncStats::new(upserted, duration)),
+        ("clubs".into(), SyncStats::new(upserts, duration)),
         db_clubs,
     ))
 }
 
 pub async fn retain_clubs(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_clubs: &[club::Club],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = club::retain(db, db_clubs).await?;
+    let deleted = club::retain(&mut *db, guard, db_clubs).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc clubs");
     stats.1.deleted = deleted;
@@ -92,7 +577,7 @@ pub async fn retain_clubs(
 }
 
 pub async fn upsert_users<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     users: I,
 ) -> Result<((String, SyncStats), Vec<user::User>)>
 where
@@ -100,22 +585,23 @@ where
 {
     let start = Instant::now();
     let db_users = users.into_iter().map(user::User::from).collect_vec();
-    let upserted = user::upsert_many(db, &db_users).await?;
+    let upserts = user::upsert_many(&mut *db, &db_users).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted users");
+    tracing::info!(?upserts, duration, "upserted users");
     Ok((
-        ("users".into(), SyncStats::new(upserted, duration)),
+        ("users".into(), SyncStats::new(upserts, duration)),
         db_users,
     ))
 }
 
 pub async fn retain_users(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_users: &[user::User],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = user::retain(db, db_users).await?;
+    let deleted = user::retain(&mut *db, guard, db_users).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc users");
     stats.1.deleted = deleted;
@@ -124,7 +610,7 @@ pub async fn retain_users(
 }
 
 pub async fn upsert_members<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     members: I,
 ) -> Result<((String, SyncStats), Vec<member::Member>)>
 where
@@ -132,22 +618,23 @@ where
 {
     let start = Instant::now();
     let db_members = members.into_iter().map(member::Member::from).collect_vec();
-    let upserted = member::upsert_many(db, &db_members).await?;
+    let upserts = member::upsert_many(&mut *db, &db_members).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted members");
+    tracing::info!(?upserts, duration, "upserted members");
     Ok((
-        ("members".into(), SyncStats::new(upserted, duration)),
+        ("members".into(), SyncStats::new(upserts, duration)),
         db_members,
     ))
 }
 
 pub async fn retain_members(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_members: &[member::Member],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = member::retain(db, db_members).await?;
+    let deleted = member::retain(&mut *db, guard, db_members).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc members");
     stats.1.deleted = deleted;
@@ -156,7 +643,7 @@ pub async fn retain_members(
 }
 
 pub async fn upsert_addresses(
-    db: &PgPool,
+    db: &mut PgConnection,
     ddb_members: &[ddb::members::Member],
     ddb_addresses: &mut HashMap<u64, ddb::members::Address>,
 ) -> Result<((String, SyncStats), Vec<address::Address>)> {
@@ -169,23 +656,23 @@ pub async fn upsert_addresses(
                 .map(|ddb_address| ddb_address.to_db_address_for_member(ddb_member))
         })
         .collect_vec();
-    let upserted = address::upsert_many(db, &db_addresses).await?;
-    let deleted = address::retain(db, &db_addresses).await?;
+    let upserts = address::upsert_many(&mut *db, &db_addresses).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(deleted, upserted, duration, "upserted addresses");
+    tracing::info!(?upserts, duration, "upserted addresses");
     Ok((
-        ("addresses".to_string(), SyncStats::new(upserted, duration)),
+        ("addresses".to_string(), SyncStats::new(upserts, duration)),
         db_addresses,
     ))
 }
 
 pub async fn retain_addresses(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_addresses: &[address::Address],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = address::retain(db, db_addresses).await?;
+    let deleted = address::retain(&mut *db, guard, db_addresses).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc addresses");
     stats.1.deleted = deleted;
@@ -193,27 +680,67 @@ pub async fn retain_addresses(
     Ok(())
 }
 
+pub async fn upsert_user_addresses<I>(
+    db: &mut PgConnection,
+    addresses: I,
+) -> Result<((String, SyncStats), Vec<address::UserAddress>)>
+where
+    I: IntoIterator<Item = ddb::addresses::Address>,
+{
+    let start = Instant::now();
+    let db_addresses = addresses
+        .into_iter()
+        .map(address::UserAddress::from)
+        .collect_vec();
+    let upserts = address::upsert_user_addresses(&mut *db, &db_addresses).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(?upserts, duration, "upserted user addresses");
+    Ok((
+        (
+            "user_addresses".to_string(),
+            SyncStats::new(upserts, duration),
+        ),
+        db_addresses,
+    ))
+}
+
+pub async fn retain_user_addresses(
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
+    stats: &mut (String, SyncStats),
+    db_addresses: &[address::UserAddress],
+) -> Result<()> {
+    let start = Instant::now();
+    let deleted = address::retain_user_addresses(&mut *db, guard, db_addresses).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(deleted, duration, "gc user addresses");
+    stats.1.deleted = deleted;
+    stats.1.duration += duration;
+    Ok(())
+}
+
 pub async fn upsert_brns(
-    db: &PgPool,
+    db: &mut PgConnection,
     db_brns: &[brn::Brn],
 ) -> Result<((String, SyncStats), Vec<brn::Brn>)> {
     let start = Instant::now();
-    let upserted = brn::upsert_many(db, db_brns).await?;
+    let upserts = brn::upsert_many(&mut *db, db_brns).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted brns");
+    tracing::info!(?upserts, duration, "upserted brns");
     Ok((
-        ("brns".to_string(), SyncStats::new(upserted, duration)),
+        ("brns".to_string(), SyncStats::new(upserts, duration)),
         db_brns.to_vec(),
     ))
 }
 
 pub async fn retain_brns(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_brns: &[brn::Brn],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = brn::retain(db, db_brns).await?;
+    let deleted = brn::retain(&mut *db, guard, db_brns).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc brns");
     stats.1.deleted = deleted;
@@ -221,10 +748,247 @@ pub async fn retain_brns(
     Ok(())
 }
 
+pub async fn upsert_club_memberships(
+    db: &mut PgConnection,
+    db_memberships: Vec<club_membership::ClubMembership>,
+) -> Result<((String, SyncStats), Vec<club_membership::ClubMembership>)> {
+    let start = Instant::now();
+    let upserts = club_membership::upsert_many(&mut *db, &db_memberships).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(?upserts, duration, "upserted club memberships");
+    Ok((
+        (
+            "club_memberships".to_string(),
+            SyncStats::new(upserts, duration),
+        ),
+        db_memberships,
+    ))
+}
+
+pub async fn retain_club_memberships(
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
+    stats: &mut (String, SyncStats),
+    db_memberships: &[club_membership::ClubMembership],
+) -> Result<()> {
+    let start = Instant::now();
+    let deleted = club_membership::retain(&mut *db, guard, db_memberships).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(deleted, duration, "gc club memberships");
+    stats.1.deleted = deleted;
+    stats.1.duration += duration;
+    Ok(())
+}
+
+// ========== Membership History Sync ==========
+
+pub async fn upsert_membership_history(
+    db: &mut PgConnection,
+    periods: Vec<ddb::members::MembershipPeriod>,
+    international: Vec<ddb::members::InternationalMembershipPeriod>,
+    skipped: &mut usize,
+) -> Result<((String, SyncStats), Vec<membership_history::Period>)> {
+    let start = Instant::now();
+    let international = international.into_iter().filter_map(|period| {
+        let paragraph_id = period.paragraph_id;
+        let db_period = period.to_db_period();
+        if db_period.is_none() {
+            *skipped += 1;
+            tracing::warn!(
+                paragraph_id,
+                "international membership without user or join date"
+            );
+        }
+        db_period
+    });
+    let db_periods = periods
+        .into_iter()
+        .map(membership_history::Period::from)
+        .chain(international)
+        .collect_vec();
+    let upserts = membership_history::upsert_many(&mut *db, &db_periods).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(?upserts, duration, "upserted membership history");
+    Ok((
+        (
+            "membership_history".to_string(),
+            SyncStats::new(upserts, duration),
+        ),
+        db_periods,
+    ))
+}
+
+pub async fn retain_membership_history(
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
+    stats: &mut (String, SyncStats),
+    db_periods: &[membership_history::Period],
+) -> Result<()> {
+    let start = Instant::now();
+    let deleted = membership_history::retain(&mut *db, guard, db_periods).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(deleted, duration, "gc membership history");
+    stats.1.deleted = deleted;
+    stats.1.duration += duration;
+    Ok(())
+}
+
+// ========== Airstream Sync ==========
+
+/// Upsert the airstreams of the ownership records, and the ownership itself
+/// if it is written
+pub async fn upsert_airstreams(
+    db: &mut PgConnection,
+    ddb_airstreams: Vec<ddb::airstreams::Airstream>,
+    ownership: bool,
+) -> Result<(
+    (String, SyncStats),
+    Vec<airstream::Airstream>,
+    (String, SyncStats),
+    Vec<airstream::Ownership>,
+)> {
+    let start = Instant::now();
+    let db_ownership = ddb_airstreams
+        .into_iter()
+        .map(airstream::Ownership::from)
+        .collect_vec();
+    let db_airstreams = db_ownership
+        .iter()
+        .map(|owned| owned.airstream.clone())
+        .unique_by(|airstream| airstream.airstream_id)
+        .collect_vec();
+    let upserts = airstream::upsert_many(&mut *db, &db_airstreams).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(?upserts, duration, "upserted airstreams");
+    let airstream_stats = ("airstreams".to_string(), SyncStats::new(upserts, duration));
+
+    let start = Instant::now();
+    let db_ownership = if ownership { db_ownership } else { vec![] };
+    let upserts = airstream::upsert_ownership(&mut *db, &db_ownership).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(?upserts, duration, "upserted airstream ownership");
+    Ok((
+        airstream_stats,
+        db_airstreams,
+        (
+            "airstream_ownership".to_string(),
+            SyncStats::new(upserts, duration),
+        ),
+        db_ownership,
+    ))
+}
+
+pub async fn retain_airstreams(
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
+    stats: &mut (String, SyncStats),
+    db_airstreams: &[airstream::Airstream],
+) -> Result<()> {
+    let start = Instant::now();
+    let deleted = airstream::retain(&mut *db, guard, db_airstreams).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(deleted, duration, "gc airstreams");
+    stats.1.deleted = deleted;
+    stats.1.duration += duration;
+    Ok(())
+}
+
+pub async fn retain_airstream_ownership(
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
+    stats: &mut (String, SyncStats),
+    db_ownership: &[airstream::Ownership],
+) -> Result<()> {
+    let start = Instant::now();
+    let deleted = airstream::retain_ownership(&mut *db, guard, db_ownership).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(deleted, duration, "gc airstream ownership");
+    stats.1.deleted = deleted;
+    stats.1.duration += duration;
+    Ok(())
+}
+
+// ========== Permissions Sync ==========
+
+pub async fn upsert_user_roles<I>(
+    db: &mut PgConnection,
+    roles: I,
+) -> Result<((String, SyncStats), Vec<user_role::UserRole>)>
+where
+    I: IntoIterator<Item = ddb::roles::UserRole>,
+{
+    let start = Instant::now();
+    let db_roles = roles
+        .into_iter()
+        .map(user_role::UserRole::from)
+        .collect_vec();
+    let upserts = user_role::upsert_many(&mut *db, &db_roles).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(?upserts, duration, "upserted user roles");
+    Ok((
+        ("user_roles".to_string(), SyncStats::new(upserts, duration)),
+        db_roles,
+    ))
+}
+
+pub async fn retain_user_roles(
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
+    stats: &mut (String, SyncStats),
+    db_roles: &[user_role::UserRole],
+) -> Result<()> {
+    let start = Instant::now();
+    let deleted = user_role::retain(&mut *db, guard, db_roles).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(deleted, duration, "gc user roles");
+    stats.1.deleted = deleted;
+    stats.1.duration += duration;
+    Ok(())
+}
+
+pub async fn upsert_entity_admins<I>(
+    db: &mut PgConnection,
+    admins: I,
+) -> Result<((String, SyncStats), Vec<entity_admin::EntityAdmin>)>
+where
+    I: IntoIterator<Item = ddb::roles::MicrositeAdmin>,
+{
+    let start = Instant::now();
+    let db_admins = admins
+        .into_iter()
+        .map(entity_admin::EntityAdmin::from)
+        .collect_vec();
+    let upserts = entity_admin::upsert_many(&mut *db, &db_admins).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(?upserts, duration, "upserted entity admins");
+    Ok((
+        (
+            "entity_admins".to_string(),
+            SyncStats::new(upserts, duration),
+        ),
+        db_admins,
+    ))
+}
+
+pub async fn retain_entity_admins(
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
+    stats: &mut (String, SyncStats),
+    db_admins: &[entity_admin::EntityAdmin],
+) -> Result<()> {
+    let start = Instant::now();
+    let deleted = entity_admin::retain(&mut *db, guard, db_admins).await?;
+    let duration = start.elapsed().as_secs();
+    tracing::info!(deleted, duration, "gc entity admins");
+    stats.1.deleted = deleted;
+    stats.1.duration += duration;
+    Ok(())
+}
+
 // ========== Leadership Role Sync ==========
 
 pub async fn upsert_roles<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     roles: I,
 ) -> Result<((String, SyncStats), Vec<leadership::Role>)>
 where
@@ -232,25 +996,26 @@ where
 {
     let start = Instant::now();
     let db_roles = roles.into_iter().map(leadership::Role::from).collect_vec();
-    let upserted = leadership::upsert_roles(db, &db_roles).await?;
+    let upserts = leadership::upsert_roles(&mut *db, &db_roles).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted leadership roles");
+    tracing::info!(?upserts, duration, "upserted leadership roles");
     Ok((
         (
             "leadership_roles".to_string(),
-            SyncStats::new(upserted, duration),
+            SyncStats::new(upserts, duration),
         ),
         db_roles,
     ))
 }
 
 pub async fn retain_roles(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_roles: &[leadership::Role],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = leadership::retain_roles(db, db_roles).await?;
+    let deleted = leadership::retain_roles(&mut *db, guard, db_roles).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc leadership roles");
     stats.1.deleted = deleted;
@@ -261,7 +1026,7 @@ pub async fn retain_roles(
 // ========== Club Leadership Sync ==========
 
 pub async fn upsert_club_leadership<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     leadership: I,
 ) -> Result<((String, SyncStats), Vec<club::Leadership>)>
 where
@@ -272,25 +1037,26 @@ where
         .into_iter()
         .map(club::Leadership::from)
         .collect_vec();
-    let upserted = club::upsert_leadership(db, &db_leadership).await?;
+    let upserts = club::upsert_leadership(&mut *db, &db_leadership).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted club leadership");
+    tracing::info!(?upserts, duration, "upserted club leadership");
     Ok((
         (
             "leadership_club".to_string(),
-            SyncStats::new(upserted, duration),
+            SyncStats::new(upserts, duration),
         ),
         db_leadership,
     ))
 }
 
 pub async fn retain_club_leadership(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_leadership: &[club::Leadership],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = club::retain_leadership(db, db_leadership).await?;
+    let deleted = club::retain_leadership(&mut *db, guard, db_leadership).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc club leadership");
     stats.1.deleted = deleted;
@@ -301,7 +1067,7 @@ pub async fn retain_club_leadership(
 // ========== Region Leadership Sync ==========
 
 pub async fn upsert_region_leadership<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     leadership: I,
 ) -> Result<((String, SyncStats), Vec<region::Leadership>)>
 where
@@ -312,25 +1078,26 @@ where
         .into_iter()
         .map(region::Leadership::from)
         .collect_vec();
-    let upserted = region::upsert_leadership(db, &db_leadership).await?;
+    let upserts = region::upsert_leadership(&mut *db, &db_leadership).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted region leadership");
+    tracing::info!(?upserts, duration, "upserted region leadership");
     Ok((
         (
             "leadership_region".to_string(),
-            SyncStats::new(upserted, duration),
+            SyncStats::new(upserts, duration),
         ),
         db_leadership,
     ))
 }
 
 pub async fn retain_region_leadership(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_leadership: &[region::Leadership],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = region::retain_leadership(db, db_leadership).await?;
+    let deleted = region::retain_leadership(&mut *db, guard, db_leadership).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc region leadership");
     stats.1.deleted = deleted;
@@ -341,7 +1108,7 @@ pub async fn retain_region_leadership(
 // ========== International Leadership Sync ==========
 
 pub async fn upsert_international_leadership<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     leadership: I,
 ) -> Result<((String, SyncStats), Vec<leadership::Leadership>)>
 where
@@ -352,25 +1119,26 @@ where
         .into_iter()
         .map(leadership::Leadership::from)
         .collect_vec();
-    let upserted = leadership::upsert_leadership(db, &db_leadership).await?;
+    let upserts = leadership::upsert_leadership(&mut *db, &db_leadership).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted international leadership");
+    tracing::info!(?upserts, duration, "upserted international leadership");
     Ok((
         (
             "leadership_international".to_string(),
-            SyncStats::new(upserted, duration),
+            SyncStats::new(upserts, duration),
         ),
         db_leadership,
     ))
 }
 
 pub async fn retain_international_leadership(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_leadership: &[leadership::Leadership],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = leadership::retain_leadership(db, db_leadership).await?;
+    let deleted = leadership::retain_leadership(&mut *db, guard, db_leadership).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc international leadership");
     stats.1.deleted = deleted;
@@ -381,7 +1149,7 @@ pub async fn retain_international_leadership(
 // ========== Standing Committee Sync ==========
 
 pub async fn upsert_standing_committees<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     committees: I,
 ) -> Result<(
     (String, SyncStats),
@@ -395,25 +1163,26 @@ where
         .into_iter()
         .map(standing_committee::StandingCommittee::from)
         .collect_vec();
-    let upserted = standing_committee::upsert_many(db, &db_committees).await?;
+    let upserts = standing_committee::upsert_many(&mut *db, &db_committees).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted standing committees");
+    tracing::info!(?upserts, duration, "upserted standing committees");
     Ok((
         (
             "standing_committees".to_string(),
-            SyncStats::new(upserted, duration),
+            SyncStats::new(upserts, duration),
         ),
         db_committees,
     ))
 }
 
 pub async fn retain_standing_committees(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_committees: &[standing_committee::StandingCommittee],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = standing_committee::retain(db, db_committees).await?;
+    let deleted = standing_committee::retain(&mut *db, guard, db_committees).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc standing committees");
     stats.1.deleted = deleted;
@@ -424,7 +1193,7 @@ pub async fn retain_standing_committees(
 // ========== Standing Committee Leadership Sync ==========
 
 pub async fn upsert_standing_committee_leadership<I>(
-    db: &PgPool,
+    db: &mut PgConnection,
     leadership: I,
 ) -> Result<((String, SyncStats), Vec<standing_committee::Leadership>)>
 where
@@ -435,25 +1204,26 @@ where
         .into_iter()
         .map(standing_committee::Leadership::from)
         .collect_vec();
-    let upserted = standing_committee::upsert_leadership(db, &db_leadership).await?;
+    let upserts = standing_committee::upsert_leadership(&mut *db, &db_leadership).await?;
     let duration = start.elapsed().as_secs();
-    tracing::info!(upserted, duration, "upserted standing committee leadership");
+    tracing::info!(?upserts, duration, "upserted standing committee leadership");
     Ok((
         (
             "leadership_standing_committee".to_string(),
-            SyncStats::new(upserted, duration),
+            SyncStats::new(upserts, duration),
         ),
         db_leadership,
     ))
 }
 
 pub async fn retain_standing_committee_leadership(
-    db: &PgPool,
+    db: &mut PgConnection,
+    guard: &DeleteGuard,
     stats: &mut (String, SyncStats),
     db_leadership: &[standing_committee::Leadership],
 ) -> Result<()> {
     let start = Instant::now();
-    let deleted = standing_committee::retain_leadership(db, db_leadership).await?;
+    let deleted = standing_committee::retain_leadership(&mut *db, guard, db_leadership).await?;
     let duration = start.elapsed().as_secs();
     tracing::info!(deleted, duration, "gc standing committee leadership");
     stats.1.deleted = deleted;
@@ -461,43 +1231,141 @@ pub async fn retain_standing_committee_leadership(
     Ok(())
 }
 
-#[tracing::instrument(skip_all, name = "sync")]
+/// Sync the app database from the membership database in a single
+/// transaction, recording the changes to members, users and leadership under
+/// `run_id`
+#[tracing::instrument(skip_all, name = "sync", fields(scope = %options.scope()))]
 pub async fn run(
     app_settings: &AppSettings,
     ddb_settings: &AciDatabaseSettings,
-) -> Result<SyncStatsMap> {
+    options: &SyncOptions,
+    run_id: Option<i64>,
+) -> Result<SyncReport> {
     let ddb = ddb_settings.connect().await?;
     let db = app_settings.db.connect().await?;
+    let guard = &DeleteGuard {
+        scope: options.scope(),
+        ..app_settings.guard.guard()?
+    };
 
     tracing::info!("starting sync");
     let start = Instant::now();
 
-    let ddb_regions = ddb::regions::all(&ddb).await?;
-    let ddb_clubs = ddb::clubs::all(&ddb).await?;
-    let ddb_standing_committees = ddb::standing_committees::all(&ddb).await?;
-    let ddb_members = ddb::members::all(&ddb).await?;
+    let (extracted, extract_timings) = extract(&ddb, ddb_settings.connections, options).await?;
+    let Extract {
+        regions: ddb_regions,
+        clubs: ddb_clubs,
+        standing_committees: ddb_standing_committees,
+        members: ddb_members,
+        addresses: mut ddb_addresses,
+        club_leadership: ddb_club_leadership,
+        region_leadership: ddb_region_leadership,
+        international_leadership: ddb_international_leadership,
+        standing_committee_leadership: ddb_standing_committee_leadership,
+        membership_history: mut ddb_membership_history,
+        international_history: ddb_international_history,
+        airstreams: ddb_airstreams,
+        user_roles: ddb_user_roles,
+        entity_admins: ddb_entity_admins,
+        user_addresses: ddb_user_addresses,
+    } = extracted;
+    tracing::info!(
+        duration = start.elapsed().as_secs(),
+        connections = ddb_settings.connections,
+        "extract complete"
+    );
+    // Only members whose home club is in scope are written. The others
+    // belong to other clubs, their rows are kept as they are.
+    let scope = options.scope();
+    let (mut ddb_members, ddb_other_members): (Vec<_>, Vec<_>) =
+        ddb_members.into_iter().partition(|ddb_member| {
+            let club = &ddb_member.local_club;
+            scope.includes(club.uid as i64, club.region.map(|region| region as i64))
+        });
     let db_brns = ddb_members
         .iter()
+        .filter(|_| options.writes(Entity::Brns))
         .flat_map(Into::<Vec<brn::Brn>>::into)
         .collect_vec();
-    let mut ddb_addresses = ddb::members::mailing_address::for_members(&ddb, &ddb_members).await?;
-
-    // Fetch leadership data from DDB (all historical)
-    let ddb_club_leadership =
-        ddb::leadership::for_all_clubs(&ddb, ddb::leadership::DateFilter::All).await?;
-    let ddb_region_leadership =
-        ddb::leadership::for_all_regions(&ddb, ddb::leadership::DateFilter::All).await?;
-    let ddb_international_leadership =
-        ddb::leadership::for_international(&ddb, ddb::leadership::DateFilter::All).await?;
-    let ddb_standing_committee_leadership =
-        ddb::leadership::for_all_standing_committees(&ddb, ddb::leadership::DateFilter::All)
+    let kept_brns = ddb_other_members
+        .iter()
+        .filter(|_| options.writes(Entity::Brns))
+        .flat_map(Into::<Vec<brn::Brn>>::into)
+        .collect_vec();
+    let kept_addresses = ddb_other_members
+        .iter()
+        .filter_map(|ddb_member| {
+            ddb_addresses
+                .remove(&ddb_member.primary.uid)
+                .map(|ddb_address| ddb_address.to_db_address_for_member(ddb_member))
+        })
+        .collect_vec();
+
+    // Collect all unique roles from all leadership types
+    let ddb_roles = ddb_club_leadership
+        .iter()
+        .chain(ddb_region_leadership.iter())
+        .chain(ddb_international_leadership.iter())
+        .chain(ddb_standing_committee_leadership.iter())
+        .map(|lead| lead.role.clone())
+        .unique_by(|role| role.uid)
+        .collect_vec();
+
+    // All writes happen in one transaction, so readers never see a half
+    // synced portal and a failure rolls back everything
+    let mut tx = db.begin().await?;
+    if app_settings.serializable {
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE")
+            .execute(&mut *tx)
             .await?;
+    }
+    audit::start(&mut tx, run_id).await?;
+
+    // Upsert roles first (no dependencies)
+    let (mut role_stats, db_roles) = upsert_roles(&mut tx, ddb_roles).await?;
 
-    // Collect all users from members AND leadership records
+    let (mut region_stats, db_regions) = upsert_regions(&mut tx, ddb_regions).await?;
+    let (mut club_stats, db_clubs) = upsert_clubs(&mut tx, ddb_clubs).await?;
+    let (mut standing_committee_stats, db_standing_committees) =
+        upsert_standing_committees(&mut tx, ddb_standing_committees).await?;
+    // Every current membership of the members in the clubs written, home
+    // club, intraclubs and affiliate clubs, also of members whose home club
+    // is out of scope. The latest of several periods in a club is kept.
+    let today = chrono::Utc::now().date_naive();
+    let club_uids: std::collections::HashSet<i64> = db_clubs.iter().map(|c| c.uid).collect();
+    let members_by_uid: HashMap<u64, &ddb::members::Member> = ddb_members
+        .iter()
+        .chain(&ddb_other_members)
+        .map(|ddb_member| (ddb_member.primary.uid, ddb_member))
+        .collect();
+    let club_membership_periods = ddb_membership_history
+        .iter()
+        .filter(|_| options.writes(Entity::ClubMemberships))
+        .filter(|period| {
+            period.is_current(today)
+                && period
+                    .club_uid
+                    .is_some_and(|uid| club_uids.contains(&(uid as i64)))
+        })
+        .sorted_by(|a, b| b.join_date.cmp(&a.join_date))
+        .filter_map(|period| Some((period, *members_by_uid.get(&period.user_uid)?)))
+        .collect_vec();
+    let db_club_memberships = club_membership_periods
+        .iter()
+        .filter_map(|(period, ddb_member)| period.to_db_club_membership(ddb_member))
+        .collect_vec();
+
+    // Collect all users from members, the members holding a club membership
+    // AND leadership records
     let ddb_users = ddb_members
         .iter()
         .flat_map(|ddb_member| [Some(ddb_member.primary.clone()), ddb_member.partner.clone()])
         .flatten()
+        .chain(
+            club_membership_periods
+                .iter()
+                .map(|(_, ddb_member)| ddb_member.primary.clone()),
+        )
         .chain(ddb_club_leadership.iter().map(|lead| lead.user.clone()))
         .chain(ddb_region_leadership.iter().map(|lead| lead.user.clone()))
         .chain(
@@ -512,42 +1380,55 @@ pub async fn run(
         )
         .unique_by(|user| user.uid)
         .collect_vec();
-
-    // Collect all unique roles from all leadership types
-    let ddb_roles = ddb_club_leadership
-        .iter()
-        .chain(ddb_region_leadership.iter())
-        .chain(ddb_international_leadership.iter())
-        .chain(ddb_standing_committee_leadership.iter())
-        .map(|lead| lead.role.clone())
-        .unique_by(|role| role.uid)
-        .collect_vec();
-
-    // Upsert roles first (no dependencies)
-    let (mut role_stats, db_roles) = upsert_roles(&db, ddb_roles).await?;
-
-    let (mut region_stats, db_regions) = upsert_regions(&db, ddb_regions).await?;
-    let (mut club_stats, db_clubs) = upsert_clubs(&db, ddb_clubs).await?;
-    let (mut standing_committee_stats, db_standing_committees) =
-        upsert_standing_committees(&db, ddb_standing_committees).await?;
-    let (mut user_stats, db_users) = upsert_users(&db, ddb_users).await?;
-    let (mut address_stats, db_addresses) =
-        upsert_addresses(&db, &ddb_members, &mut ddb_addresses).await?;
-    let (mut member_stats, db_members) = upsert_members(&db, ddb_members).await?;
-    let (mut brn_stats, db_brns) = upsert_brns(&db, &db_brns).await?;
+    let (mut user_stats, db_users) = upsert_users(&mut tx, ddb_users).await?;
+    let (mut address_stats, mut db_addresses) =
+        upsert_addresses(&mut tx, &ddb_members, &mut ddb_addresses).await?;
+    // The history is also read for the club memberships alone
+    if !options.writes(Entity::MembershipHistory) {
+        ddb_membership_history.clear();
+    }
+    // Members are also read for their addresses and brns alone
+    if !options.writes(Entity::Members) {
+        ddb_members.clear();
+    }
+    let (mut member_stats, mut db_members) = upsert_members(&mut tx, ddb_members).await?;
+    let (mut brn_stats, mut db_brns) = upsert_brns(&mut tx, &db_brns).await?;
+    let (mut club_membership_stats, db_club_memberships) =
+        upsert_club_memberships(&mut tx, db_club_memberships).await?;
+    let (mut airstream_stats, db_airstreams, mut ownership_stats, db_ownership) =
+        upsert_airstreams(
+            &mut tx,
+            ddb_airstreams,
+            options.writes(Entity::AirstreamOwnership),
+        )
+        .await?;
+    let (mut user_role_stats, db_user_roles) = upsert_user_roles(&mut tx, ddb_user_roles).await?;
+    let (mut entity_admin_stats, db_entity_admins) =
+        upsert_entity_admins(&mut tx, ddb_entity_admins).await?;
+    let (mut user_address_stats, db_user_addresses) =
+        upsert_user_addresses(&mut tx, ddb_user_addresses).await?;
+    let mut skipped_periods = 0;
+    let (mut membership_history_stats, db_membership_history) = upsert_membership_history(
+        &mut tx,
+        ddb_membership_history,
+        ddb_international_history,
+        &mut skipped_periods,
+    )
+    .await?;
 
     // Upsert leadership (depends on roles, clubs, regions, standing committees, users)
     // Filter to only leadership records referencing existing entities
-    let club_uids: std::collections::HashSet<i64> = db_clubs.iter().map(|c| c.uid).collect();
     let region_uids: std::collections::HashSet<i64> = db_regions.iter().map(|r| r.uid).collect();
     let standing_committee_uids: std::collections::HashSet<i64> =
         db_standing_committees.iter().map(|sc| sc.uid).collect();
+    let (mut orphaned_club, mut orphaned_region, mut orphaned_standing_committee) = (0, 0, 0);
 
     let (mut club_leadership_stats, db_club_leadership) = upsert_club_leadership(
-        &db,
+        &mut tx,
         ddb_club_leadership.into_iter().filter(|l| {
             let exists = club_uids.contains(&(l.entity_uid as i64));
             if !exists {
+                orphaned_club += 1;
                 tracing::warn!(
                     club_uid = l.entity_uid,
                     "leadership references non-existent club"
@@ -558,10 +1439,11 @@ pub async fn run(
     )
     .await?;
     let (mut region_leadership_stats, db_region_leadership) = upsert_region_leadership(
-        &db,
+        &mut tx,
         ddb_region_leadership.into_iter().filter(|l| {
             let exists = region_uids.contains(&(l.entity_uid as i64));
             if !exists {
+                orphaned_region += 1;
                 tracing::warn!(
                     region_uid = l.entity_uid,
                     "leadership references non-existent region"
@@ -572,13 +1454,14 @@ pub async fn run(
     )
     .await?;
     let (mut international_leadership_stats, db_international_leadership) =
-        upsert_international_leadership(&db, ddb_international_leadership).await?;
+        upsert_international_leadership(&mut tx, ddb_international_leadership).await?;
     let (mut standing_committee_leadership_stats, db_standing_committee_leadership) =
         upsert_standing_committee_leadership(
-            &db,
+            &mut tx,
             ddb_standing_committee_leadership.into_iter().filter(|l| {
                 let exists = standing_committee_uids.contains(&(l.entity_uid as i64));
                 if !exists {
+                    orphaned_standing_committee += 1;
                     tracing::warn!(
                         standing_committee_uid = l.entity_uid,
                         "leadership references non-existent standing committee"
@@ -589,31 +1472,138 @@ pub async fn run(
         )
         .await?;
 
-    retain_clubs(&db, &mut club_stats, &db_clubs).await?;
-    retain_regions(&db, &mut region_stats, &db_regions).await?;
-    retain_standing_committees(&db, &mut standing_committee_stats, &db_standing_committees).await?;
-    retain_brns(&db, &mut brn_stats, &db_brns).await?;
-    retain_members(&db, &mut member_stats, &db_members).await?;
+    let mut warnings = vec![];
+    for (count, entity) in [
+        (orphaned_club, "club"),
+        (orphaned_region, "region"),
+        (orphaned_standing_committee, "standing committee"),
+    ] {
+        if count > 0 {
+            warnings.push(format!(
+                "skipped {count} leadership records of non-existent {entity}s"
+            ));
+        }
+    }
+    if skipped_periods > 0 {
+        warnings.push(format!(
+            "skipped {skipped_periods} international memberships without user or join date"
+        ));
+    }
+
+    // Members out of scope are not written, and not deleted either
+    db_members.extend(ddb_other_members.into_iter().map(member::Member::from));
+    db_brns.extend(kept_brns);
+    db_addresses.extend(kept_addresses);
+
+    // Before the clubs and users they reference
+    if options.retains(Entity::ClubMemberships) {
+        retain_club_memberships(
+            &mut tx,
+            guard,
+            &mut club_membership_stats,
+            &db_club_memberships,
+        )
+        .await?;
+    }
+    if options.retains(Entity::Clubs) {
+        retain_clubs(&mut tx, guard, &mut club_stats, &db_clubs).await?;
+    }
+    if options.retains(Entity::Regions) {
+        retain_regions(&mut tx, guard, &mut region_stats, &db_regions).await?;
+    }
+    if options.retains(Entity::StandingCommittees) {
+        retain_standing_committees(
+            &mut tx,
+            guard,
+            &mut standing_committee_stats,
+            &db_standing_committees,
+        )
+        .await?;
+    }
+    if options.retains(Entity::Brns) {
+        retain_brns(&mut tx, guard, &mut brn_stats, &db_brns).await?;
+    }
+    if options.retains(Entity::UserAddresses) {
+        retain_user_addresses(&mut tx, guard, &mut user_address_stats, &db_user_addresses).await?;
+    }
+    if options.retains(Entity::UserRoles) {
+        retain_user_roles(&mut tx, guard, &mut user_role_stats, &db_user_roles).await?;
+    }
+    if options.retains(Entity::EntityAdmins) {
+        retain_entity_admins(&mut tx, guard, &mut entity_admin_stats, &db_entity_admins).await?;
+    }
+    // Ownership before the airstreams it references
+    if options.retains(Entity::AirstreamOwnership) {
+        retain_airstream_ownership(&mut tx, guard, &mut ownership_stats, &db_ownership).await?;
+    }
+    if options.retains(Entity::Airstreams) {
+        retain_airstreams(&mut tx, guard, &mut airstream_stats, &db_airstreams).await?;
+    }
+    if options.retains(Entity::MembershipHistory) {
+        retain_membership_history(
+            &mut tx,
+            guard,
+            &mut membership_history_stats,
+            &db_membership_history,
+        )
+        .await?;
+    }
+    if options.retains(Entity::Members) {
+        retain_members(&mut tx, guard, &mut member_stats, &db_members).await?;
+    }
 
     // Retain leadership before retaining users/roles
-    retain_club_leadership(&db, &mut club_leadership_stats, &db_club_leadership).await?;
-    retain_region_leadership(&db, &mut region_leadership_stats, &db_region_leadership).await?;
-    retain_international_leadership(
-        &db,
-        &mut international_leadership_stats,
-        &db_international_leadership,
-    )
-    .await?;
-    retain_standing_committee_leadership(
-        &db,
-        &mut standing_committee_leadership_stats,
-        &db_standing_committee_leadership,
-    )
-    .await?;
+    if options.retains(Entity::LeadershipClub) {
+        retain_club_leadership(
+            &mut tx,
+            guard,
+            &mut club_leadership_stats,
+            &db_club_leadership,
+        )
+        .await?;
+    }
+    if options.retains(Entity::LeadershipRegion) {
+        retain_region_leadership(
+            &mut tx,
+            guard,
+            &mut region_leadership_stats,
+            &db_region_leadership,
+        )
+        .await?;
+    }
+    if options.retains(Entity::LeadershipInternational) {
+        retain_international_leadership(
+            &mut tx,
+            guard,
+            &mut international_leadership_stats,
+            &db_international_leadership,
+        )
+        .await?;
+    }
+    if options.retains(Entity::LeadershipStandingCommittee) {
+        retain_standing_committee_leadership(
+            &mut tx,
+            guard,
+            &mut standing_committee_leadership_stats,
+            &db_standing_committee_leadership,
+        )
+        .await?;
+    }
+
+    if options.retains(Entity::Addresses) {
+        retain_addresses(&mut tx, guard, &mut address_stats, &db_addresses).await?;
+    }
+    if options.retains(Entity::Users) {
+        retain_users(&mut tx, guard, &mut user_stats, &db_users).await?;
+    }
+    if options.retains(Entity::LeadershipRoles) {
+        retain_roles(&mut tx, guard, &mut role_stats, &db_roles).await?;
+    }
+
+    let recorded = audit::finish(&mut tx).await?;
+    tracing::info!(recorded, "recorded changes");
 
-    retain_addresses(&db, &mut address_stats, &db_addresses).await?;
-    retain_users(&db, &mut user_stats, &db_users).await?;
-    retain_roles(&db, &mut role_stats, &db_roles).await?;
+    tx.commit().await?;
 
     let duration = start.elapsed().as_secs();
     tracing::info!(duration, "sync complete");
@@ -631,8 +1621,24 @@ pub async fn run(
         region_leadership_stats,
         international_leadership_stats,
         standing_committee_leadership_stats,
+        membership_history_stats,
+        airstream_stats,
+        ownership_stats,
+        user_role_stats,
+        entity_admin_stats,
+        user_address_stats,
+        club_membership_stats,
     ]
     .into_iter()
+    .filter(|(name, _)| {
+        Entity::ALL
+            .iter()
+            .any(|entity| entity.name() == name && options.writes(*entity))
+    })
     .collect();
-    Ok(stats)
+    Ok(SyncReport {
+        stats,
+        warnings,
+        extract: extract_timings,
+    })
 }
@@ -0,0 +1,72 @@
+use db::guard::Scope;
+use sync_app::sync::{Entity, SyncOptions};
+
+#[test]
+fn full_sync_retains_everything() {
+    let options = SyncOptions::default();
+    for entity in Entity::ALL {
+        assert!(options.retains(entity), "{entity}");
+        assert!(options.writes(entity), "{entity}");
+    }
+}
+
+#[test]
+fn only_writes_references() {
+    let options = SyncOptions::new([Entity::LeadershipClub], Scope::All).unwrap();
+    assert!(options.retains(Entity::LeadershipClub));
+    for entity in [
+        Entity::Clubs,
+        Entity::Regions,
+        Entity::Users,
+        Entity::LeadershipRoles,
+    ] {
+        assert!(options.writes(entity), "{entity}");
+        assert!(!options.retains(entity), "{entity}");
+    }
+    assert!(!options.writes(Entity::Members));
+    assert!(!options.writes(Entity::LeadershipRegion));
+}
+
+#[test]
+fn club_scope_retains_only_the_club() {
+    let options = SyncOptions::new([], Scope::Club(42)).unwrap();
+    for entity in [
+        Entity::Clubs,
+        Entity::Members,
+        Entity::Addresses,
+        Entity::Brns,
+        Entity::LeadershipClub,
+        Entity::ClubMemberships,
+    ] {
+        assert!(options.retains(entity), "{entity}");
+    }
+    for entity in [Entity::Regions, Entity::Users, Entity::LeadershipRoles] {
+        assert!(options.writes(entity), "{entity}");
+        assert!(!options.retains(entity), "{entity}");
+    }
+    assert!(!options.writes(Entity::LeadershipRegion));
+    assert!(!options.writes(Entity::StandingCommittees));
+}
+
+#[test]
+fn reject_unscoped_entities() {
+    assert!(SyncOptions::new([Entity::Users], Scope::All).is_err());
+    assert!(SyncOptions::new([Entity::LeadershipRegion], Scope::Club(42)).is_err());
+    assert!(SyncOptions::new([Entity::LeadershipRegion], Scope::Region(7)).is_ok());
+}
+
+#[test]
+fn history_and_airstreams_are_not_scoped() {
+    let options = SyncOptions::new([Entity::AirstreamOwnership], Scope::All).unwrap();
+    assert!(options.writes(Entity::Airstreams));
+    assert!(!options.retains(Entity::Airstreams));
+
+    let options = SyncOptions::new([], Scope::Region(7)).unwrap();
+    for entity in [
+        Entity::MembershipHistory,
+        Entity::Airstreams,
+        Entity::AirstreamOwnership,
+    ] {
+        assert!(!options.writes(entity), "{entity}");
+    }
+}
@@ -0,0 +1,27 @@
+-- Member events posted by Mailchimp list webhooks, as received
+create table mailchimp_webhook_events (
+    id bigint primary key generated always as identity,
+    job_id bigint not null references mailchimp (id) on delete cascade,
+    member_id text not null,
+    type text not null,
+    email text not null,
+    old_email text,
+    reason text,
+    data jsonb not null,
+    fired_at timestamptz,
+    received_at timestamptz not null default now()
+);
+
+create index mailchimp_webhook_events_member on mailchimp_webhook_events (job_id, member_id);
+
+-- The last known state of each contact that had a webhook event
+create table mailchimp_members (
+    job_id bigint not null references mailchimp (id) on delete cascade,
+    member_id text not null,
+    email text not null,
+    status text,
+    reason text,
+    merges jsonb not null default '{}',
+    updated_at timestamptz not null default now(),
+    primary key (job_id, member_id)
+);
@@ -0,0 +1,5 @@
+-- How members no longer in the membership database are removed from a list:
+-- archive, delete-permanent or unsubscribe
+alter table mailchimp
+    add column removal_policy text not null default 'archive'
+    check (removal_policy in ('archive', 'delete-permanent', 'unsubscribe'));
@@ -0,0 +1,6 @@
+-- How many contacts a single run may remove from a list, absolute and as
+-- percentage of the list. Null means no limit.
+alter table mailchimp
+    add column max_deletes bigint check (max_deletes >= 0),
+    add column max_delete_percent double precision default 20
+        check (max_delete_percent between 0 and 100);
@@ -0,0 +1,12 @@
+-- History of sync job runs. Failed runs have an error and no counts.
+create table mailchimp_job_runs (
+    id bigint primary key generated always as identity,
+    job_id bigint not null references mailchimp (id) on delete cascade,
+    started_at timestamptz not null,
+    finished_at timestamptz not null default now(),
+    upserted bigint,
+    deleted bigint,
+    error text
+);
+
+create index mailchimp_job_runs_job on mailchimp_job_runs (job_id, started_at desc);
@@ -0,0 +1,3 @@
+-- Operations of a run that failed without failing the run, like tag updates
+-- or removals of single contacts
+alter table mailchimp_job_runs add column warnings jsonb not null default '[]';
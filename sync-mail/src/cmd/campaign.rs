@@ -0,0 +1,229 @@
+use crate::{
+    Context, Result,
+    cmd::{job_client, print_json},
+    mailchimp::Newsletter,
+    settings::Settings,
+};
+use chrono::{DateTime, Utc};
+use mailchimp::campaigns::{Content, TemplateContent};
+use serde_json::json;
+
+/// Create, test and send newsletter campaigns to a sync job's list
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    cmd: CampaignCmd,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: Settings) -> Result {
+        self.cmd.run(settings).await
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum CampaignCmd {
+    Create(Create),
+    Test(Test),
+    Schedule(Schedule),
+    Unschedule(Unschedule),
+    Send(Send),
+    Status(Status),
+}
+
+impl CampaignCmd {
+    async fn run(&self, settings: Settings) -> Result {
+        match self {
+            Self::Create(cmd) => cmd.run(settings).await,
+            Self::Test(cmd) => cmd.run(settings).await,
+            Self::Schedule(cmd) => cmd.run(settings).await,
+            Self::Unschedule(cmd) => cmd.run(settings).await,
+            Self::Send(cmd) => cmd.run(settings).await,
+            Self::Status(cmd) => cmd.run(settings).await,
+        }
+    }
+}
+
+/// Parse a `NAME=CONTENT` template section
+fn parse_section(s: &str) -> std::result::Result<(String, String), String> {
+    let (name, content) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=CONTENT, got {s}"))?;
+    Ok((name.to_string(), content.to_string()))
+}
+
+/// Create a draft newsletter campaign for the list of a sync job
+///
+/// The content is either read from an HTML file or taken from a saved
+/// template. Nothing is sent.
+#[derive(Debug, clap::Args)]
+pub struct Create {
+    /// The id of the sync job
+    id: i64,
+    #[arg(long)]
+    subject: String,
+    #[arg(long, default_value = "")]
+    preview_text: String,
+    /// The internal campaign title, defaults to the job name and subject
+    #[arg(long)]
+    title: Option<String>,
+    #[arg(long)]
+    from_name: String,
+    #[arg(long)]
+    reply_to: String,
+    /// Only send to the members of the given segment of the list
+    #[arg(long)]
+    segment: Option<u64>,
+    /// A file with the HTML content of the newsletter
+    #[arg(
+        long,
+        conflicts_with = "template",
+        required_unless_present = "template"
+    )]
+    html: Option<std::path::PathBuf>,
+    /// The id of a saved template to use as content
+    #[arg(long)]
+    template: Option<u64>,
+    /// The content of an editable template section, as `NAME=CONTENT`
+    #[arg(long, requires = "template", value_parser = parse_section)]
+    section: Vec<(String, String)>,
+}
+
+impl Create {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let (job, client) = job_client(&settings, self.id).await?;
+        let content = match (&self.html, self.template) {
+            (Some(path), _) => Content::Html(
+                std::fs::read_to_string(path)
+                    .with_context(|| format!("reading {}", path.display()))?,
+            ),
+            (None, Some(id)) => Content::Template(TemplateContent {
+                id,
+                sections: self.section.iter().cloned().collect(),
+            }),
+            (None, None) => anyhow::bail!("either --html or --template is required"),
+        };
+        let newsletter = Newsletter {
+            subject: self.subject.clone(),
+            preview_text: self.preview_text.clone(),
+            title: self.title.clone(),
+            from_name: self.from_name.clone(),
+            reply_to: self.reply_to.clone(),
+            segment: self.segment,
+            content,
+        };
+        let campaign = job.create_newsletter(&client, &newsletter).await?;
+        print_json(&campaign)
+    }
+}
+
+/// Send a test email of a campaign to the given addresses
+#[derive(Debug, clap::Args)]
+pub struct Test {
+    /// The id of the sync job
+    id: i64,
+    /// The campaign id
+    campaign: String,
+    #[arg(required = true)]
+    emails: Vec<String>,
+}
+
+impl Test {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let (job, client) = job_client(&settings, self.id).await?;
+        let campaign = job.campaign(&client, &self.campaign).await?;
+        mailchimp::campaigns::send_test(&client, &campaign.id, &self.emails).await?;
+        print_json(&json!({ "test": "ok", "emails": self.emails }))
+    }
+}
+
+/// Schedule a campaign for delivery at the given time
+///
+/// Without the confirm flag this just shows the send checklist
+#[derive(Debug, clap::Args)]
+pub struct Schedule {
+    /// The id of the sync job
+    id: i64,
+    /// The campaign id
+    campaign: String,
+    /// The delivery time in RFC 3339, on the quarter hour
+    time: DateTime<Utc>,
+    #[arg(long)]
+    confirm: bool,
+}
+
+impl Schedule {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let (job, client) = job_client(&settings, self.id).await?;
+        let campaign = job.campaign(&client, &self.campaign).await?;
+        if self.confirm {
+            mailchimp::campaigns::schedule(&client, &campaign.id, self.time).await?;
+            print_json(&job.campaign(&client, &campaign.id).await?)
+        } else {
+            print_json(&mailchimp::campaigns::send_checklist(&client, &campaign.id).await?)
+        }
+    }
+}
+
+/// Return a scheduled campaign to draft
+#[derive(Debug, clap::Args)]
+pub struct Unschedule {
+    /// The id of the sync job
+    id: i64,
+    /// The campaign id
+    campaign: String,
+}
+
+impl Unschedule {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let (job, client) = job_client(&settings, self.id).await?;
+        let campaign = job.campaign(&client, &self.campaign).await?;
+        mailchimp::campaigns::unschedule(&client, &campaign.id).await?;
+        print_json(&job.campaign(&client, &campaign.id).await?)
+    }
+}
+
+/// Send a campaign to the job's list right away
+///
+/// Without the confirm flag this just shows the send checklist
+#[derive(Debug, clap::Args)]
+pub struct Send {
+    /// The id of the sync job
+    id: i64,
+    /// The campaign id
+    campaign: String,
+    #[arg(long)]
+    confirm: bool,
+}
+
+impl Send {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let (job, client) = job_client(&settings, self.id).await?;
+        let campaign = job.campaign(&client, &self.campaign).await?;
+        if self.confirm {
+            mailchimp::campaigns::send(&client, &campaign.id).await?;
+            print_json(&job.campaign(&client, &campaign.id).await?)
+        } else {
+            print_json(&mailchimp::campaigns::send_checklist(&client, &campaign.id).await?)
+        }
+    }
+}
+
+/// Show the status of one or all campaigns of a job's list
+#[derive(Debug, clap::Args)]
+pub struct Status {
+    /// The id of the sync job
+    id: i64,
+    /// The campaign id
+    campaign: Option<String>,
+}
+
+impl Status {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let (job, client) = job_client(&settings, self.id).await?;
+        match &self.campaign {
+            Some(campaign_id) => print_json(&job.campaign(&client, campaign_id).await?),
+            None => print_json(&job.campaigns(&client).await?),
+        }
+    }
+}
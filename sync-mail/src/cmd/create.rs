@@ -1,4 +1,5 @@
 use crate::{Result, cmd::print_json, mailchimp::Job, settings::Settings};
+use mailchimp::members::RemovalPolicy;
 
 /// Create a new sync job
 #[derive(Debug, clap::Args)]
@@ -15,6 +16,16 @@ pub struct Cmd {
     /// Mailchimp audience identifier
     #[arg(long)]
     list: String,
+    /// How members no longer in the membership database are removed:
+    /// archive, delete-permanent or unsubscribe
+    #[arg(long, default_value = "archive")]
+    removal_policy: RemovalPolicy,
+    /// The most contacts a run may remove from the list
+    #[arg(long)]
+    max_deletes: Option<i64>,
+    /// The largest share of the list in percent a run may remove
+    #[arg(long, default_value_t = 20.0)]
+    max_delete_percent: f64,
 }
 
 #[derive(Debug, clap::Args)]
@@ -38,6 +49,9 @@ impl Cmd {
             list: self.list.clone(),
             api_key: self.api_key.clone(),
             region: self.club_or_region.region,
+            removal_policy: self.removal_policy,
+            max_deletes: self.max_deletes,
+            max_delete_percent: Some(self.max_delete_percent),
             ..Default::default()
         };
         let db = settings.mail.db.connect().await?;
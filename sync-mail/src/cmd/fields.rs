@@ -1,7 +1,8 @@
 use crate::{Result, cmd::print_json, mailchimp::Job, settings::Settings};
-use futures::{StreamExt, TryFutureExt, TryStreamExt};
+use futures::{StreamExt, TryStreamExt};
 
-/// Sync the mailing list merge fields for a given club (or all) to mailchimp
+/// Sync the mailing list merge fields and interest groups for a given club
+/// (or all) to mailchimp
 #[derive(Debug, clap::Args)]
 pub struct Cmd {
     /// The id of the mailing list to sync
@@ -19,6 +20,8 @@ impl Cmd {
             deleted: Vec<String>,
             added: Vec<String>,
             updated: Vec<String>,
+            /// Names of the created interests
+            interests: Vec<String>,
         }
         let db = settings.mail.db.connect().await?;
         let jobs = if let Some(id) = self.id {
@@ -29,22 +32,23 @@ impl Cmd {
         } else {
             Job::all(&db).await?
         };
+        let endpoint = settings.mail.endpoint.as_deref();
         let results = futures::stream::iter(jobs)
             .map(|job| async move {
-                let name = job.name.clone();
-                job.sync_merge_fields(self.process_deletes)
-                    .map_ok(|(added, deleted, updated)| {
-                        (
-                            job.id,
-                            JobResult {
-                                name,
-                                deleted: deleted.clone(),
-                                added: added.clone(),
-                                updated: updated.clone(),
-                            },
-                        )
-                    })
-                    .await
+                let (added, deleted, updated) = job
+                    .sync_merge_fields(self.process_deletes, endpoint)
+                    .await?;
+                let interests = job.sync_interests(endpoint).await?;
+                Ok::<_, crate::Error>((
+                    job.id,
+                    JobResult {
+                        name: job.name,
+                        deleted,
+                        added,
+                        updated,
+                        interests,
+                    },
+                ))
             })
             .buffered(20)
             .try_collect::<Vec<(i64, JobResult)>>()
@@ -0,0 +1,22 @@
+use crate::{Result, cmd::print_json, history, settings::Settings};
+
+/// Show recent runs of all sync jobs or of one, with their counts and errors
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// The id of the sync job
+    id: Option<i64>,
+    /// How many runs to show
+    #[arg(long, default_value_t = 20)]
+    limit: i64,
+    /// Only show failed runs
+    #[arg(long)]
+    failed: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let db = settings.mail.db.connect().await?;
+        let runs = history::runs(&db, self.id, self.failed, self.limit).await?;
+        print_json(&runs)
+    }
+}
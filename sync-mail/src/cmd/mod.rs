@@ -1,18 +1,32 @@
-use crate::{Result, settings::Settings};
+use crate::{Result, mailchimp::Job, settings::Settings};
 
+pub mod campaign;
 pub mod create;
 pub mod delete;
 pub mod fields;
+pub mod history;
 pub mod list;
 pub mod migrate;
 pub mod run;
+pub mod serve;
 pub mod update;
+pub mod webhook;
 
 pub fn print_json<T: ?Sized + serde::Serialize>(value: &T) -> Result {
     println!("{}", serde_json::to_string_pretty(value)?);
     Ok(())
 }
 
+/// Load a job and a client for its account
+pub(crate) async fn job_client(settings: &Settings, id: i64) -> Result<(Job, mailchimp::Client)> {
+    let db = settings.mail.db.connect().await?;
+    let job = Job::get(&db, id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("sync job not found"))?;
+    let client = job.client_for(settings.mail.endpoint.as_deref())?;
+    Ok((job, client))
+}
+
 #[derive(Debug, clap::Args)]
 pub struct Cmd {
     #[clap(subcommand)]
@@ -33,6 +47,10 @@ pub enum SyncCmd {
     Delete(delete::Cmd),
     Fields(fields::Cmd),
     Run(run::Cmd),
+    History(history::Cmd),
+    Campaign(campaign::Cmd),
+    Webhook(webhook::Cmd),
+    Serve(serve::Cmd),
     Migrate(migrate::Cmd),
 }
 
@@ -45,6 +63,10 @@ impl SyncCmd {
             Self::Delete(cmd) => cmd.run(settings).await,
             Self::Fields(cmd) => cmd.run(settings).await,
             Self::Run(cmd) => cmd.run(settings).await,
+            Self::History(cmd) => cmd.run(settings).await,
+            Self::Campaign(cmd) => cmd.run(settings).await,
+            Self::Webhook(cmd) => cmd.run(settings).await,
+            Self::Serve(cmd) => cmd.run(settings).await,
             Self::Migrate(cmd) => cmd.run(settings).await,
         }
     }
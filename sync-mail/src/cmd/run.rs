@@ -1,16 +1,37 @@
-use crate::{Result, cmd::print_json, mailchimp::Job, settings::Settings};
+use crate::{
+    Result,
+    cmd::print_json,
+    mailchimp::{Job, JobPlan},
+    settings::Settings,
+};
 
 /// Sync the given club (or all) mailing list from the membership database
 #[derive(Debug, clap::Args)]
 pub struct Cmd {
     /// The id of the mailing list to sync
     id: Option<u64>,
+    /// Only show what the sync would change, without changing anything
+    #[arg(long)]
+    dry_run: bool,
+    /// Remove contacts even when more are missing than the job's delete
+    /// limits allow
+    #[arg(long, conflicts_with = "dry_run")]
+    force: bool,
+    /// How to show the plan of a dry run
+    #[arg(long, value_enum, default_value_t = Format::Json, requires = "dry_run")]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum Format {
+    Json,
+    Table,
 }
 
 impl Cmd {
     pub async fn run(&self, settings: Settings) -> Result {
         let db = settings.mail.db.connect().await?;
-        let jobs = if let Some(id) = self.id {
+        let mut jobs = if let Some(id) = self.id {
             let job = Job::get(&db, id as i64)
                 .await?
                 .ok_or_else(|| anyhow::anyhow!("sync job not found"))?;
@@ -19,7 +40,96 @@ impl Cmd {
             Job::all(&db).await?
         };
 
-        let map = Job::sync_many(jobs, settings.ddb).await;
+        if self.force {
+            jobs.iter_mut().for_each(Job::force);
+        }
+
+        if self.dry_run {
+            let mut plans = Vec::with_capacity(jobs.len());
+            for job in jobs {
+                plans.push(
+                    job.plan(settings.ddb.clone(), settings.mail.endpoint.as_deref())
+                        .await?,
+                );
+            }
+            return match self.format {
+                Format::Json => print_json(&plans),
+                Format::Table => {
+                    plans.iter().for_each(print_table);
+                    Ok(())
+                }
+            };
+        }
+
+        let map = Job::sync_many(&db, jobs, settings.ddb, settings.mail.endpoint.as_deref()).await;
         print_json(&map)
     }
 }
+
+fn print_table(plan: &JobPlan) {
+    let members = &plan.members;
+    println!("job {} ({}), list {}", plan.id, plan.name, plan.list);
+    println!(
+        "  {} to create, {} to update, {} to {}, {} unchanged",
+        members.create.len(),
+        members.update.len(),
+        members.remove.len(),
+        members.removal_policy,
+        members.unchanged
+    );
+
+    let fields = &plan.merge_fields;
+    if !fields.is_empty() || !plan.interests.is_empty() {
+        println!();
+        println!("  {:<10} {:<14} DETAILS", "ACTION", "FIELD");
+        for tag in &fields.added {
+            println!("  {:<10} {tag:<14}", "add");
+        }
+        for change in &fields.updated {
+            println!(
+                "  {:<10} {:<14} {}",
+                "update",
+                change.tag,
+                change.changes.join(", ")
+            );
+        }
+        for tag in &fields.deleted {
+            println!("  {:<10} {tag:<14}", "delete");
+        }
+        for name in &plan.interests {
+            println!("  {:<10} {:<14} {name}", "add", "interest");
+        }
+    }
+
+    if members.create.is_empty() && members.update.is_empty() && members.remove.is_empty() {
+        return;
+    }
+    println!();
+    println!("  {:<10} {:<40} CHANGES", "ACTION", "EMAIL");
+    for email in &members.create {
+        println!("  {:<10} {email:<40}", "create");
+    }
+    for member in &members.update {
+        let changes = member
+            .changes
+            .iter()
+            .map(|change| format!("{}: {} -> {}", change.field, change.from, change.to))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("  {:<10} {:<40} {changes}", "update", member.email);
+    }
+    for email in &members.remove {
+        println!("  {:<10} {email:<40}", members.removal_policy.as_str());
+    }
+    for tags in &members.tags {
+        let changes = tags
+            .add
+            .iter()
+            .map(|tag| format!("+{tag}"))
+            .chain(tags.remove.iter().map(|tag| format!("-{tag}")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!("  {:<10} {:<40} {changes}", "tag", tags.email);
+    }
+    println!();
+}
@@ -0,0 +1,27 @@
+use crate::{Context, Result, settings::Settings};
+
+/// Receive Mailchimp list webhooks and record member events
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// The address to listen on, overrides the configured one
+    #[arg(long)]
+    bind: Option<String>,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let secret = &settings.mail.webhook.secret;
+        if secret.is_empty() {
+            anyhow::bail!("a webhook secret is required, set ACI__MAIL_WEBHOOK_SECRET");
+        }
+        let db = settings.mail.db.connect().await?;
+        let bind = self.bind.as_ref().unwrap_or(&settings.mail.webhook.bind);
+        let listener = tokio::net::TcpListener::bind(bind)
+            .await
+            .with_context(|| format!("binding {bind}"))?;
+        tracing::info!(bind, "receiving webhooks");
+        axum::serve(listener, crate::webhooks::router(db, secret))
+            .await
+            .context("serving webhooks")
+    }
+}
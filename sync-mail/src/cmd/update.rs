@@ -1,4 +1,5 @@
 use crate::{Result, cmd::print_json, mailchimp::Job, mailchimp::JobUpdate, settings::Settings};
+use mailchimp::members::RemovalPolicy;
 
 /// Update a sync job
 #[derive(Debug, clap::Args)]
@@ -15,6 +16,16 @@ pub struct Cmd {
     api_key: Option<String>,
     #[arg(long)]
     list: Option<String>,
+    /// How members no longer in the membership database are removed:
+    /// archive, delete-permanent or unsubscribe
+    #[arg(long)]
+    removal_policy: Option<RemovalPolicy>,
+    /// The most contacts a run may remove from the list
+    #[arg(long)]
+    max_deletes: Option<i64>,
+    /// The largest share of the list in percent a run may remove
+    #[arg(long)]
+    max_delete_percent: Option<f64>,
 }
 
 impl From<&Cmd> for JobUpdate {
@@ -26,6 +37,9 @@ impl From<&Cmd> for JobUpdate {
             region: value.region,
             api_key: value.api_key.clone(),
             list: value.list.clone(),
+            removal_policy: value.removal_policy.map(|policy| policy.to_string()),
+            max_deletes: value.max_deletes,
+            max_delete_percent: value.max_delete_percent,
         }
     }
 }
@@ -0,0 +1,125 @@
+use crate::{
+    Result,
+    cmd::{job_client, print_json},
+    settings::Settings,
+};
+use mailchimp::webhooks::{NewWebhook, WebhookEvents, WebhookSources};
+use serde_json::json;
+
+/// Manage the webhooks of a sync job's list and show the events received
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    cmd: WebhookCmd,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: Settings) -> Result {
+        self.cmd.run(settings).await
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum WebhookCmd {
+    Register(Register),
+    List(List),
+    Delete(Delete),
+    Events(Events),
+}
+
+impl WebhookCmd {
+    async fn run(&self, settings: Settings) -> Result {
+        match self {
+            Self::Register(cmd) => cmd.run(settings).await,
+            Self::List(cmd) => cmd.run(settings).await,
+            Self::Delete(cmd) => cmd.run(settings).await,
+            Self::Events(cmd) => cmd.run(settings).await,
+        }
+    }
+}
+
+/// Register the webhook receiver for member events on a job's list
+///
+/// The receiver has to be running, Mailchimp checks the url before
+/// accepting it.
+#[derive(Debug, clap::Args)]
+pub struct Register {
+    /// The id of the sync job
+    id: i64,
+    /// The public base url of the receiver, overrides the configured one
+    #[arg(long)]
+    url: Option<String>,
+}
+
+impl Register {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let webhook = &settings.mail.webhook;
+        let Some(base) = self.url.as_ref().or(webhook.url.as_ref()) else {
+            anyhow::bail!("no receiver url, pass --url or set ACI__MAIL_WEBHOOK_URL");
+        };
+        if webhook.secret.is_empty() {
+            anyhow::bail!("a webhook secret is required, set ACI__MAIL_WEBHOOK_SECRET");
+        }
+        let (job, client) = job_client(&settings, self.id).await?;
+        let new = NewWebhook {
+            url: crate::webhooks::url(base, job.id, &webhook.secret),
+            events: WebhookEvents::members(),
+            sources: WebhookSources::external(),
+        };
+        let created = mailchimp::webhooks::create(&client, &job.list, &new).await?;
+        print_json(&created)
+    }
+}
+
+/// List the webhooks registered on a job's list
+#[derive(Debug, clap::Args)]
+pub struct List {
+    /// The id of the sync job
+    id: i64,
+}
+
+impl List {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let (job, client) = job_client(&settings, self.id).await?;
+        print_json(&mailchimp::webhooks::all(&client, &job.list).await?)
+    }
+}
+
+/// Delete a webhook from a job's list
+#[derive(Debug, clap::Args)]
+pub struct Delete {
+    /// The id of the sync job
+    id: i64,
+    /// The webhook id
+    webhook: String,
+}
+
+impl Delete {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let (job, client) = job_client(&settings, self.id).await?;
+        mailchimp::webhooks::delete(&client, &job.list, &self.webhook).await?;
+        print_json(&json!({ "deleted": "ok" }))
+    }
+}
+
+/// Show the most recent webhook events received for a job
+#[derive(Debug, clap::Args)]
+pub struct Events {
+    /// The id of the sync job
+    id: i64,
+    /// Only show events of the contact with this email address
+    #[arg(long)]
+    email: Option<String>,
+    #[arg(long, default_value_t = 50)]
+    limit: i64,
+}
+
+impl Events {
+    pub async fn run(&self, settings: Settings) -> Result {
+        let db = settings.mail.db.connect().await?;
+        let member_id = self.email.as_deref().map(mailchimp::members::member_id);
+        let events =
+            crate::webhooks::events(&db, self.id, member_id.as_deref(), self.limit).await?;
+        print_json(&events)
+    }
+}
@@ -0,0 +1,115 @@
+//! Sync job run history, recorded in `mailchimp_job_runs`
+
+use crate::{Error, Result, mailchimp::JobSync};
+use chrono::{DateTime, Utc};
+use futures::TryFutureExt;
+use mailchimp::batches::BatchOperationResult;
+use sqlx::{PgPool, types::Json};
+
+/// Operations of a run that failed without failing the run
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RunWarning {
+    /// What failed, like `tag update`
+    pub operation: String,
+    pub failed: usize,
+    /// The member id, status code and error detail of each failed operation
+    pub details: Vec<String>,
+}
+
+impl RunWarning {
+    /// A warning for the failed operations, if any
+    pub fn from_failed(operation: &str, failed: &[BatchOperationResult]) -> Option<Self> {
+        if failed.is_empty() {
+            return None;
+        }
+        let details = failed
+            .iter()
+            .map(|result| {
+                let detail = result.error().map(|err| err.detail).unwrap_or_default();
+                format!("{}: {} {detail}", result.operation_id, result.status_code)
+                    .trim_end()
+                    .to_string()
+            })
+            .collect();
+        Some(Self {
+            operation: operation.to_string(),
+            failed: failed.len(),
+            details,
+        })
+    }
+}
+
+/// A recorded run of a sync job
+#[derive(Debug, sqlx::FromRow, Clone, serde::Serialize)]
+pub struct JobRun {
+    pub id: i64,
+    pub job_id: i64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upserted: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<i64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Json<Vec<RunWarning>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Record the outcome of a job run that started at `started_at`, either
+/// the deleted and upserted counts and the warnings, or the error
+pub async fn record(
+    db: &PgPool,
+    job_id: i64,
+    started_at: DateTime<Utc>,
+    outcome: &Result<JobSync>,
+) -> Result<()> {
+    let no_warnings = vec![];
+    let (counts, warnings, error) = match outcome {
+        Ok(sync) => (
+            Some((sync.deleted as i64, sync.upserted as i64)),
+            &sync.warnings,
+            None,
+        ),
+        Err(e) => (None, &no_warnings, Some(format!("{e:#}"))),
+    };
+    sqlx::query(
+        r#"
+        insert into mailchimp_job_runs (job_id, started_at, deleted, upserted, warnings, error)
+        values ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(job_id)
+    .bind(started_at)
+    .bind(counts.map(|(deleted, _)| deleted))
+    .bind(counts.map(|(_, upserted)| upserted))
+    .bind(Json(warnings))
+    .bind(error)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// The most recent runs, of all jobs or of one, optionally only failed runs
+pub async fn runs(
+    db: &PgPool,
+    job_id: Option<i64>,
+    failed: bool,
+    limit: i64,
+) -> Result<Vec<JobRun>> {
+    sqlx::query_as(
+        r#"
+        select id, job_id, started_at, finished_at, upserted, deleted, warnings, error
+        from mailchimp_job_runs
+        where ($1::bigint is null or job_id = $1) and (not $2 or error is not null)
+        order by started_at desc, id desc
+        limit $3
+        "#,
+    )
+    .bind(job_id)
+    .bind(failed)
+    .bind(limit)
+    .fetch_all(db)
+    .map_err(Error::from)
+    .await
+}
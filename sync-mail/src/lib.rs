@@ -3,5 +3,7 @@ pub type Error = anyhow::Error;
 pub use anyhow::Context;
 
 pub mod cmd;
+pub mod history;
 pub mod mailchimp;
 pub mod settings;
+pub mod webhooks;
@@ -1,15 +1,60 @@
-use crate::{Error, Result, settings::AciDatabaseSettings};
+use crate::{Error, Result, history::RunWarning, settings::AciDatabaseSettings};
 use chrono::{DateTime, Utc};
 use futures::TryFutureExt;
-use mailchimp::RetryPolicy;
+use mailchimp::{
+    RetryPolicy,
+    campaigns::{Campaign, CampaignSettings, CampaignsQuery, Content, NewCampaign, Recipients},
+    members::{DeleteLimit, MembersPlan, RemovalPolicy},
+    merge_fields::MergeFieldsPlan,
+};
 use sqlx::{Database, Encode, MySqlPool, PgPool, Type, query::QueryAs};
-use std::time::Instant;
+use std::{collections::HashMap, time::Instant};
 
 #[derive(Debug, serde::Serialize)]
 pub struct JobSyncResult {
     pub name: String,
     pub deleted: usize,
     pub upserted: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<RunWarning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What a sync of a job changed in its list, see [`Job::sync`]
+#[derive(Debug, Default)]
+pub struct JobSync {
+    pub deleted: usize,
+    pub upserted: usize,
+    /// Operations that failed without failing the sync
+    pub warnings: Vec<RunWarning>,
+}
+
+/// What a sync of a job would change in its list, see [`Job::plan`]
+#[derive(Debug, serde::Serialize)]
+pub struct JobPlan {
+    pub id: i64,
+    pub name: String,
+    pub list: String,
+    pub merge_fields: MergeFieldsPlan,
+    /// Names of the interests missing from the list, which syncing the
+    /// merge fields creates
+    pub interests: Vec<String>,
+    pub members: MembersPlan,
+}
+
+/// A newsletter to send to the members of a job's list
+#[derive(Debug, Clone)]
+pub struct Newsletter {
+    pub subject: String,
+    pub preview_text: String,
+    /// The internal campaign title, defaults to the job name and subject
+    pub title: Option<String>,
+    pub from_name: String,
+    pub reply_to: String,
+    /// Restrict the recipients to a segment of the list
+    pub segment: Option<u64>,
+    pub content: Content,
 }
 
 #[derive(Debug, sqlx::FromRow, Clone, serde::Serialize, Default)]
@@ -23,6 +68,15 @@ pub struct Job {
     pub club: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub region: Option<i32>,
+    /// How members no longer in the membership database are removed
+    #[sqlx(try_from = "String")]
+    pub removal_policy: RemovalPolicy,
+    /// The most contacts a run may remove
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_deletes: Option<i64>,
+    /// The largest share of the list in percent a run may remove
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delete_percent: Option<f64>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -34,6 +88,9 @@ pub struct JobUpdate {
     pub list: Option<String>,
     pub club: Option<i64>,
     pub region: Option<i32>,
+    pub removal_policy: Option<String>,
+    pub max_deletes: Option<i64>,
+    pub max_delete_percent: Option<f64>,
 }
 
 trait MaybeBind<'q, DB>
@@ -76,6 +133,19 @@ impl JobUpdate {
         maybe_setter(&self.list, "list", &mut index, &mut results);
         maybe_setter(&self.club, "club", &mut index, &mut results);
         maybe_setter(&self.region, "region", &mut index, &mut results);
+        maybe_setter(
+            &self.removal_policy,
+            "removal_policy",
+            &mut index,
+            &mut results,
+        );
+        maybe_setter(&self.max_deletes, "max_deletes", &mut index, &mut results);
+        maybe_setter(
+            &self.max_delete_percent,
+            "max_delete_percent",
+            &mut index,
+            &mut results,
+        );
         results
     }
 
@@ -87,6 +157,7 @@ impl JobUpdate {
         DB: Database,
         i32: Encode<'q, DB> + Type<DB>,
         i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
         String: Encode<'q, DB> + Type<DB>,
     {
         q.bind(self.id)
@@ -95,20 +166,30 @@ impl JobUpdate {
             .maybe_bind(&self.list)
             .maybe_bind(&self.club)
             .maybe_bind(&self.region)
+            .maybe_bind(&self.removal_policy)
+            .maybe_bind(&self.max_deletes)
+            .maybe_bind(&self.max_delete_percent)
     }
 }
 
 impl Job {
     pub async fn all(db: &PgPool) -> Result<Vec<Self>> {
-        sqlx::query_as("select id, name, api_key, list, club, region, created_at from mailchimp")
-            .fetch_all(db)
-            .map_err(Error::from)
-            .await
+        sqlx::query_as(
+            "select id, name, api_key, list, club, region, removal_policy, max_deletes, \
+             max_delete_percent, created_at from mailchimp",
+        )
+        .fetch_all(db)
+        .map_err(Error::from)
+        .await
     }
 
     pub async fn get(db: &PgPool, job_id: i64) -> Result<Option<Self>> {
         sqlx::query_as(
-            r#"select id, name, api_key, list, club, region, created_at from mailchimp where id = $1;"#,
+            r#"
+            select id, name, api_key, list, club, region, removal_policy, max_deletes,
+                max_delete_percent, created_at
+            from mailchimp where id = $1;
+            "#,
         )
         .bind(job_id)
         .fetch_optional(db)
@@ -119,8 +200,9 @@ impl Job {
     pub async fn create(db: &PgPool, job: &Self) -> Result<Self> {
         sqlx::query_as(
             r#"
-            insert into mailchimp (name, api_key, list, club, region)
-            values ($1, $2, $3, $4, $5)
+            insert into mailchimp
+                (name, api_key, list, club, region, removal_policy, max_deletes, max_delete_percent)
+            values ($1, $2, $3, $4, $5, $6, $7, $8)
             returning *;
             "#,
         )
@@ -129,6 +211,9 @@ impl Job {
         .bind(&job.list)
         .bind(job.club)
         .bind(job.region)
+        .bind(job.removal_policy.as_str())
+        .bind(job.max_deletes)
+        .bind(job.max_delete_percent)
         .fetch_one(db)
         .map_err(Error::from)
         .await
@@ -161,8 +246,30 @@ impl Job {
         Ok(())
     }
 
-    fn client(&self) -> Result<mailchimp::Client> {
-        Ok(mailchimp::client::from_api_key(&self.api_key)?)
+    /// The limit on contacts a run may remove from the list
+    pub fn delete_limit(&self) -> DeleteLimit {
+        DeleteLimit {
+            rows: self.max_deletes.map(|rows| rows as u64),
+            percent: self.max_delete_percent,
+        }
+    }
+
+    /// Lift the job's delete limits, for a deliberate mass removal
+    pub fn force(&mut self) {
+        self.max_deletes = None;
+        self.max_delete_percent = None;
+    }
+
+    /// A client for the job's account, talking to the given API endpoint
+    /// instead of Mailchimp's when set
+    pub fn client_for(&self, endpoint: Option<&str>) -> Result<mailchimp::Client> {
+        match endpoint {
+            Some(endpoint) => Ok(mailchimp::client::from_api_key_with_endpoint(
+                &self.api_key,
+                endpoint,
+            )?),
+            None => Ok(mailchimp::client::from_api_key(&self.api_key)?),
+        }
     }
 
     async fn db_members(&self, db: &MySqlPool) -> Result<Vec<ddb::members::Member>> {
@@ -176,7 +283,8 @@ impl Job {
         Ok(db_members)
     }
 
-    fn merge_fields(&self) -> Result<mailchimp::merge_fields::MergeFields> {
+    /// The merge fields synced to the job's list, depending on its scope
+    pub fn merge_fields(&self) -> Result<mailchimp::merge_fields::MergeFields> {
         if self.club.is_some() {
             mailchimp::merge_fields::MergeFields::club()
         } else {
@@ -190,18 +298,35 @@ impl Job {
     pub async fn sync_merge_fields(
         &self,
         process_deletes: bool,
+        endpoint: Option<&str>,
     ) -> Result<(Vec<String>, Vec<String>, Vec<String>)> {
-        let client = self.client()?;
+        let client = self.client_for(endpoint)?;
         mailchimp::merge_fields::sync(&client, &self.list, self.merge_fields()?, process_deletes)
             .map_err(Error::from)
             .await
     }
 
-    /// Run sync for multiple jobs in parallel, returning results keyed by job ID
-    /// Jobs that fail are logged but don't stop other jobs from syncing
+    /// Create the interest groups members' communication preferences are
+    /// mapped to, returning the names of the created interests
+    #[tracing::instrument(skip_all, name = "interests", fields(name = self.name, id = self.id))]
+    pub async fn sync_interests(&self, endpoint: Option<&str>) -> Result<Vec<String>> {
+        let client = self.client_for(endpoint)?;
+        let groups = mailchimp::interests::InterestGroups::preferences()?;
+        let (_, missing) = mailchimp::interests::lookup(&client, &self.list, &groups).await?;
+        if !missing.is_empty() {
+            mailchimp::interests::sync(&client, &self.list, &groups).await?;
+        }
+        Ok(missing)
+    }
+
+    /// Run sync for multiple jobs in parallel, returning results keyed by job ID.
+    /// Jobs that fail don't stop other jobs from syncing. Every run is
+    /// recorded in the job history.
     pub async fn sync_many(
+        db: &PgPool,
         jobs: Vec<Self>,
         ddb_settings: AciDatabaseSettings,
+        endpoint: Option<&str>,
     ) -> std::collections::HashMap<i64, JobSyncResult> {
         use futures::StreamExt;
 
@@ -209,37 +334,46 @@ impl Job {
             .map(|job| {
                 let ddb_settings = ddb_settings.clone();
                 async move {
-                    let name = job.name.clone();
-                    let id = job.id;
-                    match job.sync(ddb_settings).await {
-                        Ok((deleted, upserted)) => Some((
-                            id,
-                            JobSyncResult {
-                                name,
-                                deleted,
-                                upserted,
-                            },
-                        )),
-                        Err(e) => {
-                            tracing::error!(job_id = id, job_name = name, "sync failed: {e}");
-                            None
-                        }
+                    let started_at = Utc::now();
+                    let outcome = job.sync(ddb_settings, endpoint).await;
+                    if let Err(e) = &outcome {
+                        tracing::error!(job_id = job.id, job_name = job.name, "sync failed: {e:#}");
                     }
+                    if let Err(e) = crate::history::record(db, job.id, started_at, &outcome).await {
+                        tracing::error!(job_id = job.id, "recording run failed: {e}");
+                    }
+                    let result = match outcome {
+                        Ok(sync) => JobSyncResult {
+                            name: job.name,
+                            deleted: sync.deleted,
+                            upserted: sync.upserted,
+                            warnings: sync.warnings,
+                            error: None,
+                        },
+                        Err(e) => JobSyncResult {
+                            name: job.name,
+                            deleted: 0,
+                            upserted: 0,
+                            warnings: vec![],
+                            error: Some(format!("{e:#}")),
+                        },
+                    };
+                    (job.id, result)
                 }
             })
             .buffered(20)
-            .collect::<Vec<_>>()
-            .await
-            .into_iter()
-            .flatten()
             .collect()
+            .await
     }
 
     #[tracing::instrument(skip_all, name = "sync", fields(name = self.name, id = self.id))]
-    pub async fn sync(&self, ddb_url: AciDatabaseSettings) -> Result<(usize, usize)> {
+    pub async fn sync(
+        &self,
+        ddb_url: AciDatabaseSettings,
+        endpoint: Option<&str>,
+    ) -> Result<JobSync> {
         let db = ddb_url.connect().await?;
         let db_members = self.db_members(&db).await?;
-        let merge_fields = self.merge_fields()?;
         tracing::info!("starting sync");
         // Fetch addresses for primary members
         tracing::debug!("querying ddb");
@@ -247,18 +381,57 @@ impl Job {
         let db_addresses =
             ddb::members::mailing_address::for_members(&db, db_members.iter()).await?;
 
+        let client = self.client_for(endpoint)?;
+        let sync = self
+            .sync_members(&client, &db_members, &db_addresses)
+            .await?;
+
+        let duration = start.elapsed().as_secs();
+        tracing::info!(
+            deleted = sync.deleted,
+            upserted = sync.upserted,
+            warnings = sync.warnings.len(),
+            duration,
+            "sync completed"
+        );
+
+        Ok(sync)
+    }
+
+    /// Sync already extracted DDB members and their mailing addresses to the
+    /// job's list using the given client, returning the number of deleted and
+    /// upserted contacts and the operations that failed
+    pub async fn sync_members(
+        &self,
+        client: &mailchimp::Client,
+        db_members: &[ddb::members::Member],
+        db_addresses: &HashMap<u64, ddb::members::Address>,
+    ) -> Result<JobSync> {
+        let merge_fields = self.merge_fields()?;
+
+        tracing::debug!("looking up interest groups");
+        let groups = mailchimp::interests::InterestGroups::preferences()?;
+        let (interests, missing) =
+            mailchimp::interests::lookup(client, &self.list, &groups).await?;
+        if !missing.is_empty() {
+            tracing::warn!(
+                ?missing,
+                "interests missing from the list are left out, sync the merge fields to create them"
+            );
+        }
+
         // Convert ddb members to mailchimp members while injecting address
         let mc_members = ddb::members::mailchimp::to_members_with_address(
-            &db_members,
-            &db_addresses,
+            db_members,
+            db_addresses,
             &merge_fields,
+            &interests,
         )
         .await?;
 
         tracing::debug!("upserting members");
-        let client = self.client()?;
         let upserted = mailchimp::members::upsert_many(
-            &client,
+            client,
             &self.list,
             futures::stream::iter(mc_members),
             RetryPolicy::Retries(3),
@@ -266,26 +439,167 @@ impl Job {
         .await?;
 
         tracing::debug!("deleting removed members");
-        let deleted = mailchimp::members::retain(&client, &self.list, &upserted).await?;
+        let retained = mailchimp::members::retain(
+            client,
+            &self.list,
+            &upserted,
+            self.removal_policy,
+            self.delete_limit(),
+            RetryPolicy::with_retries(3),
+        )
+        .await?;
 
         tracing::debug!("updating tags");
-        let tag_updates = ddb::members::mailchimp::to_tag_updates(&db_members);
-        mailchimp::members::tags::update_many(
-            &client,
+        let tag_updates = ddb::members::mailchimp::to_tag_updates(db_members);
+        let failed_tag_updates = mailchimp::members::tags::update_many(
+            client,
             &self.list,
             &tag_updates,
             RetryPolicy::with_retries(3),
         )
         .await?;
 
-        let duration = start.elapsed().as_secs();
-        tracing::info!(
-            deleted,
-            upserted = upserted.len(),
-            duration,
-            "sync completed"
-        );
+        let warnings = [
+            RunWarning::from_failed("removal", &retained.failed_removals),
+            RunWarning::from_failed("restore", &retained.failed_restores),
+            RunWarning::from_failed("tag update", &failed_tag_updates),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        for warning in &warnings {
+            tracing::warn!(
+                operation = warning.operation,
+                failed = warning.failed,
+                "some operations failed"
+            );
+        }
+
+        Ok(JobSync {
+            deleted: retained.removed,
+            upserted: upserted.len(),
+            warnings,
+        })
+    }
+
+    /// Compute what [`Job::sync`] would change, and what syncing the merge
+    /// fields would, without writing anything to the list
+    #[tracing::instrument(skip_all, name = "plan", fields(name = self.name, id = self.id))]
+    pub async fn plan(
+        &self,
+        ddb_url: AciDatabaseSettings,
+        endpoint: Option<&str>,
+    ) -> Result<JobPlan> {
+        let db = ddb_url.connect().await?;
+        let db_members = self.db_members(&db).await?;
+        let db_addresses =
+            ddb::members::mailing_address::for_members(&db, db_members.iter()).await?;
+        let client = self.client_for(endpoint)?;
+        self.plan_members(&client, &db_members, &db_addresses).await
+    }
 
-        Ok((deleted, upserted.len()))
+    /// Compute what [`Job::sync_members`] would change, using only read
+    /// requests
+    pub async fn plan_members(
+        &self,
+        client: &mailchimp::Client,
+        db_members: &[ddb::members::Member],
+        db_addresses: &HashMap<u64, ddb::members::Address>,
+    ) -> Result<JobPlan> {
+        let merge_fields = self.merge_fields()?;
+        let fields_plan = mailchimp::merge_fields::plan(client, &self.list, &merge_fields).await?;
+
+        let groups = mailchimp::interests::InterestGroups::preferences()?;
+        let (interests, missing) =
+            mailchimp::interests::lookup(client, &self.list, &groups).await?;
+
+        let mc_members = ddb::members::mailchimp::to_members_with_address(
+            db_members,
+            db_addresses,
+            &merge_fields,
+            &interests,
+        )
+        .await?;
+        let tag_updates = ddb::members::mailchimp::to_tag_updates(db_members);
+        let members = mailchimp::members::plan(
+            client,
+            &self.list,
+            &mc_members,
+            &tag_updates,
+            self.removal_policy,
+        )
+        .await?;
+
+        Ok(JobPlan {
+            id: self.id,
+            name: self.name.clone(),
+            list: self.list.clone(),
+            merge_fields: fields_plan,
+            interests: missing,
+            members,
+        })
+    }
+
+    /// Create a draft campaign of the newsletter for the job's list and set
+    /// its content. Nothing is sent.
+    pub async fn create_newsletter(
+        &self,
+        client: &mailchimp::Client,
+        newsletter: &Newsletter,
+    ) -> Result<Campaign> {
+        let recipients = match newsletter.segment {
+            Some(segment_id) => Recipients::segment(&self.list, segment_id),
+            None => Recipients::list(&self.list),
+        };
+        let title = newsletter
+            .title
+            .clone()
+            .unwrap_or_else(|| format!("{}: {}", self.name, newsletter.subject));
+        let campaign = NewCampaign {
+            recipients,
+            settings: CampaignSettings {
+                subject_line: newsletter.subject.clone(),
+                preview_text: newsletter.preview_text.clone(),
+                title,
+                from_name: newsletter.from_name.clone(),
+                reply_to: newsletter.reply_to.clone(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let campaign = mailchimp::campaigns::create(client, &campaign).await?;
+        tracing::info!(campaign = campaign.id, "created newsletter");
+        mailchimp::campaigns::set_content(client, &campaign.id, &newsletter.content).await?;
+        Ok(mailchimp::campaigns::get(client, &campaign.id).await?)
+    }
+
+    /// A campaign of the job's list. Campaigns sent to other lists of the
+    /// same account are rejected, so a job can only act on its own.
+    pub async fn campaign(
+        &self,
+        client: &mailchimp::Client,
+        campaign_id: &str,
+    ) -> Result<Campaign> {
+        let campaign = mailchimp::campaigns::get(client, campaign_id).await?;
+        if campaign.recipients.list_id != self.list {
+            anyhow::bail!(
+                "campaign {campaign_id} does not belong to list {} of job {}",
+                self.list,
+                self.id
+            );
+        }
+        Ok(campaign)
+    }
+
+    /// All campaigns of the job's list
+    pub async fn campaigns(&self, client: &mailchimp::Client) -> Result<Vec<Campaign>> {
+        use futures::TryStreamExt;
+        let query = CampaignsQuery {
+            list_id: Some(self.list.clone()),
+            ..Default::default()
+        };
+        Ok(mailchimp::campaigns::all(client, query)
+            .try_collect()
+            .await?)
     }
 }
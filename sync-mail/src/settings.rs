@@ -34,6 +34,38 @@ fn default_log() -> String {
 pub struct MailSettings {
     #[serde(default)]
     pub db: DatabaseSettings,
+    /// Talk to a non-default Mailchimp API endpoint, like a local stand-in
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub webhook: WebhookSettings,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebhookSettings {
+    /// The shared secret Mailchimp has to pass in the `secret` query parameter
+    #[serde(default)]
+    pub secret: String,
+    /// The address the webhook receiver listens on
+    #[serde(default = "default_webhook_bind")]
+    pub bind: String,
+    /// The public base url of the receiver, used when registering webhooks
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self {
+            secret: String::new(),
+            bind: default_webhook_bind(),
+            url: None,
+        }
+    }
+}
+
+fn default_webhook_bind() -> String {
+    "0.0.0.0:8080".to_string()
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -0,0 +1,234 @@
+//! Receiver for Mailchimp list webhooks.
+//!
+//! Mailchimp posts member events to `/webhooks/{job id}?secret=...`. Events
+//! are recorded per sync job in `mailchimp_webhook_events`, and the last known
+//! state of each contact is kept in `mailchimp_members`, so unsubscribes and
+//! bounces in Mailchimp become visible on our side.
+
+use crate::{Error, Result, mailchimp::Job};
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use futures::TryFutureExt;
+use mailchimp::webhooks::{WebhookEvent, WebhookEventType};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+#[derive(Clone)]
+struct ReceiverState {
+    db: PgPool,
+    secret: String,
+}
+
+/// The webhook receiver routes. Requests without the shared secret are
+/// rejected before anything else is looked at.
+pub fn router(db: PgPool, secret: &str) -> Router {
+    Router::new()
+        .route("/webhooks/{job_id}", get(validate).post(receive))
+        .with_state(ReceiverState {
+            db,
+            secret: secret.to_string(),
+        })
+}
+
+/// Compare secrets in constant time
+fn verify_secret(expected: &str, query: &HashMap<String, String>) -> bool {
+    let Some(given) = query.get("secret") else {
+        return false;
+    };
+    !expected.is_empty()
+        && given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Mailchimp checks a webhook url with a `GET` request when it is registered
+async fn validate(
+    State(state): State<ReceiverState>,
+    Query(query): Query<HashMap<String, String>>,
+) -> StatusCode {
+    if verify_secret(&state.secret, &query) {
+        StatusCode::OK
+    } else {
+        StatusCode::UNAUTHORIZED
+    }
+}
+
+async fn receive(
+    State(state): State<ReceiverState>,
+    Path(job_id): Path<i64>,
+    Query(query): Query<HashMap<String, String>>,
+    body: Bytes,
+) -> StatusCode {
+    if !verify_secret(&state.secret, &query) {
+        tracing::warn!(job_id, "webhook with invalid secret");
+        return StatusCode::UNAUTHORIZED;
+    }
+    let event = match WebhookEvent::parse(&body) {
+        Ok(event) => event,
+        Err(e) => {
+            tracing::warn!(job_id, "invalid webhook event: {e}");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    let job = match Job::get(&state.db, job_id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!(job_id, "loading sync job failed: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+    if event.list_id() != job.list {
+        tracing::warn!(job_id, list = event.list_id(), "webhook for another list");
+        return StatusCode::BAD_REQUEST;
+    }
+    match record(&state.db, &job, &event).await {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            tracing::error!(job_id, "recording webhook event failed: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Record a member event of the job's list and update the last known state
+/// of the contact. Events without a contact, like campaign sends, are
+/// ignored.
+pub async fn record(db: &PgPool, job: &Job, event: &WebhookEvent) -> Result<()> {
+    let (Some(member_id), Some(email)) = (event.member_id(), event.email()) else {
+        tracing::debug!(
+            job_id = job.id,
+            r#type = event.r#type.as_str(),
+            "ignoring event"
+        );
+        return Ok(());
+    };
+    let data = serde_json::to_string(&event.data)?;
+    let merges = serde_json::to_string(&event.merges())?;
+    let status = match event.r#type {
+        WebhookEventType::Subscribe => Some("subscribed"),
+        WebhookEventType::Unsubscribe => Some("unsubscribed"),
+        WebhookEventType::Cleaned => Some("cleaned"),
+        _ => None,
+    };
+
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        r#"
+        insert into mailchimp_webhook_events
+            (job_id, member_id, type, email, old_email, reason, data, fired_at)
+        values ($1, $2, $3, $4, $5, $6, $7::jsonb, $8)
+        "#,
+    )
+    .bind(job.id)
+    .bind(&member_id)
+    .bind(event.r#type.as_str())
+    .bind(email)
+    .bind(event.old_email())
+    .bind(event.reason())
+    .bind(&data)
+    .bind(event.fired_at)
+    .execute(&mut *tx)
+    .await?;
+
+    // A changed address changes the member id, carry the known state over
+    if let Some(old_email) = event.old_email() {
+        sqlx::query(
+            r#"
+            update mailchimp_members set member_id = $3, email = $4, updated_at = now()
+            where job_id = $1 and member_id = $2
+              and not exists (select 1 from mailchimp_members where job_id = $1 and member_id = $3)
+            "#,
+        )
+        .bind(job.id)
+        .bind(mailchimp::members::member_id(old_email))
+        .bind(&member_id)
+        .bind(email)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query(
+        r#"
+        insert into mailchimp_members (job_id, member_id, email, status, reason, merges)
+        values ($1, $2, $3, $4, $5, $6::jsonb)
+        on conflict (job_id, member_id) do update set
+            email = excluded.email,
+            status = coalesce(excluded.status, mailchimp_members.status),
+            reason = case when excluded.status is null
+                then mailchimp_members.reason else excluded.reason end,
+            merges = case when excluded.merges = '{}'::jsonb
+                then mailchimp_members.merges else excluded.merges end,
+            updated_at = now()
+        "#,
+    )
+    .bind(job.id)
+    .bind(&member_id)
+    .bind(email)
+    .bind(status)
+    .bind(event.reason())
+    .bind(&merges)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().map_err(Error::from).await
+}
+
+/// A recorded webhook event
+#[derive(Debug, sqlx::FromRow, Clone, serde::Serialize)]
+pub struct EventRecord {
+    pub id: i64,
+    pub job_id: i64,
+    pub member_id: String,
+    pub r#type: String,
+    pub email: String,
+    pub old_email: Option<String>,
+    pub reason: Option<String>,
+    pub fired_at: Option<DateTime<Utc>>,
+    pub received_at: DateTime<Utc>,
+}
+
+/// The most recent events of a job, optionally for a single contact
+pub async fn events(
+    db: &PgPool,
+    job_id: i64,
+    member_id: Option<&str>,
+    limit: i64,
+) -> Result<Vec<EventRecord>> {
+    sqlx::query_as(
+        r#"
+        select id, job_id, member_id, type, email, old_email, reason, fired_at, received_at
+        from mailchimp_webhook_events
+        where job_id = $1 and ($2::text is null or member_id = $2)
+        order by received_at desc, id desc
+        limit $3
+        "#,
+    )
+    .bind(job_id)
+    .bind(member_id)
+    .bind(limit)
+    .fetch_all(db)
+    .map_err(Error::from)
+    .await
+}
+
+/// The url Mailchimp posts a job's events to, below the receiver's public
+/// base url
+pub fn url(base: &str, job_id: i64, secret: &str) -> String {
+    let mut url = format!("{}/webhooks/{job_id}?", base.trim_end_matches('/'));
+    url.push_str(
+        &url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("secret", secret)
+            .finish(),
+    );
+    url
+}
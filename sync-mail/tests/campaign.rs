@@ -0,0 +1,76 @@
+use mailchimp::{
+    campaigns::{CampaignStatus, Content},
+    members::{Member, MemberStatus},
+    testing::{API_KEY, Server},
+};
+use sync_mail::mailchimp::{Job, Newsletter};
+
+fn newsletter(segment: Option<u64>) -> Newsletter {
+    Newsletter {
+        subject: "Spring rally".to_string(),
+        preview_text: String::new(),
+        title: None,
+        from_name: "Silver Bullets".to_string(),
+        reply_to: "secretary@airstream.test".to_string(),
+        segment,
+        content: Content::Html("<p>See you in Jackson Center</p>".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn newsletter_for_job_list() {
+    let server = Server::start().await.unwrap();
+    let job = Job {
+        id: 1,
+        api_key: API_KEY.to_string(),
+        name: "Silver Bullets".to_string(),
+        list: server.create_list("Silver Bullets"),
+        club: Some(7),
+        ..Default::default()
+    };
+    let client = job.client_for(Some(&server.endpoint())).unwrap();
+    server.insert_member(
+        &job.list,
+        &Member {
+            email_address: "wally@airstream.test".to_string(),
+            status: Some(MemberStatus::Subscribed),
+            ..Default::default()
+        },
+    );
+
+    let campaign = job
+        .create_newsletter(&client, &newsletter(None))
+        .await
+        .unwrap();
+    assert_eq!(campaign.status, CampaignStatus::Save);
+    assert_eq!(campaign.recipients.list_id, job.list);
+    assert_eq!(campaign.settings.title, "Silver Bullets: Spring rally");
+    let content = mailchimp::campaigns::content(&client, &campaign.id)
+        .await
+        .unwrap();
+    assert_eq!(content.html, "<p>See you in Jackson Center</p>");
+
+    mailchimp::campaigns::send_test(&client, &campaign.id, &["me@airstream.test".into()])
+        .await
+        .unwrap();
+    assert!(server.deliveries(&campaign.id).is_empty());
+
+    let status = job.campaign(&client, &campaign.id).await.unwrap();
+    assert_eq!(status.status, CampaignStatus::Save);
+    assert_eq!(job.campaigns(&client).await.unwrap().len(), 1);
+
+    // Campaigns of other lists in the same account are off limits
+    let other = Job {
+        list: server.create_list("Wally Byam Caravan"),
+        ..job.clone()
+    };
+    assert!(other.campaign(&client, &campaign.id).await.is_err());
+    assert!(other.campaigns(&client).await.unwrap().is_empty());
+
+    // Unknown segments of the list are rejected
+    assert!(
+        job.create_newsletter(&client, &newsletter(Some(9)))
+            .await
+            .is_err()
+    );
+}
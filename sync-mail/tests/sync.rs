@@ -0,0 +1,374 @@
+use ddb::{
+    clubs::Club,
+    members::{Address, Member, MemberClass, MemberStatus, MemberType},
+    users::User,
+};
+use mailchimp::{members::member_id, testing::Server};
+use std::collections::HashMap;
+use sync_mail::mailchimp::Job;
+
+fn user(uid: u64, email: &str, first_name: &str) -> User {
+    User {
+        uid,
+        email: email.to_string(),
+        first_name: Some(first_name.to_string()),
+        last_name: Some("Airstreamer".to_string()),
+        birthday: None,
+        last_login: None,
+        pass: None,
+        gender: None,
+        race_tid: None,
+        communication_preference: None,
+        blue_beret_mail: None,
+        publish_info: None,
+        special_needs: None,
+        ada_parking: None,
+        member_notes: None,
+        military_status: None,
+        first_responder_status: None,
+        active: true,
+    }
+}
+
+fn member(primary: User, partner: Option<User>, member_status: MemberStatus) -> Member {
+    Member {
+        member_class: MemberClass::Regular,
+        member_type: MemberType::Regular,
+        member_status,
+        primary,
+        partner,
+        expiration_date: None,
+        join_date: None,
+        local_club: Club {
+            uid: 7,
+            number: Some(42),
+            name: "Silver Bullets".to_string(),
+            region: Some(3),
+            active: true,
+        },
+        brns: vec!["1234".to_string()],
+    }
+}
+
+fn address(uid: u64) -> Address {
+    Address {
+        user_id: Some(uid),
+        street_address: Some("1 Riveted Way".to_string()),
+        street_address_2: None,
+        zip_code: Some("45334".to_string()),
+        city: Some("Jackson Center".to_string()),
+        state: Some("OH".to_string()),
+        country: Some("US".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn sync_members_against_stand_in() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let job = Job {
+        id: 1,
+        name: "club".to_string(),
+        list: server.create_list("Silver Bullets"),
+        club: Some(7),
+        ..Default::default()
+    };
+
+    mailchimp::merge_fields::sync(&client, &job.list, job.merge_fields().unwrap(), true)
+        .await
+        .unwrap();
+    let groups = mailchimp::interests::InterestGroups::preferences().unwrap();
+    let interests = mailchimp::interests::sync(&client, &job.list, &groups)
+        .await
+        .unwrap();
+
+    let mut wally = user(1, "wally@airstream.test", "Wally");
+    wally.communication_preference = Some("email".to_string());
+    wally.blue_beret_mail = Some(true);
+    let members = vec![
+        member(
+            wally,
+            Some(user(2, "stella@airstream.test", "Stella")),
+            MemberStatus::Current,
+        ),
+        member(
+            user(3, "lapsed@airstream.test", "Larry"),
+            None,
+            MemberStatus::Lapsed,
+        ),
+    ];
+    let addresses = HashMap::from([(1, address(1)), (3, address(3))]);
+
+    let sync = job
+        .sync_members(&client, &members, &addresses)
+        .await
+        .unwrap();
+    assert_eq!((sync.deleted, sync.upserted), (0, 3));
+    assert!(sync.warnings.is_empty());
+
+    let wally = server
+        .member(&job.list, &member_id("wally@airstream.test"))
+        .unwrap();
+    let fields = wally.merge_fields.unwrap();
+    assert_eq!(fields["FNAME"], "Wally");
+    assert_eq!(fields["BRN"], "1234");
+    assert_eq!(fields["STATE"], "OH");
+    assert_eq!(fields["MCLASS"], "Regular");
+    assert_eq!(fields["ADDRESS"]["addr1"], "1 Riveted Way");
+    assert_eq!(fields["ADDRESS"]["city"], "Jackson Center");
+    assert_eq!(fields["ADDRESS"]["zip"], "45334");
+    assert!(fields["ADDRESS"].get("addr2").is_none());
+
+    let selected = wally.interests.unwrap();
+    assert_eq!(selected.len(), 3);
+    assert!(selected[&interests["Email only"]]);
+    assert!(!selected[&interests["Email and print"]]);
+    assert!(selected[&interests["Blue Beret print"]]);
+
+    let stella = server
+        .member(&job.list, &member_id("stella@airstream.test"))
+        .unwrap();
+    assert!(stella.interests.is_none());
+    assert_eq!(
+        stella.merge_fields.unwrap()["PRIMARY"],
+        "wally@airstream.test"
+    );
+
+    assert_eq!(
+        server.tags(&job.list, &member_id("wally@airstream.test")),
+        ["member"]
+    );
+    let mut lapsed_tags = server.tags(&job.list, &member_id("lapsed@airstream.test"));
+    lapsed_tags.sort();
+    assert_eq!(lapsed_tags, ["lapsed", "member"]);
+
+    // Members dropped from the DDB are removed on the next run
+    let sync = job
+        .sync_members(&client, &members[..1], &addresses)
+        .await
+        .unwrap();
+    assert_eq!((sync.deleted, sync.upserted), (1, 2));
+    let lapsed = server
+        .member(&job.list, &member_id("lapsed@airstream.test"))
+        .unwrap();
+    assert_eq!(
+        lapsed.status,
+        Some(mailchimp::members::MemberStatus::Archived)
+    );
+
+    // Jobs can keep removed members as unsubscribed contacts instead
+    let job = Job {
+        removal_policy: mailchimp::members::RemovalPolicy::Unsubscribe,
+        ..job
+    };
+    let sync = job
+        .sync_members(&client, &members, &addresses)
+        .await
+        .unwrap();
+    assert_eq!(sync.deleted, 0);
+    let sync = job
+        .sync_members(&client, &members[..1], &addresses)
+        .await
+        .unwrap();
+    assert_eq!(sync.deleted, 1);
+    let lapsed = server
+        .member(&job.list, &member_id("lapsed@airstream.test"))
+        .unwrap();
+    assert_eq!(
+        lapsed.status,
+        Some(mailchimp::members::MemberStatus::Unsubscribed)
+    );
+    let mut lapsed_tags = server.tags(&job.list, &member_id("lapsed@airstream.test"));
+    lapsed_tags.sort();
+    assert_eq!(lapsed_tags, ["lapsed", "member", "removed"]);
+
+    // Removed members that come back are subscribed again
+    job.sync_members(&client, &members, &addresses)
+        .await
+        .unwrap();
+    let lapsed = server
+        .member(&job.list, &member_id("lapsed@airstream.test"))
+        .unwrap();
+    assert_eq!(
+        lapsed.status,
+        Some(mailchimp::members::MemberStatus::Subscribed)
+    );
+    let mut lapsed_tags = server.tags(&job.list, &member_id("lapsed@airstream.test"));
+    lapsed_tags.sort();
+    assert_eq!(lapsed_tags, ["lapsed", "member"]);
+
+    // Members who opted out meanwhile can't be subscribed again, the sync
+    // reports them
+    job.sync_members(&client, &members[..1], &addresses)
+        .await
+        .unwrap();
+    server.opt_out(&job.list, &member_id("lapsed@airstream.test"));
+    let sync = job
+        .sync_members(&client, &members, &addresses)
+        .await
+        .unwrap();
+    assert_eq!(sync.warnings.len(), 1);
+    assert_eq!(sync.warnings[0].operation, "restore");
+    assert_eq!(sync.warnings[0].failed, 1);
+    assert!(sync.warnings[0].details[0].starts_with(&member_id("lapsed@airstream.test")));
+}
+
+#[tokio::test]
+async fn tag_intraclub_members() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let job = Job {
+        id: 3,
+        name: "club".to_string(),
+        list: server.create_list("Silver Bullets"),
+        club: Some(7),
+        ..Default::default()
+    };
+    mailchimp::merge_fields::sync(&client, &job.list, job.merge_fields().unwrap(), true)
+        .await
+        .unwrap();
+
+    let mut intraclub = member(
+        user(4, "intraclub@airstream.test", "Ivy"),
+        None,
+        MemberStatus::Current,
+    );
+    intraclub.member_type = MemberType::Intraclub;
+    job.sync_members(&client, &[intraclub], &HashMap::new())
+        .await
+        .unwrap();
+
+    let mut tags = server.tags(&job.list, &member_id("intraclub@airstream.test"));
+    tags.sort();
+    assert_eq!(tags, ["intraclub", "member"]);
+}
+
+#[tokio::test]
+async fn sync_leaves_interest_groups_alone() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let job = Job {
+        id: 4,
+        name: "club".to_string(),
+        list: server.create_list("Silver Bullets"),
+        club: Some(7),
+        ..Default::default()
+    };
+    mailchimp::merge_fields::sync(&client, &job.list, job.merge_fields().unwrap(), true)
+        .await
+        .unwrap();
+
+    let mut wally = user(1, "wally@airstream.test", "Wally");
+    wally.communication_preference = Some("email".to_string());
+    job.sync_members(
+        &client,
+        &[member(wally, None, MemberStatus::Current)],
+        &HashMap::new(),
+    )
+    .await
+    .unwrap();
+
+    let groups = mailchimp::interests::InterestGroups::preferences().unwrap();
+    let (interests, _) = mailchimp::interests::lookup(&client, &job.list, &groups)
+        .await
+        .unwrap();
+    assert!(interests.is_empty());
+    let wally = server
+        .member(&job.list, &member_id("wally@airstream.test"))
+        .unwrap();
+    assert!(wally.interests.is_none());
+}
+
+#[tokio::test]
+async fn plan_without_changes() {
+    let server = Server::start().await.unwrap();
+    let client = server.client();
+    let job = Job {
+        id: 2,
+        name: "club".to_string(),
+        list: server.create_list("Silver Bullets"),
+        club: Some(7),
+        ..Default::default()
+    };
+    server.insert_member(
+        &job.list,
+        &mailchimp::members::Member {
+            id: member_id("stranger@airstream.test"),
+            email_address: "stranger@airstream.test".to_string(),
+            status: Some(mailchimp::members::MemberStatus::Subscribed),
+            ..Default::default()
+        },
+    );
+    let mut members = vec![
+        member(
+            user(1, "wally@airstream.test", "Wally"),
+            Some(user(2, "stella@airstream.test", "Stella")),
+            MemberStatus::Current,
+        ),
+        member(
+            user(3, "lapsed@airstream.test", "Larry"),
+            None,
+            MemberStatus::Lapsed,
+        ),
+    ];
+    let addresses = HashMap::from([(1, address(1))]);
+
+    let plan = job
+        .plan_members(&client, &members, &addresses)
+        .await
+        .unwrap();
+    assert!(plan.merge_fields.added.contains(&"MCLASS".to_string()));
+    assert!(plan.merge_fields.deleted.contains(&"PHONE".to_string()));
+    assert!(plan.interests.contains(&"Email only".to_string()));
+    assert_eq!(
+        plan.members.create,
+        [
+            "lapsed@airstream.test",
+            "stella@airstream.test",
+            "wally@airstream.test"
+        ]
+    );
+    assert_eq!(plan.members.remove, ["stranger@airstream.test"]);
+    assert_eq!(plan.members.tags.len(), 3);
+    // Nothing was written
+    assert!(server.batches().is_empty());
+    assert_eq!(server.members(&job.list).len(), 1);
+    assert!(
+        server
+            .merge_fields(&job.list)
+            .iter()
+            .all(|field| field.tag != "MCLASS")
+    );
+
+    mailchimp::merge_fields::sync(&client, &job.list, job.merge_fields().unwrap(), true)
+        .await
+        .unwrap();
+    let groups = mailchimp::interests::InterestGroups::preferences().unwrap();
+    mailchimp::interests::sync(&client, &job.list, &groups)
+        .await
+        .unwrap();
+    job.sync_members(&client, &members, &addresses)
+        .await
+        .unwrap();
+    let plan = job
+        .plan_members(&client, &members, &addresses)
+        .await
+        .unwrap();
+    assert!(plan.merge_fields.is_empty() && plan.interests.is_empty());
+    assert!(plan.members.create.is_empty() && plan.members.remove.is_empty());
+    assert!(plan.members.update.is_empty() && plan.members.tags.is_empty());
+    assert_eq!(plan.members.unchanged, 3);
+
+    members[0].primary.first_name = Some("Walter".to_string());
+    let plan = job
+        .plan_members(&client, &members, &addresses)
+        .await
+        .unwrap();
+    assert_eq!(plan.members.update.len(), 1);
+    let update = &plan.members.update[0];
+    assert_eq!(update.email, "wally@airstream.test");
+    assert_eq!(update.changes.len(), 1);
+    assert_eq!(update.changes[0].field, "FNAME");
+    assert_eq!(update.changes[0].from, "Wally");
+    assert_eq!(update.changes[0].to, "Walter");
+}
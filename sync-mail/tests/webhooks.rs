@@ -0,0 +1,57 @@
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+/// A pool that never connects. Requests rejected before touching the
+/// database don't need one.
+fn pool() -> PgPool {
+    PgPool::connect_lazy("postgres://localhost:1/unused").unwrap()
+}
+
+async fn status(method: Method, uri: &str, body: &str) -> StatusCode {
+    let router = sync_mail::webhooks::router(pool(), "s3cret");
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    router.oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn verifies_shared_secret() {
+    let event = "type=unsubscribe&data%5Blist_id%5D=abc&data%5Bemail%5D=a%40airstream.test";
+
+    assert_eq!(
+        status(Method::GET, "/webhooks/1?secret=s3cret", "").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        status(Method::GET, "/webhooks/1", "").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        status(Method::POST, "/webhooks/1?secret=s3cre", event).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        status(Method::POST, "/webhooks/1?secret=wrong!", event).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        status(Method::POST, "/webhooks/1?secret=s3cret", "type=bogus").await,
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[test]
+fn webhook_url() {
+    assert_eq!(
+        sync_mail::webhooks::url("https://sync.airstream.test/", 4, "a b&c"),
+        "https://sync.airstream.test/webhooks/4?secret=a+b%26c"
+    );
+}